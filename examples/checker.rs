@@ -0,0 +1,57 @@
+// Scene built in code, with an object defined outside of the crate
+#![feature(box_syntax)]
+#![feature(core)]
+
+extern crate rt;
+
+use std::num::Float;
+use std::sync::Arc;
+use rt::{ Vec3, Ray, Inter, Color, Material, Object, Sphere, Sun, Eye, SceneBuilder, PictureBuilder };
+
+// Rays leaving a surface start on it, closer hits are ignored
+const EPSILON: f64 = 0.00001;
+
+// Horizontal checkerboard at height `y`
+struct Checker {
+    y:     f64,
+    size:  f64,
+    mats:  [Arc<Material>; 2],
+}
+
+impl Object for Checker {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        if ray.dir.y == 0. {
+            return None;
+        }
+        let dist = (self.y - ray.pos.y) / ray.dir.y;
+        if dist < EPSILON {
+            return None;
+        }
+        let pos = ray.pos + ray.dir * dist;
+        let cell = ((pos.x / self.size).floor() + (pos.z / self.size).floor()) as i64;
+        let normal = Vec3::new(0., if ray.dir.y < 0. { 1. } else { -1. }, 0.);
+        Some(Inter::new(dist, pos, normal, self.mats[(cell & 1) as usize].clone()))
+    }
+}
+
+fn main() {
+    let white = Arc::new(Material::new(Color::new(0.9, 0.9, 0.9), 0., 1., 0., 1., 0.));
+    let black = Arc::new(Material::new(Color::new(0.1, 0.1, 0.1), 0., 1., 0., 1., 0.2));
+    let red = Arc::new(Material::new(Color::new(0.9, 0.2, 0.2), 0.5, 1., 0., 1., 0.1));
+
+    let scene = SceneBuilder::new()
+        .object(box Checker { y: -20., size: 10., mats: [white, black] })
+        .object(box Sphere::new(Vec3::new(0., 0., 0.), 20., red))
+        .light(box Sun::new(Vec3::new(-1., -2., -1.), 1., 20, 1.))
+        .ambient(0.2)
+        .build();
+    let picture = PictureBuilder::new(320, 240).path("image/checker.png").sample(2).threads(4).build();
+    let eye = Eye::new(Vec3::new(0., 20., 120.), Vec3::new(-0.15, 0., 0.), 2.1);
+
+    // Render in memory, then save
+    let buffer = picture.shot(&eye, &scene, false);
+    let sum = buffer.pixels.iter().fold(Color::new(0., 0., 0.), |acc, &color| acc + color);
+    let n = buffer.pixels.len() as f64;
+    println!("Mean color: {:.3} {:.3} {:.3}", sum.r / n, sum.g / n, sum.b / n);
+    picture.save(&buffer);
+}
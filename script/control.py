@@ -8,35 +8,50 @@ from librt import *
 
 actions = {}
 
-def badd(root, key, value):
-    root[key] += value
-
-def bmove(root, move):
-    actions['w'] = lambda: badd(root['pos'], 'z', -move)
-    actions['s'] = lambda: badd(root['pos'], 'z', +move)
-    actions['a'] = lambda: badd(root['pos'], 'x', -move)
-    actions['d'] = lambda: badd(root['pos'], 'x', +move)
-    actions['q'] = lambda: badd(root['pos'], 'y', +move)
-    actions['e'] = lambda: badd(root['pos'], 'y', -move)
-
-def brot(root, rot):
-    actions['o'] = lambda: badd(root['dir'], 'x', -rot)
-    actions['l'] = lambda: badd(root['dir'], 'x', +rot)
-    actions['k'] = lambda: badd(root['dir'], 'y', +rot)
-    actions[';'] = lambda: badd(root['dir'], 'y', -rot)
-    actions['i'] = lambda: badd(root['dir'], 'z', +rot)
-    actions['p'] = lambda: badd(root['dir'], 'z', -rot)
-
-def bmr(root, move, rot):
-    bmove(root, move)
-    brot(root, rot)
-
-def sscreen(conf, w, h):
-    conf['picture']['w'] = w
-    conf['picture']['h'] = h
+# Fields patched with --set, by dotted path
+sets = {}
+
+def get(conf, path):
+    for key in path.split('.'):
+        conf = conf[key]
+    return conf
+
+def badd(conf, path, value):
+    sets[path] = sets.get(path, get(conf, path)) + value
+
+def bmove(conf, root, move):
+    actions['w'] = lambda: badd(conf, root + '.pos.z', -move)
+    actions['s'] = lambda: badd(conf, root + '.pos.z', +move)
+    actions['a'] = lambda: badd(conf, root + '.pos.x', -move)
+    actions['d'] = lambda: badd(conf, root + '.pos.x', +move)
+    actions['q'] = lambda: badd(conf, root + '.pos.y', +move)
+    actions['e'] = lambda: badd(conf, root + '.pos.y', -move)
+
+def brot(conf, root, rot):
+    actions['o'] = lambda: badd(conf, root + '.dir.x', -rot)
+    actions['l'] = lambda: badd(conf, root + '.dir.x', +rot)
+    actions['k'] = lambda: badd(conf, root + '.dir.y', +rot)
+    actions[';'] = lambda: badd(conf, root + '.dir.y', -rot)
+    actions['i'] = lambda: badd(conf, root + '.dir.z', +rot)
+    actions['p'] = lambda: badd(conf, root + '.dir.z', -rot)
+
+def bmr(conf, root, move, rot):
+    bmove(conf, root, move)
+    brot(conf, root, rot)
+
+def sscreen(w, h):
+    sets['picture.w'] = w
+    sets['picture.h'] = h
 
 def init_actions(conf):
-    bmr(conf['eye'], 5, 0.1)
+    bmr(conf, 'eye', 5, 0.1)
+
+def patched(conf):
+    conf = json.loads(json.dumps(conf))
+    for path, value in sets.items():
+        keys = path.split('.')
+        get(conf, '.'.join(keys[:-1]))[keys[-1]] = value
+    return conf
 
 def control(path_conf):
     with open(path_conf, 'r') as f:
@@ -45,7 +60,7 @@ def control(path_conf):
 
     while True:
         print('Updating image ...')
-        call_rt(conf)
+        call_rt_file(path_conf, sets)
         print('Image updated !')
 
         # Read input
@@ -66,7 +81,7 @@ def control(path_conf):
         elif cmd == 'n':
             path = input('path> ')
             with open(path, 'w+') as f:
-                f.write(json.dumps(conf, indent=4))
+                f.write(json.dumps(patched(conf), indent=4))
         # Actions
         elif cmd in actions:
             try:
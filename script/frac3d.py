@@ -7,6 +7,7 @@ from libfrac import *
 
 def get_config():
     return {
+        "version": 2,
         "picture": {
             "w": 100,
             "h": 100,
@@ -7,6 +7,7 @@ from random import uniform
 
 def get_config():
     return {
+        "version": 2,
         "picture": {
             "w": 200,
             "h": 200,
@@ -18,6 +19,7 @@ def get_config():
             "fov": 2.1
         },
         "scene": {
+            "ambient": 0.2,
             "objects": [
             ],
             "lights": [
@@ -52,27 +54,59 @@ def hexaground():
     MIN_H=1
     MAX_H=8
 
-    for z in range(-COUNT_Z // 2, COUNT_Z // 2):
-        for x in range(-COUNT_X // 2, COUNT_X // 2):
-            hexa = {
-                "aahexa": {
-                    "pos": { "x": x * SZ + (z % 2) * SZ / 2., "y": 0, "z": z * tan(pi / 3) * SZ / 2. },
-                    "x": SZ,
-                    "y": get_height(MIN_H, MAX_H),
-                    "mat": {
-                        "color": get_color(),
-                        "spec": 0.4,
-                        "diff": 1,
-                        "refl": 0.1
-                    }
-                }
+    # The grid shifts its odd rows, the first row here (z = -5) was shifted:
+    # start one row earlier with flat cells, they are not drawn
+    hexgrid = {
+        "hexgrid": {
+            "pos": { "x": -COUNT_X // 2 * SZ, "y": 0, "z": (-COUNT_Z // 2 - 1) * tan(pi / 3) * SZ / 2. },
+            "size": SZ,
+            "cols": COUNT_X,
+            "rows": COUNT_Z + 1,
+            "heights": [0] * COUNT_X + [get_height(MIN_H, MAX_H) for _ in range(COUNT_X * COUNT_Z)],
+            "colors": [get_color() for _ in range(COUNT_X * (COUNT_Z + 1))],
+            "mat": {
+                "color": { "r": 1, "g": 1, "b": 1 },
+                "spec": 0.4,
+                "diff": 1,
+                "refl": 0.1
             }
-            config['scene']['objects'].append(hexa)
+        }
+    }
+    config['scene']['objects'].append(hexgrid)
+    return config
+
+# Render in process with the bindings, without going through JSON
+def render(config):
+    import png
+    import numpy
+    from pyrt import Scene, Material
+
+    def vec(v):
+        return (v['x'], v['y'], v['z'])
+
+    def color(c):
+        return (c['r'], c['g'], c['b'])
+
+    scene = Scene()
+    grid = config['scene']['objects'][0]['hexgrid']
+    m = grid['mat']
+    mat = Material(color(m['color']), spec=m['spec'], diff=m['diff'], refl=m['refl'])
+    scene.hexgrid(vec(grid['pos']), grid['size'], grid['cols'], grid['rows'], grid['heights'], mat,
+                  colors=[color(c) for c in grid['colors']])
+    bulb = config['scene']['lights'][0]['bulb']
+    scene.bulb(vec(bulb['pos']), spec=bulb['spec'], shin=bulb['shin'], diff=bulb['diff'])
 
-    print(json.dumps(config, indent=4))
+    eye, pic = config['eye'], config['picture']
+    img = scene.render(vec(eye['pos']), vec(eye['dir']), eye['fov'], pic['w'], pic['h'])
+    img = numpy.clip(img * 255, 0, 255).astype(numpy.uint8)
+    png.from_array(img.reshape(pic['h'], pic['w'] * 3), 'RGB').save(pic['path'])
 
 if __name__ == '__main__':
-    if len(sys.argv) != 1:
-        print('Usage: ./hexaground.py')
+    if len(sys.argv) > 2 or (len(sys.argv) == 2 and sys.argv[1] != '--render'):
+        print('Usage: ./hexaground.py [--render]')
         sys.exit(1)
-    hexaground()
+    config = hexaground()
+    if len(sys.argv) == 2:
+        render(config)
+    else:
+        print(json.dumps(config, indent=4))
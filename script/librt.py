@@ -5,6 +5,12 @@ import tty, sys, termios
 def call_rt(conf):
     subprocess.check_output(['cargo run --release'], input=json.dumps(conf).encode('utf-8'), shell=True)
 
+def call_rt_file(path, sets):
+    args = ['cargo', 'run', '--release', '--', '--quiet', path]
+    for key, value in sorted(sets.items()):
+        args += ['--set', '{}={}'.format(key, json.dumps(value))]
+    subprocess.check_output(args)
+
 class ReadChar():
     def __enter__(self):
         self.fd = sys.stdin.fileno()
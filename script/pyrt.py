@@ -0,0 +1,144 @@
+import os
+import ctypes
+import numpy
+
+# Bindings to the C interface of the rt library (src/capi.rs)
+# Build it first with `cargo build --release`
+
+_path = os.path.join(os.path.dirname(os.path.abspath(__file__)), '..', 'target', 'release', 'librt.so')
+_lib = ctypes.CDLL(os.environ.get('LIBRT', _path))
+
+_vec = ctypes.POINTER(ctypes.c_double)
+_handle = ctypes.c_void_p
+
+def _proto(name, args, res=None):
+    f = getattr(_lib, name)
+    f.argtypes = args
+    f.restype = res
+    return f
+
+_material_new = _proto('rt_material_new', [_vec] + [ctypes.c_double] * 5, _handle)
+_material_free = _proto('rt_material_free', [_handle])
+_builder_new = _proto('rt_builder_new', [], _handle)
+_builder_free = _proto('rt_builder_free', [_handle])
+_builder_sphere = _proto('rt_builder_sphere', [_handle, _vec, ctypes.c_double, _handle])
+_builder_plane = _proto('rt_builder_plane', [_handle, _vec, _vec, _handle])
+_builder_box = _proto('rt_builder_box', [_handle, _vec, _vec, _handle])
+_builder_cylinder = _proto('rt_builder_cylinder', [_handle, _vec, _vec, ctypes.c_double, ctypes.c_double,
+                                                   ctypes.c_int, _handle])
+_builder_polygon = _proto('rt_builder_polygon', [_handle, _vec, ctypes.c_size_t, _handle], ctypes.c_int)
+_builder_heightfield = _proto('rt_builder_heightfield', [_handle, _vec, _vec, ctypes.c_size_t, ctypes.c_size_t,
+                                                         _vec, _handle], ctypes.c_int)
+_builder_hexgrid = _proto('rt_builder_hexgrid', [_handle, _vec, ctypes.c_double, ctypes.c_size_t, ctypes.c_size_t,
+                                                 _vec, _vec, _handle], ctypes.c_int)
+_builder_bulb = _proto('rt_builder_bulb', [_handle, _vec, ctypes.c_double, ctypes.c_int, ctypes.c_double])
+_builder_sun = _proto('rt_builder_sun', [_handle, _vec, ctypes.c_double, ctypes.c_int, ctypes.c_double])
+_builder_ambient = _proto('rt_builder_ambient', [_handle, ctypes.c_double])
+_builder_back = _proto('rt_builder_back', [_handle, _vec])
+_builder_build = _proto('rt_builder_build', [_handle], _handle)
+_scene_free = _proto('rt_scene_free', [_handle])
+_render = _proto('rt_render', [_handle, _vec, _vec, ctypes.c_double, ctypes.c_uint, ctypes.c_uint,
+                               ctypes.c_uint, ctypes.c_uint, ctypes.c_uint, _vec], ctypes.c_int)
+
+def _doubles(values):
+    values = [float(v) for v in values]
+    return (ctypes.c_double * len(values))(*values)
+
+def _vec3(v):
+    if len(v) != 3:
+        raise ValueError('expected 3 values, got %d' % len(v))
+    return _doubles(v)
+
+def _flat(points):
+    return _doubles([c for p in points for c in _vec3(p)])
+
+# The library returns -1 when it rejects its input
+def _check(code, what):
+    if code != 0:
+        raise RuntimeError('%s failed' % what)
+
+class Material():
+    def __init__(self, color, spec=0, diff=1, refr=0, refr_idx=1, refl=0):
+        self.handle = _material_new(_vec3(color), spec, diff, refr, refr_idx, refl)
+
+    def __del__(self):
+        _material_free(self.handle)
+
+class Scene():
+    """Objects and lights added one by one, built on the first render"""
+
+    def __init__(self):
+        self.builder = _builder_new()
+        self.scene = None
+
+    def __del__(self):
+        if self.builder:
+            _builder_free(self.builder)
+        if self.scene:
+            _scene_free(self.scene)
+
+    def _add(self):
+        if self.scene:
+            raise RuntimeError('the scene was already rendered, create a new one')
+        return self.builder
+
+    def sphere(self, pos, radius, mat):
+        _builder_sphere(self._add(), _vec3(pos), radius, mat.handle)
+
+    def plane(self, pos, normal, mat):
+        _builder_plane(self._add(), _vec3(pos), _vec3(normal), mat.handle)
+
+    def box(self, pos, dim, mat):
+        _builder_box(self._add(), _vec3(pos), _vec3(dim), mat.handle)
+
+    def cylinder(self, pos, axis, radius, height, mat, capped=True):
+        _builder_cylinder(self._add(), _vec3(pos), _vec3(axis), radius, height, int(capped), mat.handle)
+
+    def polygon(self, points, mat):
+        if len(points) < 3:
+            raise ValueError('a polygon needs at least 3 points, got %d' % len(points))
+        _check(_builder_polygon(self._add(), _flat(points), len(points), mat.handle), 'polygon')
+
+    # heights: sequence of nx * nz values, or array of shape (nz, nx)
+    def heightfield(self, pos, size, nx, nz, heights, mat):
+        heights = numpy.asarray(heights, dtype=float).ravel()
+        if nx < 2 or nz < 2:
+            raise ValueError('a heightfield needs at least 2x2 heights')
+        if len(heights) != nx * nz:
+            raise ValueError('expected nx * nz = %d heights, got %d' % (nx * nz, len(heights)))
+        _check(_builder_heightfield(self._add(), _vec3(pos), _vec3(size), nx, nz, _doubles(heights), mat.handle),
+               'heightfield')
+
+    # colors: None, or one (r, g, b) per cell
+    def hexgrid(self, pos, size, cols, rows, heights, mat, colors=None):
+        if cols < 1 or rows < 1:
+            raise ValueError('a hexgrid needs at least one cell')
+        if len(heights) != cols * rows:
+            raise ValueError('expected cols * rows = %d heights, got %d' % (cols * rows, len(heights)))
+        if colors is not None and len(colors) != cols * rows:
+            raise ValueError('expected cols * rows = %d colors, got %d' % (cols * rows, len(colors)))
+        colors = _flat(colors) if colors is not None else None
+        _check(_builder_hexgrid(self._add(), _vec3(pos), size, cols, rows, _doubles(heights), colors, mat.handle),
+               'hexgrid')
+
+    def bulb(self, pos, spec=1, shin=20, diff=1):
+        _builder_bulb(self._add(), _vec3(pos), spec, shin, diff)
+
+    def sun(self, dir, spec=1, shin=20, diff=1):
+        _builder_sun(self._add(), _vec3(dir), spec, shin, diff)
+
+    def ambient(self, ambient):
+        _builder_ambient(self._add(), ambient)
+
+    def back(self, color):
+        _builder_back(self._add(), _vec3(color))
+
+    # Linear colors, array of shape (h, w, 3)
+    def render(self, pos, dir, fov, w, h, sample=1, bounce=5, threads=1):
+        if not self.scene:
+            self.scene = _builder_build(self.builder)
+            self.builder = None
+        out = numpy.zeros((h, w, 3))
+        _check(_render(self.scene, _vec3(pos), _vec3(dir), fov, w, h, sample, bounce, threads,
+                       out.ctypes.data_as(_vec)), 'render')
+        return out
@@ -0,0 +1,129 @@
+use std::num::Float;
+use std::f64::INFINITY;
+use std::sync::Arc;
+use vec::Vec3;
+use material::{ Color, Material };
+
+static DEPTH: [&'static str; 1] = ["Z"];
+static XYZ:   [&'static str; 3] = ["X", "Y", "Z"];
+static RGB:   [&'static str; 3] = ["R", "G", "B"];
+static GRAY:  [&'static str; 1] = ["Y"];
+static NAMES: [&'static str; 8] = ["depth", "normal", "albedo", "object", "material", "direct", "indirect", "shadow"];
+
+/// Arbitrary output variables, rendered next to the picture
+#[derive(Copy, PartialEq)]
+pub enum Pass {
+    Depth,
+    Normal,
+    Albedo,
+    Object,
+    Material,
+    Direct,
+    Indirect,
+    Shadow,
+}
+
+impl Pass {
+    /// Pass of one of `names`, panics on an unknown name
+    pub fn from_name(name: &str) -> Pass {
+        match name {
+            "depth"    => Pass::Depth,
+            "normal"   => Pass::Normal,
+            "albedo"   => Pass::Albedo,
+            "object"   => Pass::Object,
+            "material" => Pass::Material,
+            "direct"   => Pass::Direct,
+            "indirect" => Pass::Indirect,
+            "shadow"   => Pass::Shadow,
+            _          => panic!("Unknown pass: {}", name),
+        }
+    }
+
+    /// Every name accepted by `from_name`
+    pub fn names() -> &'static [&'static str] {
+        &NAMES
+    }
+
+    /// Name of the pass, the suffix of its file
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Pass::Depth    => "depth",
+            Pass::Normal   => "normal",
+            Pass::Albedo   => "albedo",
+            Pass::Object   => "object",
+            Pass::Material => "material",
+            Pass::Direct   => "direct",
+            Pass::Indirect => "indirect",
+            Pass::Shadow   => "shadow",
+        }
+    }
+
+    // Channel names in a multi-layer file
+    #[doc(hidden)]
+    pub fn channels(&self) -> &'static [&'static str] {
+        match *self {
+            Pass::Depth                                  => &DEPTH,
+            Pass::Normal                                 => &XYZ,
+            Pass::Albedo | Pass::Direct | Pass::Indirect => &RGB,
+            Pass::Object | Pass::Material | Pass::Shadow => &GRAY,
+        }
+    }
+
+    // Raw value, a single channel is stored in red
+    #[doc(hidden)]
+    pub fn value(&self, aov: &Option<Aov>, mat_id: f64) -> Color {
+        let aov = match *aov {
+            Some(ref aov) => aov,
+            None          => return match *self {
+                Pass::Depth => Color::new(INFINITY, 0., 0.),
+                _           => Color::new(0., 0., 0.),
+            },
+        };
+        let gray = |x: f64| Color::new(x, x, x);
+        match *self {
+            Pass::Depth    => gray(aov.depth),
+            Pass::Normal   => Color::new(aov.normal.x, aov.normal.y, aov.normal.z),
+            Pass::Albedo   => aov.albedo,
+            Pass::Object   => gray(aov.object as f64),
+            Pass::Material => gray(mat_id),
+            Pass::Direct   => aov.direct,
+            Pass::Indirect => aov.indirect,
+            Pass::Shadow   => gray(aov.shadow),
+        }
+    }
+
+    // Map raw values to something viewable in [0, 1]
+    #[doc(hidden)]
+    pub fn display(&self, raw: Color, max_depth: f64) -> Color {
+        match *self {
+            Pass::Depth                   => {
+                let x = if raw.r.is_finite() { raw.r / max_depth } else { 1. };
+                Color::new(x, x, x)
+            },
+            Pass::Normal                  => Color::new(raw.r * 0.5 + 0.5, raw.g * 0.5 + 0.5, raw.b * 0.5 + 0.5),
+            Pass::Object | Pass::Material => id_color(raw.r as usize),
+            _                             => raw.normalize(),
+        }
+    }
+}
+
+// Distinct colors for small ids, black for 0
+fn id_color(id: usize) -> Color {
+    if id == 0 {
+        return Color::new(0., 0., 0.);
+    }
+    let h = id as f64 * 0.618033988749895;
+    Color::new(h.fract() * 0.8 + 0.2, (h * 2.).fract() * 0.8 + 0.2, (h * 4.).fract() * 0.8 + 0.2)
+}
+
+// Everything known at the first intersection of a ray
+pub struct Aov {
+    pub depth:    f64,
+    pub normal:   Vec3,
+    pub albedo:   Color,
+    pub object:   usize, // Index in the scene, from 1
+    pub mat:      Arc<Material>,
+    pub direct:   Color, // Light coming straight from the lights
+    pub indirect: Color, // Ambient, sky, reflection and refraction
+    pub shadow:   f64,   // Fraction of the lights hidden
+}
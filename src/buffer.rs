@@ -0,0 +1,67 @@
+use std::num::Float;
+use image::{ self, GenericImage, ImageBuffer, DynamicImage, ImageRgb8 };
+use material::Color;
+
+/// Linear colors of a picture, row by row
+#[derive(Clone)]
+pub struct Buffer {
+    pub w:      u32,
+    pub h:      u32,
+    pub pixels: Vec<Color>,
+}
+
+impl Buffer {
+    /// Black image of `w` by `h` pixels
+    pub fn new(w: u32, h: u32) -> Buffer {
+        Buffer { w: w, h: h, pixels: vec![Color::new(0., 0., 0.); (w * h) as usize] }
+    }
+
+    /// Load an image as linear colors, None if it can not be read
+    pub fn load(path: &Path) -> Option<Buffer> {
+        let img = match image::open(path) {
+            Ok(img) => img.to_rgb(),
+            Err(_)  => return None,
+        };
+        let (w, h) = img.dimensions();
+        let mut buffer = Buffer::new(w, h);
+        for y in 0..h {
+            for x in 0..w {
+                let data = img.get_pixel(x, y).data;
+                buffer.set(x, y, Color::new(data[0] as f64 / 255., data[1] as f64 / 255., data[2] as f64 / 255.));
+            }
+        }
+        Some(buffer)
+    }
+
+    /// Color of a pixel, from the top left corner
+    pub fn get(&self, x: u32, y: u32) -> Color {
+        self.pixels[(y * self.w + x) as usize]
+    }
+
+    /// Change the color of a pixel
+    pub fn set(&mut self, x: u32, y: u32, color: Color) {
+        self.pixels[(y * self.w + x) as usize] = color;
+    }
+
+    // Values of one channel, 0 is red, 1 green and 2 blue
+    #[doc(hidden)]
+    pub fn channel(&self, channel: usize) -> Vec<f64> {
+        self.pixels.iter().map(|color| match channel {
+            0 => color.r,
+            1 => color.g,
+            _ => color.b,
+        }).collect()
+    }
+
+    /// Clamp to [0, 1] and quantize
+    pub fn to_image(&self) -> DynamicImage {
+        let mut raw = Vec::with_capacity(self.pixels.len() * 3);
+        for color in self.pixels.iter() {
+            let color = color.normalize() * 255.;
+            raw.push(color.r.max(0.) as u8);
+            raw.push(color.g.max(0.) as u8);
+            raw.push(color.b.max(0.) as u8);
+        }
+        ImageRgb8(ImageBuffer::from_raw(self.w, self.h, raw).unwrap())
+    }
+}
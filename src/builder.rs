@@ -0,0 +1,239 @@
+// Scenes and pictures built in code, re-exported at the root of the crate with an example
+
+use std::f64::INFINITY;
+use material::Color;
+use object::{ Object, Objects };
+use light::{ Light, Lights };
+use media::{ Media, Medium, Volume };
+use sky::{ Sky, Uniform };
+use aov::Pass;
+use denoise::Denoiser;
+use progressive::Progressive;
+use scene::{ Scene, Occlusion, Picture, Region };
+
+/// Collects objects, lights and environment, then builds a `Scene`
+pub struct SceneBuilder<'a> {
+    objects:   Vec<Box<Object + 'a>>,
+    lights:    Vec<Box<Light + 'a>>,
+    fog:       Option<(Medium, f64)>,
+    volumes:   Vec<Volume>,
+    step:      f64,
+    ambient:   f64,
+    occlusion: Option<Occlusion>,
+    sky:       Option<Box<Sky + 'a>>,
+    ibl:       u32,
+}
+
+impl<'a> SceneBuilder<'a> {
+    /// Empty scene, with the defaults of a JSON scene
+    pub fn new() -> SceneBuilder<'a> {
+        SceneBuilder {
+            objects: vec![],
+            lights: vec![],
+            fog: None,
+            volumes: vec![],
+            step: 1.,
+            ambient: 0.,
+            occlusion: None,
+            sky: None,
+            ibl: 0,
+        }
+    }
+
+    /// Add an object, of this crate or any type implementing `Object`
+    pub fn object(mut self, object: Box<Object + 'a>) -> SceneBuilder<'a> {
+        self.objects.push(object);
+        self
+    }
+
+    /// Add a light, of this crate or any type implementing `Light`
+    pub fn light(mut self, light: Box<Light + 'a>) -> SceneBuilder<'a> {
+        self.lights.push(light);
+        self
+    }
+
+    /// Homogeneous fog, hiding everything past `dist`
+    pub fn fog(mut self, medium: Medium, dist: f64) -> SceneBuilder<'a> {
+        self.fog = Some((medium, dist));
+        self
+    }
+
+    /// Add a bounded participating medium
+    pub fn volume(mut self, volume: Volume) -> SceneBuilder<'a> {
+        self.volumes.push(volume);
+        self
+    }
+
+    /// Ray marching step through the media
+    pub fn march_step(mut self, step: f64) -> SceneBuilder<'a> {
+        self.step = step;
+        self
+    }
+
+    /// Ambient light, none by default
+    pub fn ambient(mut self, ambient: f64) -> SceneBuilder<'a> {
+        self.ambient = ambient;
+        self
+    }
+
+    /// Darken the ambient light with ambient occlusion
+    pub fn occlusion(mut self, occlusion: Occlusion) -> SceneBuilder<'a> {
+        self.occlusion = Some(occlusion);
+        self
+    }
+
+    /// Light coming from infinitely far away, a plain color by default
+    pub fn sky(mut self, sky: Box<Sky + 'a>) -> SceneBuilder<'a> {
+        self.sky = Some(sky);
+        self
+    }
+
+    /// Samples of the sky for image based lighting, 0 to disable it
+    pub fn ibl(mut self, samples: u32) -> SceneBuilder<'a> {
+        self.ibl = samples;
+        self
+    }
+
+    /// Scene of everything added
+    pub fn build(self) -> Scene<'a> {
+        let (fog, fog_dist) = match self.fog {
+            Some((medium, dist)) => (Some(medium), dist),
+            None                 => (None, INFINITY),
+        };
+        let sky = match self.sky {
+            Some(sky) => sky,
+            None      => box Uniform::new(Color::new(0.39, 0.8, 0.92)) as Box<Sky>,
+        };
+        Scene::new(
+            Objects::new(self.objects),
+            Lights::new(self.lights),
+            Media::new(fog, fog_dist, self.volumes, self.step),
+            self.ambient,
+            self.occlusion,
+            sky,
+            self.ibl,
+        )
+    }
+}
+
+/// Size, quality and outputs of a `Picture`
+pub struct PictureBuilder {
+    w:           u32,
+    h:           u32,
+    path:        String,
+    ao:          Option<String>,
+    passes:      Vec<Pass>,
+    exr:         bool,
+    denoiser:    Option<Denoiser>,
+    progressive: Option<Progressive>,
+    crop:        Option<(f64, f64, f64, f64)>,
+    tiles:       Option<(u32, Vec<(u32, u32)>)>,
+    threads:     u32,
+    bounce:      u32,
+    sample:      u32,
+}
+
+impl PictureBuilder {
+    /// Picture of `w` by `h` pixels, saved to "image.png" unless `path` is given
+    pub fn new(w: u32, h: u32) -> PictureBuilder {
+        PictureBuilder {
+            w: w,
+            h: h,
+            path: "image.png".to_string(),
+            ao: None,
+            passes: vec![],
+            exr: false,
+            denoiser: None,
+            progressive: None,
+            crop: None,
+            tiles: None,
+            threads: 1,
+            bounce: 5,
+            sample: 1,
+        }
+    }
+
+    /// Where `Picture::save` writes the image, passes are written next to it
+    pub fn path(mut self, path: &str) -> PictureBuilder {
+        self.path = path.to_string();
+        self
+    }
+
+    /// Where `Picture::save_ao` writes the ambient occlusion pass
+    pub fn ao(mut self, path: &str) -> PictureBuilder {
+        self.ao = Some(path.to_string());
+        self
+    }
+
+    /// Add a pass rendered by `Picture::shot_with_passes`
+    pub fn pass(mut self, pass: Pass) -> PictureBuilder {
+        self.passes.push(pass);
+        self
+    }
+
+    /// Write the passes as layers of a single EXR file
+    pub fn exr(mut self, exr: bool) -> PictureBuilder {
+        self.exr = exr;
+        self
+    }
+
+    /// Filter used by `Picture::denoise`
+    pub fn denoiser(mut self, denoiser: Denoiser) -> PictureBuilder {
+        self.denoiser = Some(denoiser);
+        self
+    }
+
+    /// Render progressively, until the budget is spent
+    pub fn progressive(mut self, progressive: Progressive) -> PictureBuilder {
+        self.progressive = Some(progressive);
+        self
+    }
+
+    /// Render and keep only a window, x, y, w and h in [0, 1]
+    pub fn crop(mut self, x: f64, y: f64, w: f64, h: f64) -> PictureBuilder {
+        self.crop = Some((x, y, w, h));
+        self
+    }
+
+    /// Render only some tiles into the previous image
+    pub fn tiles(mut self, size: u32, tiles: Vec<(u32, u32)>) -> PictureBuilder {
+        self.tiles = Some((size, tiles));
+        self
+    }
+
+    /// Threads rendering the picture
+    pub fn threads(mut self, threads: u32) -> PictureBuilder {
+        self.threads = threads;
+        self
+    }
+
+    /// Depth of reflections and refractions
+    pub fn bounce(mut self, bounce: u32) -> PictureBuilder {
+        self.bounce = bounce;
+        self
+    }
+
+    /// Subpixels per side, for anti-aliasing
+    pub fn sample(mut self, sample: u32) -> PictureBuilder {
+        self.sample = sample;
+        self
+    }
+
+    /// Picture with every setting given
+    pub fn build(self) -> Picture {
+        Picture::new(
+            self.w,
+            self.h,
+            self.path.as_slice(),
+            self.ao.as_ref().map(|ao| ao.as_slice()),
+            self.passes,
+            self.exr,
+            self.denoiser,
+            self.progressive,
+            Region::new(self.crop, self.tiles),
+            self.threads,
+            self.bounce,
+            self.sample,
+        )
+    }
+}
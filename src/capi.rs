@@ -0,0 +1,218 @@
+// C interface of the library, used by the Python bindings in script/pyrt.py
+// Handles are boxed Rust values, freed by their `_free` function
+// Vectors are passed as pointers to 3 doubles
+// Functions that can fail return 0, or -1 when the input is rejected or a panic was caught,
+// panics must not unwind into C
+
+use std::mem;
+use std::slice;
+use std::sync::Arc;
+use std::thread;
+use vec::Vec3;
+use material::{ Color, Material };
+use object::{ Sphere, Plane, AABox, Cylinder, Polygon };
+use heightfield::Heightfield;
+use hexgrid::HexGrid;
+use light::{ Bulb, Sun };
+use sky::Uniform;
+use scene::{ Scene, Eye };
+use builder::{ SceneBuilder, PictureBuilder };
+
+pub type RtMaterial = Arc<Material>;
+pub type RtBuilder = SceneBuilder<'static>;
+pub type RtScene = Scene<'static>;
+
+unsafe fn into_raw<T>(value: Box<T>) -> *mut T {
+    mem::transmute(value)
+}
+
+unsafe fn from_raw<T>(ptr: *mut T) -> Box<T> {
+    mem::transmute(ptr)
+}
+
+unsafe fn vec3(v: *const f64) -> Vec3 {
+    let v = slice::from_raw_parts(v, 3);
+    Vec3::new(v[0], v[1], v[2])
+}
+
+// Run `f`, None after a panic
+fn guard<T, F: FnOnce() -> T + Send + 'static>(f: F) -> Option<T> {
+    thread::catch_panic(f).ok()
+}
+
+// Apply a consuming builder method in place
+unsafe fn update<F: FnOnce(RtBuilder) -> RtBuilder>(builder: *mut RtBuilder, f: F) {
+    let builder = &mut *builder;
+    let old = mem::replace(builder, SceneBuilder::new());
+    *builder = f(old);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rt_material_new(color: *const f64, spec: f64, diff: f64, refr: f64, refr_idx: f64, refl: f64) -> *mut RtMaterial {
+    let color = vec3(color);
+    let mat = Material::new(Color::new(color.x, color.y, color.z), spec, diff, refr, refr_idx, refl);
+    into_raw(box Arc::new(mat))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rt_material_free(mat: *mut RtMaterial) {
+    drop(from_raw(mat));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_new() -> *mut RtBuilder {
+    into_raw(box SceneBuilder::new())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_free(builder: *mut RtBuilder) {
+    drop(from_raw(builder));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_sphere(builder: *mut RtBuilder, pos: *const f64, radius: f64, mat: *const RtMaterial) {
+    let object = box Sphere::new(vec3(pos), radius, (*mat).clone());
+    update(builder, |b| b.object(object));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_plane(builder: *mut RtBuilder, pos: *const f64, normal: *const f64, mat: *const RtMaterial) {
+    let object = box Plane::new(vec3(pos), vec3(normal), (*mat).clone());
+    update(builder, |b| b.object(object));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_box(builder: *mut RtBuilder, pos: *const f64, dim: *const f64, mat: *const RtMaterial) {
+    let object = box AABox::new(vec3(pos), vec3(dim), (*mat).clone(), false);
+    update(builder, |b| b.object(object));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_cylinder(builder: *mut RtBuilder, pos: *const f64, axis: *const f64, radius: f64, height: f64,
+                                             capped: i32, mat: *const RtMaterial) {
+    let object = box Cylinder::new(vec3(pos), vec3(axis), radius, height, capped != 0, (*mat).clone());
+    update(builder, |b| b.object(object));
+}
+
+// `points` holds `count` points of 3 doubles, at least 3
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_polygon(builder: *mut RtBuilder, points: *const f64, count: usize, mat: *const RtMaterial) -> i32 {
+    if points.is_null() || count < 3 {
+        return -1;
+    }
+    let points = (0..count).map(|i| vec3(points.offset(i as isize * 3))).collect();
+    let mat = (*mat).clone();
+    match guard(move || Polygon::new(points, mat)) {
+        Some(object) => {
+            update(builder, |b| b.object(box object));
+            0
+        },
+        None         => -1,
+    }
+}
+
+// `heights` holds nx * nz doubles, x varying first
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_heightfield(builder: *mut RtBuilder, pos: *const f64, size: *const f64, nx: usize, nz: usize,
+                                                heights: *const f64, mat: *const RtMaterial) -> i32 {
+    if heights.is_null() || nx < 2 || nz < 2 {
+        return -1;
+    }
+    let heights = slice::from_raw_parts(heights, nx * nz).to_vec();
+    let (pos, size, mat) = (vec3(pos), vec3(size), (*mat).clone());
+    match guard(move || Heightfield::new(pos, size, nx, nz, heights, mat)) {
+        Some(object) => {
+            update(builder, |b| b.object(box object));
+            0
+        },
+        None         => -1,
+    }
+}
+
+// `heights` holds cols * rows doubles, `colors` null or 3 doubles per cell
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_hexgrid(builder: *mut RtBuilder, pos: *const f64, size: f64, cols: usize, rows: usize,
+                                            heights: *const f64, colors: *const f64, mat: *const RtMaterial) -> i32 {
+    if heights.is_null() || cols == 0 || rows == 0 {
+        return -1;
+    }
+    let heights = slice::from_raw_parts(heights, cols * rows).to_vec();
+    let mats = if colors.is_null() {
+        vec![(*mat).clone()]
+    } else {
+        (0..(cols * rows)).map(|i| {
+            let color = vec3(colors.offset(i as isize * 3));
+            Arc::new((**mat).with_color(Color::new(color.x, color.y, color.z)))
+        }).collect()
+    };
+    let pos = vec3(pos);
+    match guard(move || HexGrid::new(pos, size, cols, rows, heights, mats)) {
+        Some(object) => {
+            update(builder, |b| b.object(box object));
+            0
+        },
+        None         => -1,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_bulb(builder: *mut RtBuilder, pos: *const f64, spec: f64, shin: i32, diff: f64) {
+    let light = box Bulb::new(vec3(pos), spec, shin, diff);
+    update(builder, |b| b.light(light));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_sun(builder: *mut RtBuilder, dir: *const f64, spec: f64, shin: i32, diff: f64) {
+    let light = box Sun::new(vec3(dir), spec, shin, diff);
+    update(builder, |b| b.light(light));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_ambient(builder: *mut RtBuilder, ambient: f64) {
+    update(builder, |b| b.ambient(ambient));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_back(builder: *mut RtBuilder, color: *const f64) {
+    let color = vec3(color);
+    update(builder, |b| b.sky(box Uniform::new(Color::new(color.x, color.y, color.z))));
+}
+
+// Consume the builder, the scene can then be rendered many times
+#[no_mangle]
+pub unsafe extern "C" fn rt_builder_build(builder: *mut RtBuilder) -> *mut RtScene {
+    let builder = from_raw(builder);
+    into_raw(box builder.build())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rt_scene_free(scene: *mut RtScene) {
+    drop(from_raw(scene));
+}
+
+// Write w * h * 3 linear colors to `out`, row by row
+#[no_mangle]
+pub unsafe extern "C" fn rt_render(scene: *const RtScene, pos: *const f64, dir: *const f64, fov: f64, w: u32, h: u32,
+                                   sample: u32, bounce: u32, threads: u32, out: *mut f64) -> i32 {
+    if w == 0 || h == 0 || out.is_null() {
+        return -1;
+    }
+    let eye = Eye::new(vec3(pos), vec3(dir), fov);
+    // Raw pointers cannot be sent to the guard, the scene outlives it
+    let scene = scene as usize;
+    let rendered = guard(move || {
+        let picture = PictureBuilder::new(w, h).sample(sample).bounce(bounce).threads(threads).build();
+        picture.shot(&eye, unsafe { &*(scene as *const RtScene) }, false)
+    });
+    let buffer = match rendered {
+        Some(buffer) => buffer,
+        None         => return -1,
+    };
+    let out = slice::from_raw_parts_mut(out, (w * h * 3) as usize);
+    for (i, color) in buffer.pixels.iter().enumerate() {
+        out[i * 3] = color.r;
+        out[i * 3 + 1] = color.g;
+        out[i * 3 + 2] = color.b;
+    }
+    0
+}
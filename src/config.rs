@@ -1,31 +1,394 @@
-use std::rc::Rc;
+use std::mem;
+use std::sync::Arc;
+use std::num::Float;
+use std::f64::INFINITY;
+use std::f64::consts::PI;
+use std::collections::{ HashMap, BTreeMap };
+use std::old_io::fs::File;
 use serialize::json::Json;
 use vec::Vec3;
 use material::{ Color, Material };
-use object::{ Object, Objects, Rotate, Sphere, Plane, Dir, AARect, AABox, AAHexa };
-use light::{ Light, Lights, Bulb, Sun };
-use scene::{ Picture, Eye, Scene };
+use object::{ Object, Objects, Geometry, Instance, Rotate, Sphere, Plane, Dir, AARect, AABox, AAHexa };
+use object::{ Cylinder, Cone, Torus, Disc, Capsule, Ellipsoid, OrientedBox, Quad, Polygon };
+use heightfield::{ self, Heightfield };
+use hexgrid::{ self, HexGrid };
+use sdf::{ self, Sdf, SdfObject };
+use metaball::{ Ball, Metaballs };
+use light::{ Light, Lights, Bulb, Sun, Spot };
+use ies::IesProfile;
+use media::{ Media, Medium, Density, Volume };
+use noise::Perlin;
+use sky::{ Sky, Uniform, EnvMap, CubeMap, Preetham };
+use scene::{ Picture, Region, Eye, Scene, Occlusion };
+use aov::Pass;
+use denoise::Denoiser;
+use progressive::Progressive;
+use schema::{ self, Problem };
+use gltf::{ Gltf, Model };
+use format::Format;
+use expr;
 
-pub fn load(input: &str) -> (Eye, Scene, Picture) {
-    let root = Json::from_str(input).unwrap();
-    (load_eye(&root, "eye"), load_scene(&root, "scene"), load_picture(&root, "picture"))
+// Older versions of the format are migrated first, then includes are merged, relative to `dir`,
+// fields are patched with `sets` and variables and materials are resolved
+// The problems of the scene are returned with it, it can be loaded when none is an error
+pub fn prepare(root: &Json, dir: &Path, sets: &[(String, Json)]) -> Result<(Json, Vec<Problem>), String> {
+    let mut root = root.clone();
+    schema::migrate(&mut root);
+    try!(include(&mut root, dir));
+    for &(ref path, ref value) in sets.iter() {
+        set(&mut root, path.as_slice(), value.clone());
+    }
+    try!(resolve(&mut root));
+    let problems = schema::check(&root);
+    Ok((root, problems))
+}
+
+// Load a scene given by `prepare`
+pub fn load(root: &Json) -> (Eye, Scene, Picture) {
+    (load_eye(root, "eye"), load_scene(root, "scene"), load_picture(root, "picture"))
+}
+
+// Replace the value at a dotted path like "eye.pos.x", array items by index
+// Missing objects on the way are created
+pub fn set(root: &mut Json, path: &str, value: Json) {
+    let mut cur = root;
+    for key in path.split('.') {
+        let tmp = cur;
+        cur = match *tmp {
+            Json::Object(ref mut obj) => {
+                if !obj.contains_key(key) {
+                    obj.insert(key.to_string(), Json::Object(BTreeMap::new()));
+                }
+                obj.get_mut(key).unwrap()
+            },
+            Json::Array(ref mut array) => {
+                // Components of compact vectors and colors by name
+                let index = match key {
+                    "x" | "r" => 0,
+                    "y" | "g" => 1,
+                    "z" | "b" => 2,
+                    _         => key.parse::<usize>().unwrap(),
+                };
+                if index >= array.len() {
+                    panic!("Can not set {}: index {} out of bounds", path, index);
+                }
+                &mut array[index]
+            },
+            _                          => panic!("Can not set {}: {} is not in an object or an array", path, key),
+        };
+    }
+    *cur = value;
+}
+
+// Files named by "include" keys, relative to `dir`, are merged into the object holding the key
+// The keys of the object win, arrays are appended to the included ones
+// In arrays, { "include": "file" } items are replaced by the items of the file
+pub fn include(root: &mut Json, dir: &Path) -> Result<(), String> {
+    include_in(root, dir, 0)
+}
+
+const INCLUDE_DEPTH: u32 = 16;
+
+fn include_in(json: &mut Json, dir: &Path, depth: u32) -> Result<(), String> {
+    match *json {
+        Json::Object(ref mut obj)  => {
+            let files = obj.remove("include");
+            for (_, value) in obj.iter_mut() {
+                try!(include_in(value, dir, depth));
+            }
+            if let Some(files) = files {
+                for file in try!(include_files(&files)).iter() {
+                    match try!(read_include(dir, file.as_slice(), depth)) {
+                        Json::Object(other) => merge(obj, other),
+                        _                   => return Err(format!("{}: expected an object", file)),
+                    }
+                }
+            }
+        },
+        Json::Array(ref mut array) => {
+            let mut items = vec![];
+            for mut item in mem::replace(array, vec![]).into_iter() {
+                let single = item.as_object().map_or(false, |obj| obj.len() == 1 && obj.contains_key("include"));
+                if !single {
+                    try!(include_in(&mut item, dir, depth));
+                    items.push(item);
+                    continue;
+                }
+                for file in try!(include_files(item.find("include").unwrap())).iter() {
+                    match try!(read_include(dir, file.as_slice(), depth)) {
+                        Json::Array(included) => items.extend(included.into_iter()),
+                        included              => items.push(included),
+                    }
+                }
+            }
+            *array = items;
+        },
+        _                          => {},
+    }
+    Ok(())
+}
+
+// A file name or an array of them
+fn include_files(files: &Json) -> Result<Vec<String>, String> {
+    if let Some(file) = files.as_string() {
+        return Ok(vec![file.to_string()]);
+    }
+    match files.as_array() {
+        Some(array) if array.iter().all(|file| file.is_string()) => {
+            Ok(array.iter().map(|file| file.as_string().unwrap().to_string()).collect())
+        },
+        _                                                        => Err("include: expected a file or an array of files".to_string()),
+    }
+}
+
+// Parsed by extension, its own includes relative to itself
+fn read_include(dir: &Path, file: &str, depth: u32) -> Result<Json, String> {
+    if depth >= INCLUDE_DEPTH {
+        return Err(format!("{}: includes nested too deeply, do they include each other?", file));
+    }
+    let path = dir.join(file);
+    let input = match File::open(&path).read_to_string() {
+        Ok(input) => input,
+        Err(err)  => return Err(format!("{}: {}", path.display(), err)),
+    };
+    let mut json = match Format::from_path(&path).parse(input.as_slice()) {
+        Ok(json) => json,
+        Err(err) => return Err(format!("{}: {}", path.display(), err)),
+    };
+    try!(include_in(&mut json, &path.dir_path(), depth + 1));
+    Ok(json)
+}
+
+fn merge(obj: &mut BTreeMap<String, Json>, other: BTreeMap<String, Json>) {
+    for (key, value) in other.into_iter() {
+        let merged = match (obj.remove(&key), value) {
+            (None, value)                                       => value,
+            (Some(Json::Object(mut mine)), Json::Object(theirs)) => {
+                merge(&mut mine, theirs);
+                Json::Object(mine)
+            },
+            (Some(Json::Array(mine)), Json::Array(mut theirs))   => {
+                theirs.extend(mine.into_iter());
+                Json::Array(theirs)
+            },
+            (Some(mine), _)                                     => mine,
+        };
+        obj.insert(key, merged);
+    }
+}
+
+// Evaluate the expressions using the top level "vars", strings starting with "=" like "= r * 2",
+// then replace the names of the top level "materials" used as "mat" by their definition
+pub fn resolve(root: &mut Json) -> Result<(), String> {
+    let (defs, mats) = match *root {
+        Json::Object(ref mut obj) => (obj.remove("vars"), obj.remove("materials")),
+        _                         => return Ok(()),
+    };
+    let defs = match defs {
+        Some(Json::Object(defs)) => defs,
+        Some(_)                  => return Err("vars: expected an object".to_string()),
+        None                     => BTreeMap::new(),
+    };
+    let mut vars = Vars { defs: defs, values: HashMap::new(), resolving: vec![] };
+    let mats = match mats {
+        Some(mats) => if mats.is_object() {
+            try!(vars.value("materials", mats))
+        } else {
+            return Err("materials: expected an object".to_string());
+        },
+        None       => Json::Object(BTreeMap::new()),
+    };
+    *root = try!(vars.value("", mem::replace(root, Json::Null)));
+    use_materials(root, mats.as_object().unwrap(), "")
+}
+
+// Variables, evaluated when first used
+struct Vars {
+    defs:      BTreeMap<String, Json>,
+    values:    HashMap<String, Json>,
+    resolving: Vec<String>,
+}
+
+impl Vars {
+    fn get(&mut self, name: &str) -> Result<Json, String> {
+        if let Some(value) = self.values.get(name) {
+            return Ok(value.clone());
+        }
+        if self.resolving.iter().any(|resolving| resolving.as_slice() == name) {
+            return Err(format!("vars.{}: defined with itself", name));
+        }
+        let def = match self.defs.get(name) {
+            Some(def) => def.clone(),
+            None      => return Err(format!("unknown variable {}", name)),
+        };
+        self.resolving.push(name.to_string());
+        let value = try!(self.value(format!("vars.{}", name).as_slice(), def));
+        self.resolving.pop();
+        self.values.insert(name.to_string(), value.clone());
+        Ok(value)
+    }
+
+    fn value(&mut self, path: &str, json: Json) -> Result<Json, String> {
+        match json {
+            Json::String(ref s) if s.starts_with("=") => self.expr(path, s[1..].trim()),
+            Json::Array(array)                        => {
+                let mut items = vec![];
+                for (i, item) in array.into_iter().enumerate() {
+                    items.push(try!(self.value(format!("{}[{}]", path, i).as_slice(), item)));
+                }
+                Ok(Json::Array(items))
+            },
+            Json::Object(obj)                         => {
+                let mut items = BTreeMap::new();
+                for (key, item) in obj.into_iter() {
+                    let item = try!(self.value(schema::join(path, key.as_slice()).as_slice(), item));
+                    items.insert(key, item);
+                }
+                Ok(Json::Object(items))
+            },
+            json                                      => Ok(json),
+        }
+    }
+
+    // A lone name keeps the value of its variable, a vector or anything else
+    fn expr(&mut self, path: &str, text: &str) -> Result<Json, String> {
+        if self.defs.contains_key(text) {
+            return self.get(text).map_err(|err| format!("{}: {}", path, err));
+        }
+        let value = expr::eval(text, &mut |name: &str| {
+            match try!(self.get(name)).as_f64() {
+                Some(value) => Ok(value),
+                None        => Err(format!("{} is not a number", name)),
+            }
+        });
+        match value {
+            Ok(value) if value.fract() == 0. && value.abs() < 1e15 => {
+                Ok(if value >= 0. { Json::U64(value as u64) } else { Json::I64(value as i64) })
+            },
+            Ok(value)                                              => Ok(Json::F64(value)),
+            Err(err)                                               => Err(format!("{}: {}", path, err)),
+        }
+    }
+}
+
+fn use_materials(json: &mut Json, mats: &BTreeMap<String, Json>, path: &str) -> Result<(), String> {
+    match *json {
+        Json::Object(ref mut obj)  => for (key, value) in obj.iter_mut() {
+            let path = schema::join(path, key.as_slice());
+            let name = if key.as_slice() == "mat" { value.as_string().map(|name| name.to_string()) } else { None };
+            match name {
+                Some(name) => *value = match mats.get(name.as_slice()) {
+                    Some(mat) => mat.clone(),
+                    None      => return Err(format!("{}: unknown material {}", path, name)),
+                },
+                None       => try!(use_materials(value, mats, path.as_slice())),
+            }
+        },
+        Json::Array(ref mut array) => for (i, item) in array.iter_mut().enumerate() {
+            try!(use_materials(item, mats, format!("{}[{}]", path, i).as_slice()));
+        },
+        _                          => {},
+    }
+    Ok(())
 }
 
 // Picture
 fn load_picture(root: &Json, key: &str) -> Picture {
     let obj = root.find(key).unwrap();
+    let (w, h) = (load_u32(obj, "w"), load_u32(obj, "h"));
+    let scale = load_f64_or(obj, "scale", 1.);
     Picture::new(
-        load_u32(obj, "w"),
-        load_u32(obj, "h"),
+        ((w as f64 * scale).round() as u32).max(1),
+        ((h as f64 * scale).round() as u32).max(1),
         load_str(obj, "path"),
+        obj.find("ao").map(|ao| ao.as_string().unwrap()),
+        load_passes(obj, "passes"),
+        load_str_or(obj, "passes-format", "png") == "exr",
+        load_denoiser(obj, "denoise"),
+        load_progressive(obj, "progressive"),
+        load_region(obj, w, h),
+        load_u32_or(obj, "threads", 1),
         load_u32_or(obj, "bounce", 5),
         load_u32_or(obj, "sample", 1),
     )
 }
 
-// Eye
+// Crop window, in pixels of the full size picture or normalized, and tiles to render
+fn load_region(root: &Json, w: u32, h: u32) -> Region {
+    let crop = root.find("crop").map(|obj| {
+        let (x, y, cw, ch) = (load_f64(obj, "x"), load_f64(obj, "y"), load_f64(obj, "w"), load_f64(obj, "h"));
+        if load_bool_or(obj, "normalized", false) {
+            (x, y, cw, ch)
+        } else {
+            (x / w as f64, y / h as f64, cw / w as f64, ch / h as f64)
+        }
+    });
+    let tiles = root.find("tiles").map(|obj| {
+        let list = obj.find("list").unwrap().as_array().unwrap().iter().map(|tile| {
+            let tile = tile.as_array().unwrap();
+            (tile[0].as_u64().unwrap() as u32, tile[1].as_u64().unwrap() as u32)
+        }).collect();
+        (load_u32(obj, "size"), list)
+    });
+    Region::new(crop, tiles)
+}
+
+// Passes, by name
+fn load_passes(root: &Json, key: &str) -> Vec<Pass> {
+    match root.find(key) {
+        Some(passes) => passes.as_array().unwrap().iter().map(|pass| Pass::from_name(pass.as_string().unwrap())).collect(),
+        None         => vec![],
+    }
+}
+
+// Denoiser, `true` for the defaults
+fn load_denoiser(root: &Json, key: &str) -> Option<Denoiser> {
+    let obj = match root.find(key) {
+        Some(obj) => obj,
+        None      => return None,
+    };
+    if let Some(enabled) = obj.as_boolean() {
+        return if enabled { Some(Denoiser::new(3, 2., 0.3, 0.2, 0.1)) } else { None };
+    }
+    // A sigma of 0 divides by 0 in the weights
+    let sigma = |key: &str, default: f64| {
+        let sigma = load_f64_or(obj, key, default);
+        if sigma <= 0. {
+            panic!("Denoiser {} must be greater than 0, got {}", key, sigma);
+        }
+        sigma
+    };
+    Some(Denoiser::new(
+        load_u32_or(obj, "radius", 3),
+        sigma("sigma-space", 2.),
+        sigma("sigma-color", 0.3),
+        sigma("sigma-normal", 0.2),
+        sigma("sigma-albedo", 0.1),
+    ))
+}
+
+// Progressive rendering, the accumulation buffer defaults to "image.acc"
+fn load_progressive(root: &Json, key: &str) -> Option<Progressive> {
+    root.find(key).map(|obj| {
+        let accum = Path::new(load_str(root, "path")).with_extension("acc");
+        Progressive::new(
+            load_u32_or(obj, "passes", 0),
+            load_f64_or(obj, "time", 0.),
+            load_f64_or(obj, "noise", 0.),
+            load_f64_or(obj, "checkpoint-time", 60.),
+            load_u32_or(obj, "checkpoint-passes", 0),
+            load_str_or(obj, "accum", accum.as_str().unwrap()),
+            load_bool_or(obj, "resume", false),
+        )
+    })
+}
+
+// Eye, from the glTF file of the scene when missing
 fn load_eye(root: &Json, key: &str) -> Eye {
-    let obj = root.find(key).unwrap();
+    let obj = match root.find(key) {
+        Some(obj) => obj,
+        None      => return load_gltf_eye(root),
+    };
     Eye::new(
         load_vec3(obj, "pos"),
         load_vec3(obj, "dir"),
@@ -36,26 +399,187 @@ fn load_eye(root: &Json, key: &str) -> Eye {
 // Scene
 fn load_scene<'a>(root: &Json, key: &str) -> Scene<'a> {
     let obj = root.find(key).unwrap();
+    let geometries = load_geometries(obj, "geometries");
+    let mut objects = load_objects(obj, "objects", &geometries);
+    let mut lights = load_lights(obj, "lights");
+    // A glTF file brings its meshes and lights
+    if let Some(options) = gltf_options(obj) {
+        let file = Gltf::load(load_str(&options, "path"));
+        let scene = load_index(&options, "scene");
+        objects.push(box Model::new(&file, scene, Vec3::new(0., 0., 0.), Vec3::new(0., 0., 0.), 1.) as Box<Object>);
+        lights.extend(file.lights(scene, load_f64_or(&options, "intensity", 1.)).into_iter());
+    }
     Scene::new(
-        load_objects(obj, "objects"),
-        load_lights(obj, "lights"),
-        load_f64_or(obj, "ambient", 0.2),
-        load_color_or(obj, "back", Color::new(0.39, 0.8, 0.92)),
+        Objects::new(objects),
+        Lights::new(lights),
+        load_media(obj),
+        load_f64_or(obj, "ambient", 0.),
+        load_occlusion(obj, "occlusion"),
+        load_sky(obj),
+        load_u32_or(obj, "ibl", 0),
     )
 }
 
-// Lights
-fn load_lights<'a>(root: &Json, key: &str) -> Lights<'a> {
-    let array = root.find(key).unwrap().as_array().unwrap();
-    let all = array.iter().map(|obj| {
+// glTF file of a scene, a path or { path, scene?, camera?, intensity? }
+fn gltf_options(root: &Json) -> Option<Json> {
+    root.find("gltf").map(|gltf| match gltf.as_string() {
+        Some(path) => Json::Object(vec![("path".to_string(), Json::String(path.to_string()))].into_iter().collect()),
+        None       => gltf.clone(),
+    })
+}
+
+// Eye of a glTF camera, the first one by default, or looking at the whole file without cameras
+fn load_gltf_eye(root: &Json) -> Eye {
+    let options = root.find("scene").and_then(|scene| gltf_options(scene)).expect("Missing eye, and no glTF file to take it from");
+    let picture = root.find("picture").unwrap();
+    let aspect = load_u32(picture, "w") as f64 / load_u32(picture, "h") as f64;
+    let file = Gltf::load(load_str(&options, "path"));
+    let scene = load_index(&options, "scene");
+    match load_index(&options, "camera") {
+        Some(camera) => file.eye(scene, Some(camera), aspect).expect("Unknown glTF camera"),
+        None         => file.eye(scene, None, aspect).unwrap_or_else(|| file.overview(scene, aspect)),
+    }
+}
+
+// Ambient occlusion, disabled by default
+fn load_occlusion(root: &Json, key: &str) -> Option<Occlusion> {
+    root.find(key).map(|obj| Occlusion::new(
+        load_f64_or(obj, "radius", 10.),
+        load_u32_or(obj, "samples", 16),
+    ))
+}
+
+// Sky, a plain color by default
+fn load_sky(root: &Json) -> Box<Sky> {
+    let back = Color::new(0.39, 0.8, 0.92);
+    let sky = root.find("sky");
+    if sky.is_none() {
+        return box Uniform::new(back);
+    }
+    let sky = sky.unwrap();
+    let key = sky.as_object().unwrap().keys().next().unwrap();
+    let obj = sky.find(key.as_slice()).unwrap();
+    match key.as_slice() {
+        "uniform"  => box Uniform::new(load_color_or(obj, "color", back)) as Box<Sky>,
+        "equirect" => box EnvMap::new(
+            load_str(obj, "path"),
+            load_f64_or(obj, "intensity", 1.),
+            load_f64_or(obj, "rotate", 0.),
+        ) as Box<Sky>,
+        "cubemap"  => {
+            let faces = ["px", "nx", "py", "ny", "pz", "nz"];
+            let paths: Vec<&str> = faces.iter().map(|face| load_str(obj, face)).collect();
+            box CubeMap::new(paths.as_slice(), load_f64_or(obj, "intensity", 1.)) as Box<Sky>
+        },
+        "preetham" => {
+            // Follow the first sun when no direction is given
+            let dir = if obj.find("dir").is_some() {
+                load_vec3(obj, "dir")
+            } else {
+                root.find("lights").and_then(|lights| lights.as_array()).into_iter().flat_map(|lights| lights.iter())
+                    .filter_map(|light| light.find("sun"))
+                    .map(|sun| load_vec3(sun, "dir"))
+                    .next()
+                    .expect("Preetham sky needs a direction or a sun")
+            };
+            box Preetham::new(
+                dir * -1.,
+                load_f64_or(obj, "turbidity", 3.),
+                load_f64_or(obj, "intensity", 0.05),
+            ) as Box<Sky>
+        },
+        _          => panic!("Not a sky"),
+    }
+}
+
+// Media
+fn load_media(root: &Json) -> Media {
+    let fog = root.find("fog");
+    let volumes = match root.find("volumes") {
+        Some(array) => array.as_array().unwrap().iter().map(|obj| load_volume(obj)).collect(),
+        None        => vec![],
+    };
+    Media::new(
+        fog.map(|obj| load_medium(obj)),
+        fog.map_or(INFINITY, |obj| load_f64_or(obj, "dist", INFINITY)),
+        volumes,
+        load_f64_or(root, "march-step", 1.),
+    )
+}
+
+// Volume
+fn load_volume(root: &Json) -> Volume {
+    let density = root.find("density").unwrap();
+    let density = if density.is_number() {
+        Density::Constant(density.as_f64().unwrap())
+    } else if density.find("noise").is_some() {
+        let obj = density.find("noise").unwrap();
+        Density::Noise(
+            Perlin::new(load_u32_or(obj, "seed", 0)),
+            load_f64_or(obj, "scale", 1.),
+            load_u32_or(obj, "octaves", 4),
+            load_f64_or(obj, "max", 1.),
+        )
+    } else if density.find("grid").unwrap().is_string() {
+        Density::load_grid(load_str(density, "grid"))
+    } else {
+        let obj = density.find("grid").unwrap();
+        Density::Grid(
+            load_u32(obj, "nx") as usize,
+            load_u32(obj, "ny") as usize,
+            load_u32(obj, "nz") as usize,
+            load_f64_array(obj, "values"),
+        )
+    };
+    Volume::new(
+        load_vec3(root, "pos"),
+        load_vec3(root, "dim"),
+        density,
+        load_medium(root),
+    )
+}
+
+// Medium
+fn load_medium(root: &Json) -> Medium {
+    Medium::new(
+        load_f64_or(root, "absorb", 0.),
+        load_f64_or(root, "scatter", 0.),
+        load_color_or(root, "color", Color::new(1., 1., 1.)),
+        load_f64_or(root, "g", 0.),
+    )
+}
+
+// Lights, none by default
+fn load_lights<'a>(root: &Json, key: &str) -> Vec<Box<Light + 'a>> {
+    let array = match root.find(key) {
+        Some(array) => array.as_array().unwrap(),
+        None        => return vec![],
+    };
+    array.iter().map(|obj| {
         let key = obj.as_object().unwrap().keys().next().unwrap();
         match key.as_slice() {
             "bulb" => box load_bulb(obj, key) as Box<Light>,
             "sun"  => box load_sun(obj, key) as Box<Light>,
+            "spot" => box load_spot(obj, key) as Box<Light>,
             _      => panic!("Not a light"),
         }
-    }).collect();
-    Lights::new(all)
+    }).collect()
+}
+
+// Spot
+fn load_spot(root: &Json, key: &str) -> Spot {
+    let obj = root.find(key).unwrap();
+    let profile = obj.find("ies").map(|path| IesProfile::load(path.as_string().unwrap()));
+    Spot::new(
+        load_vec3(obj, "pos"),
+        load_vec3(obj, "dir"),
+        load_f64_or(obj, "angle", PI / 4.),
+        load_f64_or(obj, "falloff", 0.),
+        profile,
+        load_f64(obj, "spec"),
+        load_i32(obj, "shin"),
+        load_f64(obj, "diff"),
+    )
 }
 
 // Sun
@@ -80,34 +604,101 @@ fn load_bulb(root: &Json, key: &str) -> Bulb {
     )
 }
 
-// Objects
-fn load_objects<'a>(root: &Json, key: &str) -> Objects<'a> {
-    let array = root.find(key).unwrap().as_array().unwrap();
-    let all = array.iter().map(|obj| load_object(obj)).collect();
-    Objects::new(all)
+// Geometries, shared by name between instances
+type Geometries = HashMap<String, Geometry<'static>>;
+
+fn load_geometries(root: &Json, key: &str) -> Geometries {
+    let mut geometries = HashMap::new();
+    let obj = root.find(key);
+    if obj.is_none() {
+        return geometries;
+    }
+    for (name, geometry) in obj.unwrap().as_object().unwrap().iter() {
+        // Geometries cannot instance each other
+        geometries.insert(name.clone(), Arc::new(load_object(geometry, &HashMap::new())));
+    }
+    geometries
+}
+
+// Objects, none by default
+fn load_objects<'a>(root: &Json, key: &str, geometries: &Geometries) -> Vec<Box<Object + 'a>> {
+    match root.find(key) {
+        Some(array) => array.as_array().unwrap().iter().map(|obj| load_object(obj, geometries)).collect(),
+        None        => vec![],
+    }
 }
 
 // Object
-fn load_object(root: &Json) -> Box<Object> {
+fn load_object(root: &Json, geometries: &Geometries) -> Box<Object> {
     let key = root.as_object().unwrap().keys().next().unwrap();
     match key.as_slice() {
-        "rotate" => box load_rotate(root, key) as Box<Object>,
-        "sphere" => box load_sphere(root, key) as Box<Object>,
-        "plane"  => box load_plane(root, key) as Box<Object>,
-        "aarect" => box load_aarect(root, key) as Box<Object>,
-        "aabox"  => box load_aabox(root, key) as Box<Object>,
-        "aahexa" => box load_aahexa(root, key) as Box<Object>,
-        _        => panic!("Not an object"),
+        "instance"    => box load_instance(root, key, geometries) as Box<Object>,
+        "rotate"      => box load_rotate(root, key, geometries) as Box<Object>,
+        "sphere"      => box load_sphere(root, key) as Box<Object>,
+        "plane"       => box load_plane(root, key) as Box<Object>,
+        "aarect"      => box load_aarect(root, key) as Box<Object>,
+        "aabox"       => box load_aabox(root, key) as Box<Object>,
+        "aahexa"      => box load_aahexa(root, key) as Box<Object>,
+        "cylinder"    => box load_cylinder(root, key) as Box<Object>,
+        "cone"        => box load_cone(root, key) as Box<Object>,
+        "torus"       => box load_torus(root, key) as Box<Object>,
+        "disc"        => box load_disc(root, key) as Box<Object>,
+        "capsule"     => box load_capsule(root, key) as Box<Object>,
+        "ellipsoid"   => box load_ellipsoid(root, key) as Box<Object>,
+        "box"         => box load_box(root, key) as Box<Object>,
+        "quad"        => box load_quad(root, key) as Box<Object>,
+        "polygon"     => box load_polygon(root, key) as Box<Object>,
+        "heightfield" => box load_heightfield(root, key) as Box<Object>,
+        "hexgrid"     => box load_hexgrid(root, key) as Box<Object>,
+        "sdf"         => box load_sdf_object(root, key) as Box<Object>,
+        "metaballs"   => box load_metaballs(root, key) as Box<Object>,
+        "gltf"        => box load_gltf_object(root, key) as Box<Object>,
+        _             => panic!("Not an object"),
     }
 }
 
+// Instance
+fn load_instance(root: &Json, key: &str, geometries: &Geometries) -> Instance<'static> {
+    let obj = root.find(key).unwrap();
+    let name = load_str(obj, "geometry");
+    let geometry = match geometries.get(name) {
+        Some(geometry) => geometry.clone(),
+        None           => panic!("Unknown geometry: {}", name),
+    };
+    let mat = if obj.find("mat").is_some() { Some(Arc::new(load_material(obj, "mat"))) } else { None };
+    let scale = load_f64_or(obj, "scale", 1.);
+    if scale <= 0. {
+        panic!("Instance scale must be greater than 0, got {}", scale);
+    }
+    Instance::new(
+        name.to_string(),
+        load_vec3_or(obj, "pos", Vec3::new(0., 0., 0.)),
+        load_vec3_or(obj, "dir", Vec3::new(0., 0., 0.)),
+        scale,
+        mat,
+        geometry,
+    )
+}
+
+// glTF model, its meshes placed like an instance
+fn load_gltf_object(root: &Json, key: &str) -> Model {
+    let obj = root.find(key).unwrap();
+    Model::new(
+        &Gltf::load(load_str(obj, "path")),
+        load_index(obj, "scene"),
+        load_vec3_or(obj, "pos", Vec3::new(0., 0., 0.)),
+        load_vec3_or(obj, "dir", Vec3::new(0., 0., 0.)),
+        load_f64_or(obj, "scale", 1.),
+    )
+}
+
 // Rotate
-fn load_rotate<'a>(root: &Json, key: &str) -> Rotate<'a> {
+fn load_rotate<'a>(root: &Json, key: &str, geometries: &Geometries) -> Rotate<'a> {
     let obj = root.find(key).unwrap();
     Rotate::new(
         load_vec3(obj, "pos"),
         load_vec3(obj, "dir"),
-        load_object(obj.find("object").unwrap()),
+        load_object(obj.find("object").unwrap(), geometries),
     )
 }
 
@@ -118,17 +709,17 @@ fn load_aahexa<'a>(root: &Json, key: &str) -> AAHexa<'a> {
         load_vec3(obj, "pos"),
         load_f64(obj, "x"),
         load_f64(obj, "y"),
-        Rc::new(load_material(obj, "mat")),
+        Arc::new(load_material(obj, "mat")),
     )
 }
 
 // AABox
-fn load_aabox<'a>(root: &Json, key: &str) -> AABox<'a> {
+fn load_aabox(root: &Json, key: &str) -> AABox {
     let obj = root.find(key).unwrap();
     AABox::new(
         load_vec3(obj, "pos"),
         load_vec3(obj, "dim"),
-        Rc::new(load_material(obj, "mat")),
+        Arc::new(load_material(obj, "mat")),
         load_bool(obj, "skybox"),
     )
 }
@@ -140,7 +731,7 @@ fn load_aarect(root: &Json, key: &str) -> AARect {
         load_vec3(obj, "pos"),
         load_dir(obj, "dir"),
         load_vec3(obj, "dim"),
-        Rc::new(load_material(obj, "mat")),
+        Arc::new(load_material(obj, "mat")),
     )
 }
 
@@ -163,7 +754,7 @@ fn load_plane(root: &Json, key: &str) -> Plane {
     Plane::new(
         load_vec3(obj, "pos"),
         load_vec3(obj, "normal"),
-        Rc::new(load_material(obj, "mat")),
+        Arc::new(load_material(obj, "mat")),
     )
 }
 
@@ -173,7 +764,234 @@ fn load_sphere(root: &Json, key: &str) -> Sphere {
     Sphere::new(
         load_vec3(obj, "pos"),
         load_f64(obj, "radius"),
-        Rc::new(load_material(obj, "mat")),
+        Arc::new(load_material(obj, "mat")),
+    )
+}
+
+// Metaballs
+fn load_metaballs(root: &Json, key: &str) -> Metaballs {
+    let obj = root.find(key).unwrap();
+    let balls = obj.find("balls").unwrap().as_array().unwrap().iter().map(|ball| {
+        let radius = load_f64(ball, "radius");
+        if radius <= 0. {
+            panic!("Metaball radius must be greater than 0, got {}", radius);
+        }
+        Ball::new(
+            load_vec3(ball, "pos"),
+            radius,
+            load_f64_or(ball, "strength", 1.),
+        )
+    }).collect();
+    Metaballs::new(
+        balls,
+        load_f64_or(obj, "threshold", 0.5),
+        Arc::new(load_material(obj, "mat")),
+    )
+}
+
+// SdfObject
+fn load_sdf_object<'a>(root: &Json, key: &str) -> SdfObject<'a> {
+    let obj = root.find(key).unwrap();
+    SdfObject::new(
+        load_sdf(obj.find("shape").unwrap()),
+        Arc::new(load_material(obj, "mat")),
+        load_u32_or(obj, "steps", 256),
+        load_f64_or(obj, "eps", 0.001),
+        load_f64_or(obj, "max-dist", 1000.),
+        load_f64_or(obj, "step", 1.),
+    )
+}
+
+// Sdf
+fn load_sdf(root: &Json) -> Box<Sdf> {
+    let key = root.as_object().unwrap().keys().next().unwrap();
+    let obj = root.find(key.as_slice()).unwrap();
+    match key.as_slice() {
+        "sphere"       => box sdf::Sphere::new(load_vec3(obj, "pos"), load_f64(obj, "radius")) as Box<Sdf>,
+        "box"          => box sdf::Cuboid::new(load_vec3(obj, "pos"), load_vec3(obj, "dim")) as Box<Sdf>,
+        "torus"        => box sdf::Torus::new(load_vec3(obj, "pos"), load_f64(obj, "major"), load_f64(obj, "minor")) as Box<Sdf>,
+        "cylinder"     => box sdf::Cylinder::new(load_vec3(obj, "pos"), load_f64(obj, "radius"), load_f64(obj, "height")) as Box<Sdf>,
+        "plane"        => box sdf::Plane::new(load_vec3(obj, "pos"), load_vec3(obj, "normal")) as Box<Sdf>,
+        "union"        => box sdf::Union::new(load_sdf_array(obj, "shapes"), load_f64_or(obj, "k", 0.)) as Box<Sdf>,
+        "intersection" => box sdf::Intersection::new(load_sdf_array(obj, "shapes")) as Box<Sdf>,
+        "subtraction"  => box sdf::Subtraction::new(
+            load_sdf(obj.find("from").unwrap()),
+            load_sdf(obj.find("shape").unwrap()),
+            load_f64_or(obj, "k", 0.),
+        ) as Box<Sdf>,
+        "translate"    => box sdf::Translate::new(load_vec3(obj, "pos"), load_sdf(obj.find("shape").unwrap())) as Box<Sdf>,
+        "repeat"       => box sdf::Repeat::new(load_vec3(obj, "period"), load_sdf(obj.find("shape").unwrap())) as Box<Sdf>,
+        "twist"        => box sdf::Twist::new(load_f64(obj, "k"), load_sdf(obj.find("shape").unwrap())) as Box<Sdf>,
+        "bend"         => box sdf::Bend::new(load_f64(obj, "k"), load_sdf(obj.find("shape").unwrap())) as Box<Sdf>,
+        "mandelbulb"   => box sdf::Mandelbulb::new(
+            load_vec3(obj, "pos"),
+            load_f64_or(obj, "scale", 1.),
+            load_f64_or(obj, "power", 8.),
+            load_u32_or(obj, "iter", 10),
+        ) as Box<Sdf>,
+        "menger"       => box sdf::Menger::new(load_vec3(obj, "pos"), load_f64(obj, "size"), load_u32_or(obj, "iter", 4)) as Box<Sdf>,
+        _              => panic!("Not a sdf"),
+    }
+}
+
+fn load_sdf_array(root: &Json, key: &str) -> Vec<Box<Sdf>> {
+    root.find(key).unwrap().as_array().unwrap().iter().map(|obj| load_sdf(obj)).collect()
+}
+
+// HexGrid
+fn load_hexgrid(root: &Json, key: &str) -> HexGrid {
+    let obj = root.find(key).unwrap();
+    let mat = load_material(obj, "mat");
+    let (cols, rows, heights, colors) = if obj.find("image").is_some() {
+        let (cols, rows, heights, colors) = hexgrid::load_image(load_str(obj, "image"));
+        let scale = load_f64(obj, "height");
+        let colors = if load_bool_or(obj, "colored", true) { colors } else { vec![] };
+        (cols, rows, heights.iter().map(|h| h * scale).collect(), colors)
+    } else {
+        let colors = match obj.find("colors") {
+            Some(array) => array.as_array().unwrap().iter().map(|color| to_color(color)).collect(),
+            None        => vec![],
+        };
+        (load_u32(obj, "cols") as usize, load_u32(obj, "rows") as usize, load_f64_array(obj, "heights"), colors)
+    };
+    let mats = if colors.is_empty() {
+        vec![Arc::new(mat)]
+    } else {
+        colors.iter().map(|&color| Arc::new(mat.with_color(color))).collect()
+    };
+    HexGrid::new(
+        load_vec3(obj, "pos"),
+        load_f64(obj, "size"),
+        cols,
+        rows,
+        heights,
+        mats,
+    )
+}
+
+// Heightfield
+fn load_heightfield(root: &Json, key: &str) -> Heightfield {
+    let obj = root.find(key).unwrap();
+    let (nx, nz, heights) = if obj.find("image").is_some() {
+        heightfield::load_image(load_str(obj, "image"))
+    } else if obj.find("grid").is_some() {
+        match heightfield::load_grid(load_str(obj, "grid")) {
+            Ok(grid) => grid,
+            Err(err) => panic!("{}", err),
+        }
+    } else {
+        (load_u32(obj, "nx") as usize, load_u32(obj, "nz") as usize, load_f64_array(obj, "heights"))
+    };
+    Heightfield::new(
+        load_vec3(obj, "pos"),
+        load_vec3(obj, "size"),
+        nx,
+        nz,
+        heights,
+        Arc::new(load_material(obj, "mat")),
+    )
+}
+
+// Polygon
+fn load_polygon(root: &Json, key: &str) -> Polygon {
+    let obj = root.find(key).unwrap();
+    Polygon::new(
+        load_vec3_array(obj, "points"),
+        Arc::new(load_material(obj, "mat")),
+    )
+}
+
+// Quad
+fn load_quad(root: &Json, key: &str) -> Quad {
+    let obj = root.find(key).unwrap();
+    Quad::new(
+        load_vec3(obj, "pos"),
+        load_vec3(obj, "u"),
+        load_vec3(obj, "v"),
+        Arc::new(load_material(obj, "mat")),
+    )
+}
+
+// Box
+fn load_box(root: &Json, key: &str) -> OrientedBox {
+    let obj = root.find(key).unwrap();
+    OrientedBox::new(
+        load_vec3(obj, "pos"),
+        load_vec3(obj, "u"),
+        load_vec3(obj, "v"),
+        load_vec3(obj, "w"),
+        Arc::new(load_material(obj, "mat")),
+    )
+}
+
+// Ellipsoid
+fn load_ellipsoid(root: &Json, key: &str) -> Ellipsoid {
+    let obj = root.find(key).unwrap();
+    Ellipsoid::new(
+        load_vec3(obj, "pos"),
+        load_vec3(obj, "radii"),
+        load_vec3_or(obj, "dir", Vec3::new(0., 0., 0.)),
+        Arc::new(load_material(obj, "mat")),
+    )
+}
+
+// Capsule
+fn load_capsule(root: &Json, key: &str) -> Capsule {
+    let obj = root.find(key).unwrap();
+    Capsule::new(
+        load_vec3(obj, "a"),
+        load_vec3(obj, "b"),
+        load_f64(obj, "radius"),
+        Arc::new(load_material(obj, "mat")),
+    )
+}
+
+// Disc
+fn load_disc(root: &Json, key: &str) -> Disc {
+    let obj = root.find(key).unwrap();
+    Disc::new(
+        load_vec3(obj, "pos"),
+        load_vec3(obj, "normal"),
+        load_f64(obj, "radius"),
+        Arc::new(load_material(obj, "mat")),
+    )
+}
+
+// Torus
+fn load_torus(root: &Json, key: &str) -> Torus {
+    let obj = root.find(key).unwrap();
+    Torus::new(
+        load_vec3(obj, "pos"),
+        load_vec3(obj, "axis"),
+        load_f64(obj, "major"),
+        load_f64(obj, "minor"),
+        Arc::new(load_material(obj, "mat")),
+    )
+}
+
+// Cone
+fn load_cone(root: &Json, key: &str) -> Cone {
+    let obj = root.find(key).unwrap();
+    Cone::new(
+        load_vec3(obj, "pos"),
+        load_vec3(obj, "axis"),
+        load_f64(obj, "radius"),
+        load_f64(obj, "height"),
+        load_bool_or(obj, "capped", true),
+        Arc::new(load_material(obj, "mat")),
+    )
+}
+
+// Cylinder
+fn load_cylinder(root: &Json, key: &str) -> Cylinder {
+    let obj = root.find(key).unwrap();
+    Cylinder::new(
+        load_vec3(obj, "pos"),
+        load_vec3(obj, "axis"),
+        load_f64(obj, "radius"),
+        load_f64(obj, "height"),
+        load_bool_or(obj, "capped", true),
+        Arc::new(load_material(obj, "mat")),
     )
 }
 
@@ -192,34 +1010,47 @@ fn load_material(root: &Json, key: &str) -> Material {
 
 // Color
 fn load_color(root: &Json, key: &str) -> Color {
-    let obj = root.find(key).unwrap();
-    Color::new(
-        load_f64(obj, "r"),
-        load_f64(obj, "g"),
-        load_f64(obj, "b"),
-    )
+    to_color(root.find(key).unwrap())
 }
 
 fn load_color_or(root: &Json, key: &str, def: Color) -> Color {
-    let obj = root.find(key);
-    if obj.is_none() {
-        return def;
-    }
-    Color::new(
-        load_f64(obj.unwrap(), "r"),
-        load_f64(obj.unwrap(), "g"),
-        load_f64(obj.unwrap(), "b"),
-    )
+    root.find(key).map_or(def, |obj| to_color(obj))
+}
+
+fn to_color(obj: &Json) -> Color {
+    let (r, g, b) = load_triple(obj, ["r", "g", "b"]);
+    Color::new(r, g, b)
 }
 
 // Vec3
 fn load_vec3(root: &Json, key: &str) -> Vec3 {
-    let obj = root.find(key).unwrap();
-    Vec3::new(
-        load_f64(obj, "x"),
-        load_f64(obj, "y"),
-        load_f64(obj, "z"),
-    )
+    to_vec3(root.find(key).unwrap())
+}
+
+fn load_vec3_or(root: &Json, key: &str, def: Vec3) -> Vec3 {
+    root.find(key).map_or(def, |obj| to_vec3(obj))
+}
+
+fn load_vec3_array(root: &Json, key: &str) -> Vec<Vec3> {
+    root.find(key).unwrap().as_array().unwrap().iter().map(|obj| to_vec3(obj)).collect()
+}
+
+fn to_vec3(obj: &Json) -> Vec3 {
+    let (x, y, z) = load_triple(obj, ["x", "y", "z"]);
+    Vec3::new(x, y, z)
+}
+
+// Three named values, or the compact [x, y, z]
+fn load_triple(obj: &Json, keys: [&str; 3]) -> (f64, f64, f64) {
+    match obj.as_array() {
+        Some(array) => {
+            if array.len() != 3 {
+                panic!("Expected 3 values, got {}", array.len());
+            }
+            (array[0].as_f64().unwrap(), array[1].as_f64().unwrap(), array[2].as_f64().unwrap())
+        },
+        None        => (load_f64(obj, keys[0]), load_f64(obj, keys[1]), load_f64(obj, keys[2])),
+    }
 }
 
 // String
@@ -227,11 +1058,19 @@ fn load_str<'a>(root: &'a Json, key: &str) -> &'a str {
     root.find(key).unwrap().as_string().unwrap()
 }
 
+fn load_str_or<'a>(root: &'a Json, key: &str, default: &'a str) -> &'a str {
+    root.find(key).map_or(default, |obj| obj.as_string().unwrap())
+}
+
 // f64
 fn load_f64(root: &Json, key: &str) -> f64 {
     root.find(key).unwrap().as_f64().unwrap()
 }
 
+fn load_f64_array(root: &Json, key: &str) -> Vec<f64> {
+    root.find(key).unwrap().as_array().unwrap().iter().map(|obj| obj.as_f64().unwrap()).collect()
+}
+
 fn load_f64_or(root: &Json, key: &str, def: f64) -> f64 {
     let obj = root.find(key);
     if obj.is_none() {
@@ -253,6 +1092,10 @@ fn load_u32_or(root: &Json, key: &str, def: u32) -> u32 {
     obj.unwrap().as_i64().unwrap() as u32
 }
 
+fn load_index(root: &Json, key: &str) -> Option<usize> {
+    root.find(key).map(|n| n.as_u64().unwrap() as usize)
+}
+
 // i32
 fn load_i32(root: &Json, key: &str) -> i32 {
     root.find(key).unwrap().as_i64().unwrap() as i32
@@ -262,3 +1105,105 @@ fn load_i32(root: &Json, key: &str) -> i32 {
 fn load_bool(root: &Json, key: &str) -> bool {
     root.find(key).unwrap().as_boolean().unwrap()
 }
+
+fn load_bool_or(root: &Json, key: &str, def: bool) -> bool {
+    let obj = root.find(key);
+    if obj.is_none() {
+        return def;
+    }
+    obj.unwrap().as_boolean().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::old_io::{ TempDir, USER_RWX };
+    use std::old_io::fs::{ self, File };
+    use serialize::json::Json;
+    use super::{ include, resolve, set };
+
+    fn json(text: &str) -> Json {
+        Json::from_str(text).unwrap()
+    }
+
+    // Files written to a new directory, removed with it
+    fn files(files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new("rt-include").unwrap();
+        for &(name, text) in files.iter() {
+            let path = dir.path().join(name);
+            fs::mkdir_recursive(&path.dir_path(), USER_RWX).unwrap();
+            File::create(&path).write_str(text).unwrap();
+        }
+        dir
+    }
+
+    fn included(root: &str, dir: &TempDir) -> Result<Json, String> {
+        let mut root = json(root);
+        try!(include(&mut root, dir.path()));
+        Ok(root)
+    }
+
+    #[test]
+    fn include_merge_order() {
+        let dir = files(&[("base.json", r#"{ "picture": { "w": 100, "h": 50 }, "objects": [1, 2] }"#)]);
+        let root = included(r#"{ "include": "base.json", "picture": { "w": 200 }, "objects": [3] }"#, &dir).unwrap();
+        assert_eq!(root, json(r#"{ "picture": { "w": 200, "h": 50 }, "objects": [1, 2, 3] }"#));
+    }
+
+    #[test]
+    fn include_in_arrays() {
+        let dir = files(&[("list.json", "[1, 2]"), ("one.json", r#"{ "x": 3 }"#)]);
+        let root = included(r#"{ "objects": [0, { "include": "list.json" }, { "include": "one.json" }, 4] }"#, &dir).unwrap();
+        assert_eq!(root, json(r#"{ "objects": [0, 1, 2, { "x": 3 }, 4] }"#));
+    }
+
+    #[test]
+    fn include_relative_to_the_including_file() {
+        let dir = files(&[("sub/a.json", r#"{ "include": "b.json", "a": 1 }"#), ("sub/b.json", r#"{ "b": 2 }"#)]);
+        let root = included(r#"{ "include": "sub/a.json" }"#, &dir).unwrap();
+        assert_eq!(root, json(r#"{ "a": 1, "b": 2 }"#));
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = files(&[("a.json", r#"{ "include": "b.json" }"#), ("b.json", r#"{ "include": "a.json" }"#)]);
+        let err = included(r#"{ "include": "a.json" }"#, &dir).unwrap_err();
+        assert!(err.contains("includes nested too deeply"), "{}", err);
+    }
+
+    #[test]
+    fn include_errors() {
+        let dir = files(&[("list.json", "[1, 2]")]);
+        assert!(included(r#"{ "include": "missing.json" }"#, &dir).unwrap_err().contains("missing.json"));
+        assert_eq!(included(r#"{ "include": "list.json" }"#, &dir), Err("list.json: expected an object".to_string()));
+        assert_eq!(included(r#"{ "include": 1 }"#, &dir), Err("include: expected a file or an array of files".to_string()));
+    }
+
+    #[test]
+    fn variables_and_materials() {
+        let mut root = json(r#"{
+            "vars": { "r": 2, "d": "= r * 2", "pos": [0, 1, 2] },
+            "materials": { "red": { "color": [1, 0, 0], "spec": "= r / 4" } },
+            "sphere": { "radius": "= d + 0.5", "pos": "= pos", "mat": "red" }
+        }"#);
+        resolve(&mut root).unwrap();
+        assert_eq!(root, json(r#"{ "sphere": { "radius": 4.5, "pos": [0, 1, 2], "mat": { "color": [1, 0, 0], "spec": 0.5 } } }"#));
+    }
+
+    #[test]
+    fn resolve_errors() {
+        let mut root = json(r#"{ "vars": { "a": "= b + 1", "b": "= a" }, "x": "= a" }"#);
+        assert!(resolve(&mut root).unwrap_err().contains("defined with itself"));
+        let mut root = json(r#"{ "sphere": { "mat": "blue" } }"#);
+        assert_eq!(resolve(&mut root), Err("sphere.mat: unknown material blue".to_string()));
+        let mut root = json(r#"{ "x": "= 1 +" }"#);
+        assert_eq!(resolve(&mut root), Err("x: expected a number, a name or '(' at column 4 of \"1 +\"".to_string()));
+    }
+
+    #[test]
+    fn set_paths() {
+        let mut root = json(r#"{ "eye": { "pos": [0, 0, 0] } }"#);
+        set(&mut root, "eye.pos.y", Json::U64(10));
+        set(&mut root, "picture.w", Json::U64(800));
+        assert_eq!(root, json(r#"{ "eye": { "pos": [0, 10, 0] }, "picture": { "w": 800 } }"#));
+    }
+}
@@ -0,0 +1,79 @@
+use std::num::Float;
+use std::cmp::{ min, max };
+use serialize::json::Json;
+use material::Color;
+use buffer::Buffer;
+use export::{ obj, num, int };
+
+/// Joint bilateral filter, edges are kept where the albedo or the normal change
+#[derive(Copy)]
+pub struct Denoiser {
+    radius:       u32, // Half width of the window, in pixels
+    sigma_space:  f64,
+    sigma_color:  f64,
+    sigma_normal: f64,
+    sigma_albedo: f64,
+}
+
+impl Denoiser {
+    /// Panics if a sigma is not greater than 0
+    pub fn new(radius: u32, sigma_space: f64, sigma_color: f64, sigma_normal: f64, sigma_albedo: f64) -> Denoiser {
+        assert!(sigma_space > 0. && sigma_color > 0. && sigma_normal > 0. && sigma_albedo > 0.,
+                "Denoiser sigmas must be greater than 0");
+        Denoiser {
+            radius: radius,
+            sigma_space: sigma_space,
+            sigma_color: sigma_color,
+            sigma_normal: sigma_normal,
+            sigma_albedo: sigma_albedo,
+        }
+    }
+
+    pub fn to_json(&self) -> Json {
+        obj(vec![
+            ("radius", int(self.radius as u64)),
+            ("sigma-space", num(self.sigma_space)),
+            ("sigma-color", num(self.sigma_color)),
+            ("sigma-normal", num(self.sigma_normal)),
+            ("sigma-albedo", num(self.sigma_albedo)),
+        ])
+    }
+
+    // Squared distance between two colors, or two normals stored as colors
+    fn dist(left: Color, right: Color) -> f64 {
+        let (r, g, b) = (left.r - right.r, left.g - right.g, left.b - right.b);
+        r * r + g * g + b * b
+    }
+
+    pub fn apply(&self, image: &Buffer, albedo: &Buffer, normal: &Buffer) -> Buffer {
+        let mut out = Buffer::new(image.w, image.h);
+        let r = self.radius as i32;
+        let space = 2. * self.sigma_space * self.sigma_space;
+        let color = 2. * self.sigma_color * self.sigma_color;
+        let normals = 2. * self.sigma_normal * self.sigma_normal;
+        let albedos = 2. * self.sigma_albedo * self.sigma_albedo;
+
+        for y in 0..image.h {
+            for x in 0..image.w {
+                let (c0, a0, n0) = (image.get(x, y), albedo.get(x, y), normal.get(x, y));
+                let mut sum = Color::new(0., 0., 0.);
+                let mut total = 0.;
+                for qy in max(0, y as i32 - r)..min(image.h as i32, y as i32 + r + 1) {
+                    for qx in max(0, x as i32 - r)..min(image.w as i32, x as i32 + r + 1) {
+                        let (qx, qy) = (qx as u32, qy as u32);
+                        let (dx, dy) = (qx as f64 - x as f64, qy as f64 - y as f64);
+                        let c = image.get(qx, qy);
+                        let weight = (-(dx * dx + dy * dy) / space
+                            - Denoiser::dist(c, c0) / color
+                            - Denoiser::dist(normal.get(qx, qy), n0) / normals
+                            - Denoiser::dist(albedo.get(qx, qy), a0) / albedos).exp();
+                        sum = sum + c * weight;
+                        total += weight;
+                    }
+                }
+                out.set(x, y, sum * (1. / total));
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,72 @@
+// Scenes written back to the canonical JSON read by `config`, every default explicit
+// Objects, lights, skies and shapes write themselves with `to_json`,
+// custom ones that do not are left out
+
+use std::collections::BTreeMap;
+use serialize::json::Json;
+use vec::Vec3;
+use material::Color;
+use object::Geometry;
+use scene::{ Eye, Scene, Picture };
+use schema;
+
+// Geometries of the instances, written once by name
+pub struct Export {
+    geometries: BTreeMap<String, Json>,
+}
+
+impl Export {
+    pub fn geometry(&mut self, name: &str, geometry: &Geometry) {
+        if !self.geometries.contains_key(name) {
+            let json = geometry.to_json(self);
+            self.geometries.insert(name.to_string(), json);
+        }
+    }
+}
+
+pub fn export(eye: &Eye, scene: &Scene, picture: &Picture) -> Json {
+    let mut export = Export { geometries: BTreeMap::new() };
+    let mut scene = scene.to_json(&mut export);
+    if let Json::Object(ref mut obj) = scene {
+        obj.insert("geometries".to_string(), Json::Object(export.geometries));
+    }
+    obj(vec![
+        ("version", Json::U64(schema::VERSION)),
+        ("picture", picture.to_json()),
+        ("eye", eye.to_json()),
+        ("scene", scene),
+    ])
+}
+
+pub fn obj(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+}
+
+// Single key object, like { "sphere": { ... } }
+pub fn variant(name: &str, fields: Vec<(&str, Json)>) -> Json {
+    obj(vec![(name, obj(fields))])
+}
+
+pub fn num(n: f64) -> Json {
+    Json::F64(n)
+}
+
+pub fn int(n: u64) -> Json {
+    Json::U64(n)
+}
+
+pub fn string(s: &str) -> Json {
+    Json::String(s.to_string())
+}
+
+pub fn nums(values: &[f64]) -> Json {
+    Json::Array(values.iter().map(|n| num(*n)).collect())
+}
+
+pub fn vec3(v: Vec3) -> Json {
+    obj(vec![("x", num(v.x)), ("y", num(v.y)), ("z", num(v.z))])
+}
+
+pub fn color(c: Color) -> Json {
+    obj(vec![("r", num(c.r)), ("g", num(c.g)), ("b", num(c.b))])
+}
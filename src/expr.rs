@@ -0,0 +1,224 @@
+// Arithmetic expressions of scene files, like "r * 2 + sin(pi / 4)"
+// Operators + - * / % ^, parentheses, pi, and the functions below
+// Variables are looked up by the caller
+
+use std::num::Float;
+use std::f64::consts::PI;
+
+pub fn eval(input: &str, var: &mut FnMut(&str) -> Result<f64, String>) -> Result<f64, String> {
+    let mut parser = Parser { chars: input.chars().collect(), pos: 0, var: var };
+    let value = try!(parser.sum());
+    parser.skip();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("unexpected character"));
+    }
+    Ok(value)
+}
+
+fn call(name: &str, args: &[f64]) -> Option<f64> {
+    let value = match (name, args.len()) {
+        ("sin", 1)   => args[0].sin(),
+        ("cos", 1)   => args[0].cos(),
+        ("tan", 1)   => args[0].tan(),
+        ("asin", 1)  => args[0].asin(),
+        ("acos", 1)  => args[0].acos(),
+        ("atan", 1)  => args[0].atan(),
+        ("sqrt", 1)  => args[0].sqrt(),
+        ("abs", 1)   => args[0].abs(),
+        ("floor", 1) => args[0].floor(),
+        ("ceil", 1)  => args[0].ceil(),
+        ("round", 1) => args[0].round(),
+        ("rad", 1)   => args[0].to_radians(),
+        ("atan2", 2) => args[0].atan2(args[1]),
+        ("pow", 2)   => args[0].powf(args[1]),
+        ("min", 2)   => args[0].min(args[1]),
+        ("max", 2)   => args[0].max(args[1]),
+        _            => return None,
+    };
+    Some(value)
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos:   usize,
+    var:   &'a mut (FnMut(&str) -> Result<f64, String> + 'a),
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> String {
+        let text: String = self.chars.iter().map(|c| *c).collect();
+        format!("{} at column {} of \"{}\"", message, self.pos + 1, text)
+    }
+
+    fn skip(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    // Next non space character, consumed if it is one of `ops`
+    fn op(&mut self, ops: &str) -> Option<char> {
+        self.skip();
+        match self.chars.get(self.pos) {
+            Some(&c) if ops.chars().any(|op| op == c) => {
+                self.pos += 1;
+                Some(c)
+            },
+            _                                         => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<f64, String> {
+        let mut value = try!(self.product());
+        while let Some(op) = self.op("+-") {
+            let rhs = try!(self.product());
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<f64, String> {
+        let mut value = try!(self.unary());
+        while let Some(op) = self.op("*/%") {
+            let rhs = try!(self.unary());
+            value = match op {
+                '*' => value * rhs,
+                '/' => value / rhs,
+                _   => value % rhs,
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<f64, String> {
+        match self.op("-+") {
+            Some('-') => Ok(-try!(self.unary())),
+            Some(_)   => self.unary(),
+            None      => self.power(),
+        }
+    }
+
+    // Right associative, 2 ^ 3 ^ 2 is 2 ^ 9
+    fn power(&mut self) -> Result<f64, String> {
+        let value = try!(self.atom());
+        if self.op("^").is_some() {
+            let exp = try!(self.unary());
+            return Ok(value.powf(exp));
+        }
+        Ok(value)
+    }
+
+    fn atom(&mut self) -> Result<f64, String> {
+        if self.op("(").is_some() {
+            let value = try!(self.sum());
+            if self.op(")").is_none() {
+                return Err(self.error("expected ')'"));
+            }
+            return Ok(value);
+        }
+        self.skip();
+        let start = self.pos;
+        match self.chars.get(self.pos) {
+            Some(c) if c.is_digit(10) || *c == '.' => {
+                while self.pos < self.chars.len() && (self.chars[self.pos].is_digit(10) || self.chars[self.pos] == '.') {
+                    self.pos += 1;
+                }
+                // Exponent, like 1e-3
+                if self.pos < self.chars.len() && (self.chars[self.pos] == 'e' || self.chars[self.pos] == 'E') {
+                    self.pos += 1;
+                    if self.pos < self.chars.len() && (self.chars[self.pos] == '-' || self.chars[self.pos] == '+') {
+                        self.pos += 1;
+                    }
+                    while self.pos < self.chars.len() && self.chars[self.pos].is_digit(10) {
+                        self.pos += 1;
+                    }
+                }
+                let text: String = self.chars[start..self.pos].iter().map(|c| *c).collect();
+                match text.parse::<f64>() {
+                    Ok(value) => Ok(value),
+                    Err(_)    => {
+                        self.pos = start;
+                        Err(self.error("invalid number"))
+                    },
+                }
+            },
+            Some(c) if c.is_alphabetic() || *c == '_' => {
+                while self.pos < self.chars.len() && (self.chars[self.pos].is_alphanumeric() || self.chars[self.pos] == '_') {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().map(|c| *c).collect();
+                if self.op("(").is_some() {
+                    let mut args = vec![];
+                    if self.op(")").is_none() {
+                        loop {
+                            args.push(try!(self.sum()));
+                            match self.op(",)") {
+                                Some(',') => {},
+                                Some(_)   => break,
+                                None      => return Err(self.error("expected ',' or ')'")),
+                            }
+                        }
+                    }
+                    return match call(name.as_slice(), args.as_slice()) {
+                        Some(value) => Ok(value),
+                        None        => Err(format!("unknown function {} with {} arguments", name, args.len())),
+                    };
+                }
+                if name == "pi" {
+                    return Ok(PI);
+                }
+                (self.var)(name.as_slice())
+            },
+            _                                      => Err(self.error("expected a number, a name or '('")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use super::eval;
+
+    // With r = 3
+    fn eval_r(input: &str) -> Result<f64, String> {
+        eval(input, &mut |name: &str| if name == "r" { Ok(3.) } else { Err(format!("unknown variable {}", name)) })
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval_r("1 + 2 * 3"), Ok(7.));
+        assert_eq!(eval_r("(1 + 2) * 3"), Ok(9.));
+        assert_eq!(eval_r("10 - 4 - 3"), Ok(3.));
+        assert_eq!(eval_r("8 / 4 / 2"), Ok(1.));
+        assert_eq!(eval_r("7 % 4 * 2"), Ok(6.));
+        assert_eq!(eval_r("2 * -r"), Ok(-6.));
+    }
+
+    #[test]
+    fn power() {
+        assert_eq!(eval_r("-r ^ 2"), Ok(-9.));
+        assert_eq!(eval_r("(-r) ^ 2"), Ok(9.));
+        assert_eq!(eval_r("2 ^ 3 ^ 2"), Ok(512.));
+        assert_eq!(eval_r("2 ^ -1"), Ok(0.5));
+        assert_eq!(eval_r("2 * r ^ 2"), Ok(18.));
+    }
+
+    #[test]
+    fn names_and_numbers() {
+        assert_eq!(eval_r("pi"), Ok(PI));
+        assert_eq!(eval_r("max(1, r) + sqrt(16)"), Ok(7.));
+        assert_eq!(eval_r("1e-3 * 1000 + .5"), Ok(1.5));
+        assert_eq!(eval_r("abs(-r) + floor(1.5)"), Ok(4.));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(eval_r("1 +"), Err("expected a number, a name or '(' at column 4 of \"1 +\"".to_string()));
+        assert_eq!(eval_r("(1 + 2"), Err("expected ')' at column 7 of \"(1 + 2\"".to_string()));
+        assert_eq!(eval_r("1 2"), Err("unexpected character at column 3 of \"1 2\"".to_string()));
+        assert_eq!(eval_r("1..2"), Err("invalid number at column 1 of \"1..2\"".to_string()));
+        assert_eq!(eval_r("max(1 2)"), Err("expected ',' or ')' at column 7 of \"max(1 2)\"".to_string()));
+        assert_eq!(eval_r("foo(1)"), Err("unknown function foo with 1 arguments".to_string()));
+        assert_eq!(eval_r("d * 2"), Err("unknown variable d".to_string()));
+    }
+}
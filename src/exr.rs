@@ -0,0 +1,73 @@
+use std::old_io::Writer;
+use std::old_io::fs::File;
+
+// Header attribute: name, type, size and value
+fn attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    out.push_all(name.as_bytes());
+    out.push(0);
+    out.push_all(kind.as_bytes());
+    out.push(0);
+    out.write_le_i32(value.len() as i32).unwrap();
+    out.push_all(value);
+}
+
+fn window(w: usize, h: usize) -> Vec<u8> {
+    let mut value = vec![];
+    for &x in [0, 0, w as i32 - 1, h as i32 - 1].iter() {
+        value.write_le_i32(x).unwrap();
+    }
+    value
+}
+
+// Uncompressed scanline OpenEXR, one 32-bit float channel per named layer of values
+// Names like "normal.X" group channels into layers
+pub fn save(path: &Path, w: usize, h: usize, channels: &[(String, Vec<f64>)]) {
+    let mut channels: Vec<&(String, Vec<f64>)> = channels.iter().collect();
+    channels.sort_by(|l, r| l.0.cmp(&r.0));
+
+    // Magic number and version 2, single part scanline file
+    let mut out: Vec<u8> = vec![];
+    out.write_le_i32(20000630).unwrap();
+    out.write_le_i32(2).unwrap();
+
+    // Channel list, sorted by name
+    let mut list = vec![];
+    for &&(ref name, _) in channels.iter() {
+        list.push_all(name.as_bytes());
+        list.push(0);
+        list.write_le_i32(2).unwrap(); // Float
+        list.push_all(&[0, 0, 0, 0]);  // Linear and reserved
+        list.write_le_i32(1).unwrap(); // Sampling
+        list.write_le_i32(1).unwrap();
+    }
+    list.push(0);
+    attribute(&mut out, "channels", "chlist", list.as_slice());
+    attribute(&mut out, "compression", "compression", &[0]);
+    attribute(&mut out, "dataWindow", "box2i", window(w, h).as_slice());
+    attribute(&mut out, "displayWindow", "box2i", window(w, h).as_slice());
+    attribute(&mut out, "lineOrder", "lineOrder", &[0]);
+    let mut one = vec![];
+    one.write_le_f32(1.).unwrap();
+    attribute(&mut out, "pixelAspectRatio", "float", one.as_slice());
+    attribute(&mut out, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut out, "screenWindowWidth", "float", one.as_slice());
+    out.push(0);
+
+    // Offsets of the scanlines, each one in its own block
+    let size = channels.len() * w * 4;
+    let start = out.len() + h * 8;
+    for y in 0..h {
+        out.write_le_u64((start + y * (8 + size)) as u64).unwrap();
+    }
+    for y in 0..h {
+        out.write_le_i32(y as i32).unwrap();
+        out.write_le_i32(size as i32).unwrap();
+        for &&(_, ref values) in channels.iter() {
+            for x in 0..w {
+                out.write_le_f32(values[y * w + x] as f32).unwrap();
+            }
+        }
+    }
+
+    File::create(path).write_all(out.as_slice()).unwrap();
+}
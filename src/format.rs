@@ -0,0 +1,171 @@
+// Scene files in TOML, YAML or RON, converted to the JSON read by `config`
+// Vectors and colors can be written [x, y, z] and [r, g, b] in every format
+
+use std::f64::{ INFINITY, NEG_INFINITY, NAN };
+use std::collections::BTreeMap;
+use serialize::json::Json;
+use toml;
+use yaml_rust::{ Yaml, YamlLoader };
+use ron;
+
+#[derive(Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+    Ron,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json"         => Some(Format::Json),
+            "toml"         => Some(Format::Toml),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "ron"          => Some(Format::Ron),
+            _              => None,
+        }
+    }
+
+    // From the extension, JSON by default
+    pub fn from_path(path: &Path) -> Format {
+        path.extension_str().and_then(|ext| Format::from_name(ext)).unwrap_or(Format::Json)
+    }
+
+    pub fn parse(&self, input: &str) -> Result<Json, String> {
+        match *self {
+            Format::Json => Json::from_str(input).map_err(|err| format!("{}", err)),
+            Format::Toml => parse_toml(input),
+            Format::Yaml => parse_yaml(input),
+            Format::Ron  => ron::parse(input),
+        }
+    }
+}
+
+// Same integers as the JSON parser, unsigned when they can be
+fn integer(n: i64) -> Json {
+    if n >= 0 { Json::U64(n as u64) } else { Json::I64(n) }
+}
+
+fn parse_toml(input: &str) -> Result<Json, String> {
+    let mut parser = toml::Parser::new(input);
+    match parser.parse() {
+        Some(table) => Ok(from_toml(toml::Value::Table(table))),
+        None        => {
+            let errors: Vec<String> = parser.errors.iter().map(|err| {
+                let (line, col) = parser.to_linecol(err.lo);
+                format!("{} at line {} column {}", err.desc, line + 1, col + 1)
+            }).collect();
+            Err(errors.connect("\n"))
+        },
+    }
+}
+
+fn from_toml(value: toml::Value) -> Json {
+    match value {
+        toml::Value::String(s)    => Json::String(s),
+        toml::Value::Integer(n)   => integer(n),
+        toml::Value::Float(n)     => Json::F64(n),
+        toml::Value::Boolean(b)   => Json::Boolean(b),
+        toml::Value::Datetime(s)  => Json::String(s),
+        toml::Value::Array(array) => Json::Array(array.into_iter().map(from_toml).collect()),
+        toml::Value::Table(table) => Json::Object(table.into_iter().map(|(key, value)| (key, from_toml(value))).collect()),
+    }
+}
+
+// Only the first document of the file
+fn parse_yaml(input: &str) -> Result<Json, String> {
+    match YamlLoader::load_from_str(input) {
+        Ok(docs) => match docs.into_iter().next() {
+            Some(doc) => from_yaml(doc),
+            None      => Err("empty YAML file".to_string()),
+        },
+        Err(err) => Err(format!("{}", err)),
+    }
+}
+
+fn from_yaml(value: Yaml) -> Result<Json, String> {
+    let json = match value {
+        Yaml::Real(s)      => Json::F64(try!(yaml_real(s.as_slice()))),
+        Yaml::Integer(n)   => integer(n),
+        Yaml::String(s)    => Json::String(s),
+        Yaml::Boolean(b)   => Json::Boolean(b),
+        Yaml::Array(array) => {
+            let mut items = vec![];
+            for item in array.into_iter() {
+                items.push(try!(from_yaml(item)));
+            }
+            Json::Array(items)
+        },
+        Yaml::Hash(hash)   => {
+            let mut obj = BTreeMap::new();
+            for (key, value) in hash.into_iter() {
+                obj.insert(yaml_key(key), try!(from_yaml(value)));
+            }
+            Json::Object(obj)
+        },
+        _                  => Json::Null,
+    };
+    Ok(json)
+}
+
+// Floats are kept as text by yaml-rust, with the infinities and NaN of YAML that `parse` does not know
+fn yaml_real(s: &str) -> Result<f64, String> {
+    match s {
+        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => Ok(INFINITY),
+        "-.inf" | "-.Inf" | "-.INF"                            => Ok(NEG_INFINITY),
+        ".nan" | ".NaN" | ".NAN"                               => Ok(NAN),
+        _                                                      => s.parse::<f64>().map_err(|_| format!("invalid number {}", s)),
+    }
+}
+
+fn yaml_key(key: Yaml) -> String {
+    match key {
+        Yaml::String(s) | Yaml::Real(s) => s,
+        Yaml::Integer(n)                => n.to_string(),
+        Yaml::Boolean(b)                => b.to_string(),
+        _                               => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serialize::json::Json;
+    use super::Format;
+
+    fn parse(format: Format, text: &str) -> Json {
+        format.parse(text).unwrap()
+    }
+
+    #[test]
+    fn vectors() {
+        let json = parse(Format::Json, r#"{ "pos": [0.5, 1.5, -2.5], "dim": [1, 2, 3] }"#);
+        assert_eq!(parse(Format::Toml, "pos = [0.5, 1.5, -2.5]\ndim = [1, 2, 3]"), json);
+        assert_eq!(parse(Format::Yaml, "pos: [0.5, 1.5, -2.5]\ndim: [1, 2, 3]"), json);
+        assert_eq!(parse(Format::Yaml, "pos:\n  - 0.5\n  - 1.5\n  - -2.5\ndim: [1, 2, 3]"), json);
+        assert_eq!(parse(Format::Ron, "(pos: (0.5, 1.5, -2.5), dim: [1, 2, 3])"), json);
+    }
+
+    #[test]
+    fn tables() {
+        let json = parse(Format::Json, r#"{ "scene": { "objects": [{ "sphere": { "pos": [0, 0, 0], "radius": 10 } }] } }"#);
+        assert_eq!(parse(Format::Toml, "[[scene.objects]]\nsphere = { pos = [0, 0, 0], radius = 10 }"), json);
+        assert_eq!(parse(Format::Yaml, "scene:\n  objects:\n    - sphere: { pos: [0, 0, 0], radius: 10 }"), json);
+    }
+
+    #[test]
+    fn yaml_special_floats() {
+        let json = parse(Format::Yaml, "[.inf, -.Inf, .nan, 1e3]");
+        let values: Vec<f64> = json.as_array().unwrap().iter().map(|value| value.as_f64().unwrap()).collect();
+        assert!(values[0].is_infinite() && values[0] > 0.);
+        assert!(values[1].is_infinite() && values[1] < 0.);
+        assert!(values[2].is_nan());
+        assert_eq!(values[3], 1000.);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Format::Toml.parse("pos = [0, 0.5]").is_err());
+        assert_eq!(Format::Yaml.parse(""), Err("empty YAML file".to_string()));
+    }
+}
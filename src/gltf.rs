@@ -0,0 +1,574 @@
+// glTF 2.0 import: meshes, node transforms, metallic-roughness materials with base color textures,
+// perspective cameras and KHR_lights_punctual lights
+// .gltf files with external or embedded buffers, or binary .glb files
+
+use std::mem;
+use std::num::Float;
+use std::f64::consts::PI;
+use std::sync::Arc;
+use std::collections::{ HashMap, BTreeMap };
+use std::old_io::fs::File;
+use std::old_io::stdio;
+use serialize::json::Json;
+use serialize::base64::FromBase64;
+use image::{ self, GenericImage };
+use vec::{ Vec3, cross, rotate };
+use ray::{ Ray, Inter };
+use material::{ Color, Material };
+use object::Object;
+use mesh::{ Mesh, Triangle, Surface, Texture };
+use light::{ Light, Bulb, Sun, Spot };
+use scene::Eye;
+use export::{ Export, obj, variant, num, int, string, vec3 };
+use schema;
+
+// Shininess of the imported lights, glTF has no specular term
+const SHININESS: i32 = 20;
+
+// Affine transform, m[row][col], applied to column vectors
+type Mat = [[f64; 4]; 4];
+
+const IDENTITY: Mat = [[1., 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.], [0., 0., 0., 1.]];
+
+fn mul(a: &Mat, b: &Mat) -> Mat {
+    let mut m = [[0.; 4]; 4];
+    for i in 0..4 {
+        for j in 0..4 {
+            for k in 0..4 {
+                m[i][j] += a[i][k] * b[k][j];
+            }
+        }
+    }
+    m
+}
+
+fn column(m: &Mat, j: usize) -> Vec3 {
+    Vec3::new(m[0][j], m[1][j], m[2][j])
+}
+
+fn transform_point(m: &Mat, pos: Vec3) -> Vec3 {
+    transform_dir(m, pos) + column(m, 3)
+}
+
+fn transform_dir(m: &Mat, dir: Vec3) -> Vec3 {
+    column(m, 0) * dir.x + column(m, 1) * dir.y + column(m, 2) * dir.z
+}
+
+// Inverse transpose up to a scale, the cofactors of the linear part
+fn transform_normal(m: &Mat, normal: Vec3) -> Vec3 {
+    let (x, y, z) = (column(m, 0), column(m, 1), column(m, 2));
+    (cross(y, z) * normal.x + cross(z, x) * normal.y + cross(x, y) * normal.z).normalize()
+}
+
+// Translation, rotation quaternion (x, y, z, w) and scale of a node
+fn trs(t: &[f64], r: &[f64], s: &[f64]) -> Mat {
+    let (x, y, z, w) = (r[0], r[1], r[2], r[3]);
+    let rot = [
+        [1. - 2. * (y * y + z * z), 2. * (x * y - z * w), 2. * (x * z + y * w)],
+        [2. * (x * y + z * w), 1. - 2. * (x * x + z * z), 2. * (y * z - x * w)],
+        [2. * (x * z - y * w), 2. * (y * z + x * w), 1. - 2. * (x * x + y * y)],
+    ];
+    let mut m = IDENTITY;
+    for i in 0..3 {
+        for j in 0..3 {
+            m[i][j] = rot[i][j] * s[j];
+        }
+        m[i][3] = t[i];
+    }
+    m
+}
+
+// Position, angles of `rotate` and scale, like an instance
+fn placement(pos: Vec3, dir: Vec3, scale: f64) -> Mat {
+    let axes = [Vec3::new(1., 0., 0.), Vec3::new(0., 1., 0.), Vec3::new(0., 0., 1.)];
+    let mut m = IDENTITY;
+    for j in 0..3 {
+        let axis = rotate(axes[j], dir) * scale;
+        m[0][j] = axis.x;
+        m[1][j] = axis.y;
+        m[2][j] = axis.z;
+    }
+    m[0][3] = pos.x;
+    m[1][3] = pos.y;
+    m[2][3] = pos.z;
+    m
+}
+
+// Angles of `rotate`, x then y then z, giving the orientation of a transform
+fn angles(m: &Mat) -> Vec3 {
+    let (x, y, z) = (column(m, 0).normalize(), column(m, 1).normalize(), column(m, 2).normalize());
+    Vec3::new(y.z.atan2(z.z), (-x.z).max(-1.).min(1.).asin(), x.y.atan2(x.x))
+}
+
+fn u16_at(data: &[u8], pos: usize) -> u16 {
+    data[pos] as u16 | (data[pos + 1] as u16) << 8
+}
+
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    data[pos] as u32 | (data[pos + 1] as u32) << 8 | (data[pos + 2] as u32) << 16 | (data[pos + 3] as u32) << 24
+}
+
+// Component of an accessor, integers normalized to [0, 1] or [-1, 1] when asked
+fn component(data: &[u8], pos: usize, kind: usize, normalized: bool) -> f64 {
+    let (value, max) = match kind {
+        5120 => (data[pos] as i8 as f64, 127.),
+        5121 => (data[pos] as f64, 255.),
+        5122 => (u16_at(data, pos) as i16 as f64, 32767.),
+        5123 => (u16_at(data, pos) as f64, 65535.),
+        5125 => (u32_at(data, pos) as f64, 4294967295.),
+        _    => (unsafe { mem::transmute::<u32, f32>(u32_at(data, pos)) } as f64, 1.),
+    };
+    if normalized && kind != 5126 { (value / max).max(-1.) } else { value }
+}
+
+fn index(root: &Json, key: &str) -> Option<usize> {
+    root.find(key).and_then(|n| n.as_u64()).map(|n| n as usize)
+}
+
+fn indices(root: &Json, key: &str) -> Vec<usize> {
+    match root.find(key).and_then(|array| array.as_array()) {
+        Some(array) => array.iter().map(|n| n.as_u64().unwrap() as usize).collect(),
+        None        => vec![],
+    }
+}
+
+fn number(root: &Json, key: &str, default: f64) -> f64 {
+    root.find(key).and_then(|n| n.as_f64()).unwrap_or(default)
+}
+
+fn numbers(root: &Json, key: &str, default: &[f64]) -> Vec<f64> {
+    match root.find(key).and_then(|array| array.as_array()) {
+        Some(array) => array.iter().map(|n| n.as_f64().unwrap()).collect(),
+        None        => default.to_vec(),
+    }
+}
+
+// Local transform of a node, a matrix in column order or TRS
+fn local(node: &Json) -> Mat {
+    if node.find("matrix").is_some() {
+        let values = numbers(node, "matrix", &[]);
+        let mut m = IDENTITY;
+        for j in 0..4 {
+            for i in 0..4 {
+                m[i][j] = values[j * 4 + i];
+            }
+        }
+        return m;
+    }
+    trs(
+        numbers(node, "translation", &[0., 0., 0.]).as_slice(),
+        numbers(node, "rotation", &[0., 0., 0., 1.]).as_slice(),
+        numbers(node, "scale", &[1., 1., 1.]).as_slice(),
+    )
+}
+
+// Binary container: a header, a JSON chunk and an optional binary chunk
+fn parse_glb(path: &str, data: &[u8]) -> (String, Option<Vec<u8>>) {
+    if u32_at(data, 4) != 2 {
+        panic!("{}: only glTF 2.0 is supported", path);
+    }
+    let (mut json, mut bin) = (None, None);
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let len = u32_at(data, pos) as usize;
+        let chunk = data[(pos + 8)..(pos + 8 + len)].to_vec();
+        match u32_at(data, pos + 4) {
+            0x4E4F534A => json = Some(String::from_utf8(chunk).unwrap()),
+            0x004E4942 => bin = Some(chunk),
+            _          => {},
+        }
+        pos += 8 + len;
+    }
+    (json.expect("glb file without JSON chunk"), bin)
+}
+
+#[doc(hidden)]
+pub fn is_gltf(path: &Path) -> bool {
+    match path.extension_str() {
+        Some("gltf") | Some("glb") => true,
+        _                          => false,
+    }
+}
+
+// Scene rendering a glTF file on its own, with its cameras and lights, to "<file>.png"
+#[doc(hidden)]
+pub fn wrap(path: &str) -> Json {
+    let output = Path::new(path).with_extension("png");
+    obj(vec![
+        ("version", int(schema::VERSION)),
+        ("picture", obj(vec![("w", int(800)), ("h", int(600)), ("path", string(output.as_str().unwrap()))])),
+        ("scene", obj(vec![("gltf", string(path))])),
+    ])
+}
+
+/// glTF or glb file with its buffers
+pub struct Gltf {
+    path:    String,
+    json:    Json,
+    buffers: Vec<Vec<u8>>,
+    dir:     Path, // Directory of the file, external buffers and images are relative to it
+}
+
+impl Gltf {
+    /// Panics when the file or its buffers cannot be read, or when it is not glTF 2.0
+    pub fn load(path: &str) -> Gltf {
+        let data = File::open(&Path::new(path)).read_to_end().unwrap();
+        let (text, bin) = if data.starts_with(b"glTF") {
+            parse_glb(path, data.as_slice())
+        } else {
+            (String::from_utf8(data).unwrap(), None)
+        };
+        let json = Json::from_str(text.as_slice()).unwrap();
+        match json.find_path(&["asset", "version"]).and_then(|version| version.as_string()) {
+            Some(version) if version.starts_with("2.") => {},
+            _                                          => panic!("{}: only glTF 2.0 is supported", path),
+        }
+
+        let mut gltf = Gltf { path: path.to_string(), json: json, buffers: vec![], dir: Path::new(path).dir_path() };
+        let buffers: Vec<Vec<u8>> = gltf.items("buffers").iter().map(|buffer| {
+            match buffer.find("uri").and_then(|uri| uri.as_string()) {
+                Some(uri) => gltf.uri(uri),
+                None      => bin.clone().expect("glTF buffer without data"),
+            }
+        }).collect();
+        gltf.buffers = buffers;
+        gltf
+    }
+
+    fn items(&self, key: &str) -> &[Json] {
+        match self.json.find(key).and_then(|items| items.as_array()) {
+            Some(items) => items.as_slice(),
+            None        => &[],
+        }
+    }
+
+    // Data URI, or file relative to the glTF file
+    fn uri(&self, uri: &str) -> Vec<u8> {
+        if uri.starts_with("data:") {
+            let start = uri.find(',').expect("Invalid data URI") + 1;
+            return uri[start..].from_base64().unwrap();
+        }
+        File::open(&self.dir.join(uri)).read_to_end().unwrap()
+    }
+
+    // Elements of an accessor, each one a list of components
+    fn accessor(&self, i: usize) -> Vec<Vec<f64>> {
+        let accessor = &self.items("accessors")[i];
+        if accessor.find("sparse").is_some() {
+            panic!("{}: sparse accessors are not supported", self.path);
+        }
+        let count = index(accessor, "count").unwrap();
+        let size = match accessor.find("type").and_then(|kind| kind.as_string()).unwrap_or("SCALAR") {
+            "SCALAR" => 1,
+            "VEC2"   => 2,
+            "VEC3"   => 3,
+            "VEC4"   => 4,
+            "MAT2"   => 4,
+            "MAT3"   => 9,
+            "MAT4"   => 16,
+            kind     => panic!("{}: unknown accessor type {}", self.path, kind),
+        };
+        let kind = index(accessor, "componentType").unwrap();
+        let bytes = match kind {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _           => panic!("{}: unknown component type {}", self.path, kind),
+        };
+        let normalized = accessor.find("normalized").and_then(|b| b.as_boolean()).unwrap_or(false);
+
+        // Without buffer view, every component is 0
+        let view = match index(accessor, "bufferView") {
+            Some(view) => &self.items("bufferViews")[view],
+            None       => return (0..count).map(|_| vec![0.; size]).collect(),
+        };
+        let data = self.buffers[index(view, "buffer").unwrap()].as_slice();
+        let offset = index(view, "byteOffset").unwrap_or(0) + index(accessor, "byteOffset").unwrap_or(0);
+        let stride = index(view, "byteStride").unwrap_or(size * bytes);
+        (0..count).map(|e| {
+            (0..size).map(|c| component(data, offset + e * stride + c * bytes, kind, normalized)).collect()
+        }).collect()
+    }
+
+    // Nodes of a scene with their world transforms, the default scene without index
+    fn nodes(&self, scene: Option<usize>) -> Vec<(usize, Mat)> {
+        let roots = match scene.or(index(&self.json, "scene")) {
+            Some(scene)                            => indices(&self.items("scenes")[scene], "nodes"),
+            None if !self.items("scenes").is_empty() => indices(&self.items("scenes")[0], "nodes"),
+            None                                   => {
+                // Every node that is nobody's child
+                let children: Vec<usize> = self.items("nodes").iter().flat_map(|node| indices(node, "children").into_iter()).collect();
+                (0..self.items("nodes").len()).filter(|i| !children.contains(i)).collect()
+            },
+        };
+        let mut nodes = vec![];
+        for &root in roots.iter() {
+            self.walk(root, &IDENTITY, &mut nodes);
+        }
+        nodes
+    }
+
+    fn walk(&self, i: usize, parent: &Mat, nodes: &mut Vec<(usize, Mat)>) {
+        let node = &self.items("nodes")[i];
+        let world = mul(parent, &local(node));
+        nodes.push((i, world));
+        for &child in indices(node, "children").iter() {
+            self.walk(child, &world, nodes);
+        }
+    }
+
+    // Every mesh of a scene, in world space moved by `transform`
+    fn mesh(&self, scene: Option<usize>, transform: &Mat) -> Mesh {
+        let mut triangles = vec![];
+        let mut surfaces = vec![];
+        let mut by_material = HashMap::new();
+        let mut textures = HashMap::new();
+        for &(node, ref world) in self.nodes(scene).iter() {
+            let mesh = match index(&self.items("nodes")[node], "mesh") {
+                Some(mesh) => mesh,
+                None       => continue,
+            };
+            let world = mul(transform, world);
+            for primitive in self.items("meshes")[mesh].find("primitives").unwrap().as_array().unwrap().iter() {
+                let material = index(primitive, "material");
+                if !by_material.contains_key(&material) {
+                    by_material.insert(material, surfaces.len());
+                    surfaces.push(self.surface(material, &mut textures));
+                }
+                self.primitive(primitive, &world, *by_material.get(&material).unwrap(), &mut triangles);
+            }
+        }
+        Mesh::new(triangles, surfaces)
+    }
+
+    // Triangles of a primitive, strips and fans are unrolled, points and lines are left out
+    fn primitive(&self, primitive: &Json, world: &Mat, surface: usize, triangles: &mut Vec<Triangle>) {
+        let attributes = primitive.find("attributes").unwrap();
+        let points: Vec<Vec3> = match index(attributes, "POSITION") {
+            Some(accessor) => self.accessor(accessor).iter().map(|p| transform_point(world, Vec3::new(p[0], p[1], p[2]))).collect(),
+            None           => return,
+        };
+        let normals: Option<Vec<Vec3>> = index(attributes, "NORMAL").map(|accessor| {
+            self.accessor(accessor).iter().map(|n| transform_normal(world, Vec3::new(n[0], n[1], n[2]))).collect()
+        });
+        let uvs: Option<Vec<(f64, f64)>> = index(attributes, "TEXCOORD_0").map(|accessor| {
+            self.accessor(accessor).iter().map(|uv| (uv[0], uv[1])).collect()
+        });
+        let order: Vec<usize> = match index(primitive, "indices") {
+            Some(accessor) => self.accessor(accessor).iter().map(|i| i[0] as usize).collect(),
+            None           => (0..points.len()).collect(),
+        };
+        let corners: Vec<[usize; 3]> = match index(primitive, "mode").unwrap_or(4) {
+            4 => (0..(order.len() / 3)).map(|i| [order[3 * i], order[3 * i + 1], order[3 * i + 2]]).collect(),
+            5 => (2..order.len()).map(|i| {
+                if i % 2 == 0 { [order[i - 2], order[i - 1], order[i]] } else { [order[i - 1], order[i - 2], order[i]] }
+            }).collect(),
+            6 => (2..order.len()).map(|i| [order[0], order[i - 1], order[i]]).collect(),
+            _ => vec![],
+        };
+        for c in corners.iter() {
+            triangles.push(Triangle {
+                points:  [points[c[0]], points[c[1]], points[c[2]]],
+                normals: normals.as_ref().map(|n| [n[c[0]], n[c[1]], n[c[2]]]),
+                uvs:     uvs.as_ref().map(|uv| [uv[c[0]], uv[c[1]], uv[c[2]]]),
+                surface: surface,
+            });
+        }
+    }
+
+    // Metals reflect, smooth surfaces shine, blended alpha refracts
+    fn surface(&self, material: Option<usize>, textures: &mut HashMap<usize, Arc<Texture>>) -> Surface {
+        let empty = Json::Object(BTreeMap::new());
+        let material = match material {
+            Some(i) => &self.items("materials")[i],
+            None    => &empty,
+        };
+        let pbr = material.find("pbrMetallicRoughness").unwrap_or(&empty);
+        let base = numbers(pbr, "baseColorFactor", &[1., 1., 1., 1.]);
+        let metallic = number(pbr, "metallicFactor", 1.);
+        let smooth = 1. - number(pbr, "roughnessFactor", 1.);
+        let alpha = if material.find("alphaMode").and_then(|mode| mode.as_string()) == Some("BLEND") { base[3] } else { 1. };
+        let ior = material.find_path(&["extensions", "KHR_materials_ior", "ior"]).and_then(|n| n.as_f64()).unwrap_or(1.5);
+        let mat = Material::new(
+            Color::new(base[0], base[1], base[2]),
+            smooth,
+            1. - metallic * smooth,
+            1. - alpha,
+            ior,
+            metallic * smooth,
+        );
+
+        let image = pbr.find_path(&["baseColorTexture", "index"])
+            .and_then(|i| i.as_u64())
+            .and_then(|i| index(&self.items("textures")[i as usize], "source"));
+        let texture = image.map(|image| {
+            if !textures.contains_key(&image) {
+                textures.insert(image, Arc::new(self.texture(image)));
+            }
+            textures.get(&image).unwrap().clone()
+        });
+        Surface::new(Arc::new(mat), texture)
+    }
+
+    // Image from a file, a data URI or a buffer view
+    fn texture(&self, i: usize) -> Texture {
+        let source = &self.items("images")[i];
+        let data = match source.find("uri").and_then(|uri| uri.as_string()) {
+            Some(uri) => self.uri(uri),
+            None      => {
+                let view = &self.items("bufferViews")[index(source, "bufferView").unwrap()];
+                let start = index(view, "byteOffset").unwrap_or(0);
+                let end = start + index(view, "byteLength").unwrap();
+                self.buffers[index(view, "buffer").unwrap()][start..end].to_vec()
+            },
+        };
+        let img = image::load_from_memory(data.as_slice()).unwrap().to_rgb();
+        let (w, h) = img.dimensions();
+        let mut pixels = Vec::with_capacity((w * h) as usize);
+        for y in 0..h {
+            for x in 0..w {
+                let data = img.get_pixel(x, y).data;
+                pixels.push(Color::new(data[0] as f64 / 255., data[1] as f64 / 255., data[2] as f64 / 255.));
+            }
+        }
+        Texture::new(w as usize, h as usize, pixels)
+    }
+
+    /// KHR_lights_punctual lights, white, the brightest one of intensity `scale` and the others in proportion
+    /// Colors only keep their luminance, `range` is ignored
+    pub fn lights(&self, scene: Option<usize>, scale: f64) -> Vec<Box<Light + 'static>> {
+        let all = match self.json.find_path(&["extensions", "KHR_lights_punctual", "lights"]).and_then(|all| all.as_array()) {
+            Some(all) => all,
+            None      => return vec![],
+        };
+
+        // Candela and lux have no meaning here, the lights do not fade with distance
+        let max = all.iter().fold(0., |acc: f64, light| acc.max(number(light, "intensity", 1.)));
+        let unit = if max > 0. { scale / max } else { scale };
+
+        let mut lights = vec![];
+        for &(node, ref world) in self.nodes(scene).iter() {
+            let index = match self.items("nodes")[node].find_path(&["extensions", "KHR_lights_punctual", "light"]) {
+                Some(light) => light.as_u64().unwrap() as usize,
+                None        => continue,
+            };
+            let light = &all[index];
+            let color = numbers(light, "color", &[1., 1., 1.]);
+            if color.iter().any(|&c| c != 1.) {
+                let warning = format!("warning: {}: light {} is colored, only its luminance is kept", self.path, index);
+                let _ = stdio::stderr().write_line(warning.as_slice());
+            }
+            let luminance = 0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2];
+            let pos = transform_point(world, Vec3::new(0., 0., 0.));
+            let dir = transform_dir(world, Vec3::new(0., 0., -1.));
+            let power = number(light, "intensity", 1.) * luminance * unit;
+            lights.push(match light.find("type").and_then(|kind| kind.as_string()).unwrap_or("point") {
+                "directional" => box Sun::new(dir, power, SHININESS, power) as Box<Light>,
+                "spot"        => {
+                    let (inner, outer) = match light.find("spot") {
+                        Some(spot) => (number(spot, "innerConeAngle", 0.), number(spot, "outerConeAngle", PI / 4.)),
+                        None       => (0., PI / 4.),
+                    };
+                    box Spot::new(pos, dir, outer, outer - inner, None, power, SHININESS, power) as Box<Light>
+                },
+                _             => box Bulb::new(pos, power, SHININESS, power) as Box<Light>,
+            });
+        }
+        lights
+    }
+
+    /// Eye of a perspective camera, the first one without index
+    /// glTF gives the vertical field of view, the eye takes the tangent of its half horizontal angle doubled,
+    /// see `Picture::ray_at`
+    pub fn eye(&self, scene: Option<usize>, camera: Option<usize>, aspect: f64) -> Option<Eye> {
+        let cameras = self.items("cameras");
+        for &(node, ref world) in self.nodes(scene).iter() {
+            let perspective = match index(&self.items("nodes")[node], "camera") {
+                Some(i) if camera.map_or(true, |camera| camera == i) => cameras[i].find("perspective"),
+                _                                                    => None,
+            };
+            if let Some(perspective) = perspective {
+                let yfov = number(perspective, "yfov", PI / 3.);
+                let fov = 2. * (2. * (yfov / 2.).tan() * aspect).atan();
+                return Some(Eye::new(transform_point(world, Vec3::new(0., 0., 0.)), angles(world), fov));
+            }
+        }
+        None
+    }
+
+    /// Eye looking along -z at the whole scene, for files without cameras
+    pub fn overview(&self, scene: Option<usize>, aspect: f64) -> Eye {
+        let (mut lo, mut hi) = (Vec3::new(0., 0., 0.), Vec3::new(0., 0., 0.));
+        let mut first = true;
+        for &(node, ref world) in self.nodes(scene).iter() {
+            let mesh = match index(&self.items("nodes")[node], "mesh") {
+                Some(mesh) => mesh,
+                None       => continue,
+            };
+            for primitive in self.items("meshes")[mesh].find("primitives").unwrap().as_array().unwrap().iter() {
+                let positions = match primitive.find("attributes").and_then(|attributes| index(attributes, "POSITION")) {
+                    Some(accessor) => self.accessor(accessor),
+                    None           => continue,
+                };
+                for p in positions.iter() {
+                    let p = transform_point(world, Vec3::new(p[0], p[1], p[2]));
+                    if first {
+                        lo = p;
+                        hi = p;
+                        first = false;
+                    }
+                    lo = Vec3::new(lo.x.min(p.x), lo.y.min(p.y), lo.z.min(p.z));
+                    hi = Vec3::new(hi.x.max(p.x), hi.y.max(p.y), hi.z.max(p.z));
+                }
+            }
+        }
+        let fov = PI / 3.;
+        let radius = ((hi - lo).length() / 2.).max(1.);
+        // Tangent of the half angle of the narrowest side of the picture
+        let half = (fov / 2.).tan() / 2. / aspect.max(1.);
+        Eye::new((lo + hi) / 2. + Vec3::new(0., 0., radius / half + radius), Vec3::new(0., 0., 0.), fov)
+    }
+}
+
+/// Meshes of a glTF scene, placed like an instance
+#[allow(dead_code)]
+pub struct Model {
+    path:  String,
+    scene: Option<usize>,
+    pos:   Vec3,
+    dir:   Vec3,
+    scale: f64,
+    mesh:  Mesh,
+}
+
+impl Model {
+    /// `scene` defaults to the scene of the file, then to its first one
+    #[allow(dead_code)]
+    pub fn new(gltf: &Gltf, scene: Option<usize>, pos: Vec3, dir: Vec3, scale: f64) -> Model {
+        Model {
+            path: gltf.path.clone(),
+            scene: scene,
+            pos: pos,
+            dir: dir,
+            scale: scale,
+            mesh: gltf.mesh(scene, &placement(pos, dir, scale)),
+        }
+    }
+}
+
+impl Object for Model {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        self.mesh.intersect(ray)
+    }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        let mut fields = vec![("path", string(self.path.as_slice()))];
+        if let Some(scene) = self.scene {
+            fields.push(("scene", int(scene as u64)));
+        }
+        fields.push(("pos", vec3(self.pos)));
+        fields.push(("dir", vec3(self.dir)));
+        fields.push(("scale", num(self.scale)));
+        variant("gltf", fields)
+    }
+}
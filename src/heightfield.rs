@@ -0,0 +1,243 @@
+use std::num::Float;
+use std::f64::INFINITY;
+use std::sync::Arc;
+use std::old_io::fs::File;
+use serialize::json::Json;
+use image::{ self, GenericImage };
+use vec::{ Vec3, dot, cross };
+use ray::{ Ray, Inter };
+use material::Material;
+use object::{ Object, EPSILON, slab, facing };
+use export::{ Export, variant, int, nums, vec3 };
+
+// Load heights in [0, 1] from the luminance of an image
+#[doc(hidden)]
+pub fn load_image(path: &str) -> (usize, usize, Vec<f64>) {
+    let img = image::open(&Path::new(path)).unwrap().to_luma();
+    let (w, h) = img.dimensions();
+    let mut heights = Vec::with_capacity((w * h) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            heights.push(img.get_pixel(x, y).data[0] as f64 / 255.);
+        }
+    }
+    (w as usize, h as usize, heights)
+}
+
+// Load heights from a text file, one row of numbers per line
+#[doc(hidden)]
+pub fn load_grid(path: &str) -> Result<(usize, usize, Vec<f64>), String> {
+    let content = match File::open(&Path::new(path)).read_to_string() {
+        Ok(content) => content,
+        Err(err)    => return Err(format!("{}: {}", path, err)),
+    };
+    let mut rows: Vec<Vec<f64>> = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let mut row = vec![];
+        for word in line.words() {
+            match word.parse::<f64>() {
+                Ok(height) => row.push(height),
+                Err(_)     => return Err(format!("{}: line {}: {} is not a number", path, i + 1, word)),
+            }
+        }
+        if !row.is_empty() {
+            rows.push(row);
+        }
+    }
+    if rows.len() < 2 || rows[0].len() < 2 {
+        return Err(format!("{}: a heightfield needs at least 2 rows of 2 heights", path));
+    }
+    let w = rows[0].len();
+    if rows.iter().any(|row| row.len() != w) {
+        return Err(format!("{}: heightfield rows must have the same length", path));
+    }
+    let h = rows.len();
+    Ok((w, h, rows.concat()))
+}
+
+// Möller-Trumbore, return the distance and the barycentric coordinates
+#[doc(hidden)]
+pub fn triangle(ray: &Ray, a: Vec3, b: Vec3, c: Vec3) -> Option<(f64, f64, f64)> {
+    let e1 = b - a;
+    let e2 = c - a;
+    let p = cross(ray.dir, e2);
+    let det = dot(e1, p);
+    if det.abs() < 1e-12 {
+        return None
+    }
+    let t = ray.pos - a;
+    let u = dot(t, p) / det;
+    if u < 0. || u > 1. {
+        return None
+    }
+    let q = cross(t, e1);
+    let v = dot(ray.dir, q) / det;
+    if v < 0. || u + v > 1. {
+        return None
+    }
+    let dist = dot(e2, q) / det;
+    if dist < EPSILON {
+        return None
+    }
+    Some((dist, u, v))
+}
+
+/// Terrain of `nx` by `nz` heights over `size.x` by `size.z`, from the corner `pos`
+#[allow(dead_code)]
+pub struct Heightfield {
+    pos:     Vec3,       // Corner with the lowest x and z
+    size:    Vec3,
+    cell:    (f64, f64), // Size of a cell along x and z
+    nx:      usize,
+    nz:      usize,
+    heights: Vec<f64>,
+    normals: Vec<Vec3>,
+    max:     Vec3,
+    mat:     Arc<Material>,
+}
+
+impl Heightfield {
+    /// `heights` holds nx * nz samples in [0, 1], scaled by `size.y`
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, size: Vec3, nx: usize, nz: usize, heights: Vec<f64>, mat: Arc<Material>) -> Heightfield {
+        if nx < 2 || nz < 2 || heights.len() != nx * nz {
+            panic!("Heightfield needs at least 2x2 samples");
+        }
+        let heights: Vec<f64> = heights.iter().map(|h| h * size.y).collect();
+        let top = heights.iter().fold(0., |acc: f64, &h| acc.max(h));
+        let cell = (size.x / (nx - 1) as f64, size.z / (nz - 1) as f64);
+
+        // Smooth normals from central differences
+        let mut normals = Vec::with_capacity(nx * nz);
+        for z in 0..nz {
+            for x in 0..nx {
+                let left = heights[z * nx + if x > 0 { x - 1 } else { x }];
+                let right = heights[z * nx + if x < nx - 1 { x + 1 } else { x }];
+                let back = heights[if z > 0 { z - 1 } else { z } * nx + x];
+                let front = heights[if z < nz - 1 { z + 1 } else { z } * nx + x];
+                normals.push(Vec3::new((left - right) / (2. * cell.0), 1., (back - front) / (2. * cell.1)).normalize());
+            }
+        }
+
+        Heightfield {
+            pos: pos,
+            size: size,
+            cell: cell,
+            nx: nx,
+            nz: nz,
+            heights: heights,
+            normals: normals,
+            max: pos + Vec3::new(size.x, top, size.z),
+            mat: mat,
+        }
+    }
+
+    fn vertex(&self, x: usize, z: usize) -> Vec3 {
+        self.pos + Vec3::new(x as f64 * self.cell.0, self.heights[z * self.nx + x], z as f64 * self.cell.1)
+    }
+
+    // Intersect the two triangles of a cell
+    fn intersect_cell(&self, ray: &Ray, x: usize, z: usize) -> Option<Inter> {
+        let corners = [(x, z), (x + 1, z), (x + 1, z + 1), (x, z + 1)];
+        let mut best: Option<(f64, Vec3)> = None;
+        for &(i, j, k) in [(0, 1, 2), (0, 2, 3)].iter() {
+            let (a, b, c) = (corners[i], corners[j], corners[k]);
+            let hit = triangle(ray, self.vertex(a.0, a.1), self.vertex(b.0, b.1), self.vertex(c.0, c.1));
+            if let Some((dist, u, v)) = hit {
+                if best.is_none() || dist < best.unwrap().0 {
+                    let normal = self.normals[a.1 * self.nx + a.0] * (1. - u - v)
+                        + self.normals[b.1 * self.nx + b.0] * u
+                        + self.normals[c.1 * self.nx + c.0] * v;
+                    best = Some((dist, normal));
+                }
+            }
+        }
+        best.map(|(dist, normal)| facing(ray, dist, normal, &self.mat))
+    }
+}
+
+impl Object for Heightfield {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        let (near, _, far, _) = match slab(ray.pos, ray.dir, self.pos, self.max) {
+            Some(hit) => hit,
+            None      => return None,
+        };
+        let start = ray.pos + ray.dir * near.max(0.);
+
+        // Cell containing the entry point
+        let cell_x = ((start.x - self.pos.x) / self.cell.0).floor();
+        let cell_z = ((start.z - self.pos.z) / self.cell.1).floor();
+        let mut x = cell_x.max(0.).min((self.nx - 2) as f64) as isize;
+        let mut z = cell_z.max(0.).min((self.nz - 2) as f64) as isize;
+
+        // Walk the grid (DDA)
+        let step_x = if ray.dir.x < 0. { -1 } else { 1 };
+        let step_z = if ray.dir.z < 0. { -1 } else { 1 };
+        let delta_x = (self.cell.0 / ray.dir.x).abs();
+        let delta_z = (self.cell.1 / ray.dir.z).abs();
+        let next_x = self.pos.x + (x + if step_x > 0 { 1 } else { 0 }) as f64 * self.cell.0;
+        let next_z = self.pos.z + (z + if step_z > 0 { 1 } else { 0 }) as f64 * self.cell.1;
+        let mut max_x = if ray.dir.x != 0. { (next_x - ray.pos.x) / ray.dir.x } else { INFINITY };
+        let mut max_z = if ray.dir.z != 0. { (next_z - ray.pos.z) / ray.dir.z } else { INFINITY };
+
+        loop {
+            let inter = self.intersect_cell(ray, x as usize, z as usize);
+            if inter.is_some() {
+                return inter;
+            }
+            if max_x.min(max_z) > far {
+                return None
+            }
+            if max_x < max_z {
+                x += step_x;
+                max_x += delta_x;
+            } else {
+                z += step_z;
+                max_z += delta_z;
+            }
+            if x < 0 || z < 0 || x >= (self.nx - 1) as isize || z >= (self.nz - 1) as isize {
+                return None
+            }
+        }
+    }
+
+    // Heights written inline, back in [0, 1]
+    fn to_json(&self, _: &mut Export) -> Json {
+        let scale = if self.size.y != 0. { self.size.y } else { 1. };
+        let heights: Vec<f64> = self.heights.iter().map(|h| h / scale).collect();
+        variant("heightfield", vec![
+            ("pos", vec3(self.pos)),
+            ("size", vec3(self.size)),
+            ("nx", int(self.nx as u64)),
+            ("nz", int(self.nz as u64)),
+            ("heights", nums(heights.as_slice())),
+            ("mat", self.mat.to_json()),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::old_io::TempDir;
+    use std::old_io::fs::File;
+    use super::load_grid;
+
+    fn grid(text: &str) -> Result<(usize, usize, Vec<f64>), String> {
+        let dir = TempDir::new("rt-grid").unwrap();
+        let path = dir.path().join("grid.txt");
+        File::create(&path).write_str(text).unwrap();
+        load_grid(path.as_str().unwrap()).map_err(|err| err.split_str(": ").last().unwrap().to_string())
+    }
+
+    #[test]
+    fn rows() {
+        assert_eq!(grid("0 0.5\n\n1 0.25\n"), Ok((2, 2, vec![0., 0.5, 1., 0.25])));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(grid(""), Err("a heightfield needs at least 2 rows of 2 heights".to_string()));
+        assert_eq!(grid("0 1\n2\n"), Err("heightfield rows must have the same length".to_string()));
+        assert_eq!(grid("0 1\n2 x\n"), Err("x is not a number".to_string()));
+    }
+}
@@ -0,0 +1,219 @@
+use std::num::Float;
+use std::f64::INFINITY;
+use std::sync::Arc;
+use serialize::json::Json;
+use image::{ self, GenericImage };
+use vec::{ Vec3, dot };
+use ray::{ Ray, Inter };
+use material::{ Color, Material };
+use object::{ Object, EPSILON, slab, facing };
+use export::{ self, Export, variant, num, int, nums, vec3 };
+
+const SQRT_3: f64 = 1.7320508075688772;
+
+// Axial offsets of the neighbours, in the order of EDGES
+const NEIGHBOURS: [(isize, isize); 6] = [(1, 0), (0, 1), (-1, 1), (-1, 0), (0, -1), (1, -1)];
+
+// Outward normals of the six edges in the xz plane
+const EDGES: [(f64, f64); 6] = [
+    (1., 0.),
+    (0.5, SQRT_3 / 2.),
+    (-0.5, SQRT_3 / 2.),
+    (-1., 0.),
+    (-0.5, -SQRT_3 / 2.),
+    (0.5, -SQRT_3 / 2.),
+];
+
+// Load heights in [0, 1] and colors from an image, one pixel per cell
+#[doc(hidden)]
+pub fn load_image(path: &str) -> (usize, usize, Vec<f64>, Vec<Color>) {
+    let img = image::open(&Path::new(path)).unwrap().to_rgb();
+    let (w, h) = img.dimensions();
+    let mut heights = Vec::with_capacity((w * h) as usize);
+    let mut colors = Vec::with_capacity((w * h) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            let data = img.get_pixel(x, y).data;
+            let color = Color::new(data[0] as f64 / 255., data[1] as f64 / 255., data[2] as f64 / 255.);
+            heights.push(0.299 * color.r + 0.587 * color.g + 0.114 * color.b);
+            colors.push(color);
+        }
+    }
+    (w as usize, h as usize, heights, colors)
+}
+
+/// Grid of hexagonal columns standing on the plane y = pos.y
+/// Rows go along z, odd rows are shifted by half a cell along x
+#[allow(dead_code)]
+pub struct HexGrid {
+    pos:     Vec3, // Center of the first cell
+    size:    f64,  // Distance between two opposite edges
+    cols:    usize,
+    rows:    usize,
+    heights: Vec<f64>,
+    mats:    Vec<Arc<Material>>, // One material for all cells, or one per cell
+    min:     Vec3,
+    max:     Vec3,
+}
+
+impl HexGrid {
+    /// `heights` holds cols * rows heights, `mats` one material for all cells or one per cell
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, size: f64, cols: usize, rows: usize, heights: Vec<f64>, mats: Vec<Arc<Material>>) -> HexGrid {
+        if heights.len() != cols * rows {
+            panic!("HexGrid needs one height per cell");
+        }
+        if mats.len() != 1 && mats.len() != cols * rows {
+            panic!("HexGrid needs one material, or one per cell");
+        }
+        let top = heights.iter().fold(0., |acc: f64, &h| acc.max(h));
+        let radius = size / SQRT_3;
+        let min = pos + Vec3::new(-size / 2., 0., -radius);
+        let max = pos + Vec3::new(cols as f64 * size, top, (rows - 1) as f64 * size * SQRT_3 / 2. + radius);
+        HexGrid { pos: pos, size: size, cols: cols, rows: rows, heights: heights, mats: mats, min: min, max: max }
+    }
+
+    // Axial coordinates of the cell containing a point
+    fn cell_at(&self, pos: Vec3) -> (isize, isize) {
+        let radius = self.size / SQRT_3;
+        let x = pos.x - self.pos.x;
+        let z = pos.z - self.pos.z;
+        let fq = (SQRT_3 / 3. * x - 1. / 3. * z) / radius;
+        let fr = (2. / 3. * z) / radius;
+        let fs = -fq - fr;
+
+        // Round in cube coordinates
+        let (mut q, mut r, s) = (fq.round(), fr.round(), fs.round());
+        let (dq, dr, ds) = ((q - fq).abs(), (r - fr).abs(), (s - fs).abs());
+        if dq > dr && dq > ds {
+            q = -r - s;
+        } else if dr > ds {
+            r = -q - s;
+        }
+        (q as isize, r as isize)
+    }
+
+    fn center(&self, q: isize, r: isize) -> (f64, f64) {
+        (
+            self.pos.x + self.size * (q as f64 + r as f64 / 2.),
+            self.pos.z + self.size * SQRT_3 / 2. * r as f64,
+        )
+    }
+
+    // Index of a cell in the row-major arrays
+    fn index(&self, q: isize, r: isize) -> Option<usize> {
+        let col = q + (r - (r & 1)) / 2;
+        if r < 0 || col < 0 || r >= self.rows as isize || col >= self.cols as isize {
+            return None
+        }
+        Some(r as usize * self.cols + col as usize)
+    }
+
+    fn intersect_cell(&self, ray: &Ray, idx: usize, entry: (f64, Vec3), exit: (f64, Vec3)) -> Option<(f64, Vec3)> {
+        let base = self.pos.y;
+        let top = self.pos.y + self.heights[idx];
+        if top <= base {
+            return None
+        }
+        let up = Vec3::new(0., 1., 0.);
+        let cap = |y: f64| (y - ray.pos.y) / ray.dir.y;
+
+        let (t_in, n_in) = entry;
+        let (t_out, n_out) = exit;
+        let start = t_in.max(0.);
+        let y = ray.pos.y + ray.dir.y * start;
+
+        if y > top {
+            // From above
+            if ray.dir.y < 0. && cap(top) <= t_out {
+                return Some((cap(top), up));
+            }
+        } else if y < base {
+            // From below
+            if ray.dir.y > 0. && cap(base) <= t_out {
+                return Some((cap(base), up * -1.));
+            }
+        } else if t_in > EPSILON {
+            // Through a wall
+            return Some((t_in, n_in));
+        } else {
+            // From inside the column
+            let t_cap = if ray.dir.y > 0. { cap(top) } else if ray.dir.y < 0. { cap(base) } else { INFINITY };
+            if t_cap < t_out {
+                return Some((t_cap, up));
+            }
+            if t_out < INFINITY {
+                return Some((t_out, n_out));
+            }
+        }
+        None
+    }
+}
+
+impl Object for HexGrid {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        let (near, _, far, _) = match slab(ray.pos, ray.dir, self.min, self.max) {
+            Some(hit) => hit,
+            None      => return None,
+        };
+        let (mut q, mut r) = self.cell_at(ray.pos + ray.dir * near.max(0.));
+
+        // Walk the lattice, from cell to neighbour
+        loop {
+            let (cx, cz) = self.center(q, r);
+            let mut entry = (-INFINITY, Vec3::new(0., 0., 0.));
+            let mut exit = (INFINITY, Vec3::new(0., 0., 0.));
+            let mut next = None;
+            for k in 0..6 {
+                let (nx, nz) = EDGES[k];
+                let normal = Vec3::new(nx, 0., nz);
+                let speed = dot(ray.dir, normal);
+                if speed == 0. {
+                    continue;
+                }
+                let offset = (ray.pos.x - cx) * nx + (ray.pos.z - cz) * nz;
+                let t = (self.size / 2. - offset) / speed;
+                if speed < 0. && t > entry.0 {
+                    entry = (t, normal);
+                } else if speed > 0. && t < exit.0 {
+                    exit = (t, normal);
+                    next = Some(NEIGHBOURS[k]);
+                }
+            }
+
+            if let Some(idx) = self.index(q, r) {
+                let hit = self.intersect_cell(ray, idx, entry, exit);
+                if let Some((dist, normal)) = hit {
+                    if dist > EPSILON {
+                        let mat = &self.mats[if self.mats.len() == 1 { 0 } else { idx }];
+                        return Some(facing(ray, dist, normal, mat));
+                    }
+                }
+            }
+
+            match next {
+                Some((dq, dr)) if exit.0 <= far => {
+                    q += dq;
+                    r += dr;
+                },
+                _ => return None,
+            }
+        }
+    }
+
+    // Cells written inline, with their colors when they have their own materials
+    fn to_json(&self, _: &mut Export) -> Json {
+        let mut fields = vec![
+            ("pos", vec3(self.pos)),
+            ("size", num(self.size)),
+            ("cols", int(self.cols as u64)),
+            ("rows", int(self.rows as u64)),
+            ("heights", nums(self.heights.as_slice())),
+            ("mat", self.mats[0].to_json()),
+        ];
+        if self.mats.len() > 1 {
+            fields.push(("colors", Json::Array(self.mats.iter().map(|mat| export::color(mat.color)).collect())));
+        }
+        variant("hexgrid", fields)
+    }
+}
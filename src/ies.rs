@@ -0,0 +1,108 @@
+use std::num::Float;
+use std::old_io::fs::File;
+
+// IES LM-63 photometric profile, angles in degrees
+// Vertical angles start at the nadir, horizontal angles turn around it
+pub struct IesProfile {
+    pub path: Option<String>, // File the profile was loaded from
+    vert:    Vec<f64>,
+    horiz:   Vec<f64>,
+    candela: Vec<Vec<f64>>, // One row of vertical values per horizontal angle
+}
+
+impl IesProfile {
+    pub fn load(path: &str) -> IesProfile {
+        let content = File::open(&Path::new(path)).read_to_string().unwrap();
+        let mut profile = IesProfile::parse(content.as_slice());
+        profile.path = Some(path.to_string());
+        profile
+    }
+
+    pub fn parse(content: &str) -> IesProfile {
+        // Skip keywords until the tilt line
+        let mut lines = content.lines();
+        loop {
+            match lines.next() {
+                Some(line) if line.trim().starts_with("TILT=") => {
+                    if line.trim() != "TILT=NONE" {
+                        panic!("Only TILT=NONE IES profiles are supported");
+                    }
+                    break;
+                },
+                Some(_)                                        => {},
+                None                                           => panic!("Not an IES profile"),
+            }
+        }
+        let rest: Vec<&str> = lines.collect();
+        let values: Vec<f64> = rest.iter()
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|word| !word.is_empty())
+            .map(|word| word.parse::<f64>().unwrap())
+            .collect();
+
+        let multiplier = values[2];
+        let n_vert = values[3] as usize;
+        let n_horiz = values[4] as usize;
+        let start = 13;
+        let vert = values[start..(start + n_vert)].to_vec();
+        let horiz = values[(start + n_vert)..(start + n_vert + n_horiz)].to_vec();
+        let mut candela = vec![];
+        let mut pos = start + n_vert + n_horiz;
+        for _ in 0..n_horiz {
+            candela.push(values[pos..(pos + n_vert)].iter().map(|c| c * multiplier).collect());
+            pos += n_vert;
+        }
+
+        // Normalize, the light keeps its own intensity
+        let max = candela.iter().flat_map(|row: &Vec<f64>| row.iter()).fold(0., |acc: f64, &c| acc.max(c));
+        if max > 0. {
+            for row in candela.iter_mut() {
+                for c in row.iter_mut() {
+                    *c /= max;
+                }
+            }
+        }
+
+        IesProfile { path: None, vert: vert, horiz: horiz, candela: candela }
+    }
+
+    // Index and interpolation factor of `x` in sorted `angles`
+    fn locate(angles: &Vec<f64>, x: f64) -> (usize, f64) {
+        if angles.len() == 1 || x <= angles[0] {
+            return (0, 0.);
+        }
+        for i in 0..(angles.len() - 1) {
+            if x <= angles[i + 1] {
+                return (i, (x - angles[i]) / (angles[i + 1] - angles[i]));
+            }
+        }
+        (angles.len() - 2, 1.)
+    }
+
+    // Relative intensity in [0, 1]
+    pub fn intensity(&self, vert: f64, horiz: f64) -> f64 {
+        if vert > self.vert[self.vert.len() - 1] {
+            return 0.;
+        }
+
+        // Fold the horizontal angle according to the symmetry of the profile
+        let last = self.horiz[self.horiz.len() - 1];
+        let mut horiz = horiz % 360.;
+        if horiz < 0. {
+            horiz += 360.;
+        }
+        if last == 90. {
+            horiz = if horiz > 180. { 360. - horiz } else { horiz };
+            horiz = if horiz > 90. { 180. - horiz } else { horiz };
+        } else if last == 180. && horiz > 180. {
+            horiz = 360. - horiz;
+        }
+
+        let (v, fv) = IesProfile::locate(&self.vert, vert);
+        let (h, fh) = IesProfile::locate(&self.horiz, horiz);
+        let v1 = (v + 1).min(self.vert.len() - 1);
+        let h1 = (h + 1).min(self.horiz.len() - 1);
+        let row = |h: usize| self.candela[h][v] * (1. - fv) + self.candela[h][v1] * fv;
+        row(h) * (1. - fh) + row(h1) * fh
+    }
+}
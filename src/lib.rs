@@ -0,0 +1,93 @@
+//! Ray tracer, scenes are built in code with `SceneBuilder` and `PictureBuilder`
+//!
+//! ```ignore
+//! extern crate rt;
+//!
+//! use std::sync::Arc;
+//! use rt::{ SceneBuilder, PictureBuilder, Sphere, Bulb, Material, Color, Vec3, Eye };
+//!
+//! let mat = Arc::new(Material::new(Color::new(0.9, 0.2, 0.1), 0.5, 1., 0., 1., 0.));
+//! let scene = SceneBuilder::new()
+//!     .object(Box::new(Sphere::new(Vec3::new(0., 0., 0.), 10., mat)))
+//!     .light(Box::new(Bulb::new(Vec3::new(50., 50., 50.), 1., 20, 1.)))
+//!     .build();
+//! let picture = PictureBuilder::new(320, 240).sample(2).threads(4).build();
+//! let buffer = picture.shot(&Eye::new(Vec3::new(0., 0., 100.), Vec3::new(0., 0., 0.), 2.1), &scene, false);
+//! ```
+//!
+//! Objects, lights and skies are traits, any type implementing `Object`, `Light` or `Sky`
+//! can be added to a scene next to the ones of this crate. Signed distance shapes are in
+//! the `sdf` module, drawn by an `SdfObject`, and glTF files are loaded with `Gltf` then
+//! placed with a `Model`.
+
+#![feature(core)]
+#![feature(box_syntax)]
+#![feature(old_io)]
+#![feature(old_path)]
+#![feature(std_misc)]
+
+extern crate num;
+extern crate "rustc-serialize" as serialize;
+extern crate image;
+extern crate rand;
+extern crate time;
+extern crate toml;
+extern crate "yaml-rust" as yaml_rust;
+
+mod vec;
+mod solver;
+mod ray;
+mod material;
+mod buffer;
+mod exr;
+mod aov;
+mod denoise;
+mod object;
+mod heightfield;
+mod mesh;
+mod hexgrid;
+mod metaball;
+mod ies;
+mod light;
+mod noise;
+mod media;
+mod sky;
+mod scene;
+mod ron;
+mod expr;
+mod builder;
+
+// Shapes named like the objects, kept in their own module
+pub mod sdf;
+
+// Used by the rt binary and the tests, not part of the API
+#[doc(hidden)] pub mod progressive;
+#[doc(hidden)] pub mod gltf;
+#[doc(hidden)] pub mod schema;
+#[doc(hidden)] pub mod format;
+#[doc(hidden)] pub mod export;
+#[doc(hidden)] pub mod config;
+
+// C interface of script/pyrt.py, public so that its functions are exported
+#[doc(hidden)] pub mod capi;
+
+pub use vec::Vec3;
+pub use ray::{ Ray, Inter };
+pub use material::{ Color, Material };
+pub use buffer::Buffer;
+pub use aov::Pass;
+pub use object::{ Object, Sphere, Plane, AABox, Cylinder, Cone, Torus, Disc, Capsule, Ellipsoid, OrientedBox, Quad, Polygon };
+pub use object::{ Rotate, Instance, Geometry };
+pub use heightfield::Heightfield;
+pub use hexgrid::HexGrid;
+pub use metaball::{ Metaballs, Ball };
+pub use sdf::{ Sdf, SdfObject };
+pub use gltf::{ Gltf, Model };
+pub use light::{ Light, Bulb, Sun, Spot };
+pub use sky::{ Sky, Uniform, EnvMap, CubeMap, Preetham };
+pub use noise::Perlin;
+pub use media::{ Medium, Volume, Density };
+pub use denoise::Denoiser;
+pub use progressive::Progressive;
+pub use scene::{ Scene, Picture, Eye, Occlusion, Status };
+pub use builder::{ SceneBuilder, PictureBuilder };
@@ -1,10 +1,31 @@
 use std::num::Float;
-use vec::{ Vec3, dot };
+use std::f64::consts::PI;
+use serialize::json::Json;
+use vec::{ Vec3, Frame, dot };
 use ray::{ Ray, Inter };
 use scene::Scene;
+use media::phase;
+use ies::IesProfile;
+use export::{ variant, num, string, vec3 };
 
-pub trait Light {
+/// Source of light, implement it to add your own lights to a scene
+pub trait Light: Send + Sync {
+    /// Specular and diffuse intensity at an intersection seen along `ray`
     fn bright(&self, ray: &Ray, inter: &Inter, scene: &Scene) -> (f64, f64);
+
+    /// Light reaching `pos` and scattered along `dir` by a medium
+    fn scatter(&self, pos: Vec3, dir: Vec3, g: f64, scene: &Scene) -> f64;
+
+    /// 1 if the light is seen from `pos`, 0 if it is hidden, lights that cast no shadow are always seen
+    fn visibility(&self, _: Vec3, _: &Scene) -> f64 {
+        1.
+    }
+
+    // Canonical JSON, null for lights that cannot be written back
+    #[doc(hidden)]
+    fn to_json(&self) -> Json {
+        Json::Null
+    }
 }
 
 pub struct Lights<'a> {
@@ -31,6 +52,13 @@ impl<'a> Lights<'a> {
 
         (spec, diff)
     }
+
+    fn scatter_helper(light_pos: Vec3, pos: Vec3, dir: Vec3, g: f64, scene: &Scene) -> f64 {
+        if !scene.visible(pos, light_pos) {
+            return 0.;
+        }
+        phase(dot(dir, (light_pos - pos).normalize()), g)
+    }
 }
 
 impl<'a> Light for Lights<'a> {
@@ -39,8 +67,29 @@ impl<'a> Light for Lights<'a> {
             .map(|l| l.bright(ray, inter, scene))
             .fold((0., 0.), |acc, item| (acc.0 + item.0, acc.1 + item.1))
     }
+
+    fn scatter(&self, pos: Vec3, dir: Vec3, g: f64, scene: &Scene) -> f64 {
+        self.all.iter()
+            .map(|l| l.scatter(pos, dir, g, scene))
+            .fold(0., |acc, item| acc + item)
+    }
+
+    // Average over all lights
+    fn visibility(&self, pos: Vec3, scene: &Scene) -> f64 {
+        if self.all.is_empty() {
+            return 1.;
+        }
+        self.all.iter()
+            .map(|l| l.visibility(pos, scene))
+            .fold(0., |acc, item| acc + item) / self.all.len() as f64
+    }
+
+    fn to_json(&self) -> Json {
+        Json::Array(self.all.iter().map(|l| l.to_json()).filter(|json| !json.is_null()).collect())
+    }
 }
 
+/// Point light at `pos`
 #[allow(dead_code)]
 pub struct Bulb {
     pos:  Vec3,
@@ -61,8 +110,26 @@ impl Light for Bulb {
         let (spec, diff) = Lights::bright_helper(self.pos, self.shin, ray, inter, scene);
         (spec * self.spec, diff * self.diff)
     }
+
+    fn scatter(&self, pos: Vec3, dir: Vec3, g: f64, scene: &Scene) -> f64 {
+        Lights::scatter_helper(self.pos, pos, dir, g, scene) * self.diff
+    }
+
+    fn visibility(&self, pos: Vec3, scene: &Scene) -> f64 {
+        scene.shadow(pos, self.pos)
+    }
+
+    fn to_json(&self) -> Json {
+        variant("bulb", vec![
+            ("pos", vec3(self.pos)),
+            ("spec", num(self.spec)),
+            ("shin", Json::I64(self.shin as i64)),
+            ("diff", num(self.diff)),
+        ])
+    }
 }
 
+/// Light from infinitely far away, shining along `dir`
 #[allow(dead_code)]
 pub struct Sun {
     dir:  Vec3,
@@ -84,4 +151,112 @@ impl Light for Sun {
         let (spec, diff) = Lights::bright_helper(pos, self.shin, ray, inter, scene);
         (spec * self.spec, diff * self.diff)
     }
+
+    fn scatter(&self, pos: Vec3, dir: Vec3, g: f64, scene: &Scene) -> f64 {
+        Lights::scatter_helper(pos - self.dir * 1000000., pos, dir, g, scene) * self.diff
+    }
+
+    fn visibility(&self, pos: Vec3, scene: &Scene) -> f64 {
+        scene.shadow(pos, pos - self.dir * 1000000.)
+    }
+
+    fn to_json(&self) -> Json {
+        variant("sun", vec![
+            ("dir", vec3(self.dir)),
+            ("spec", num(self.spec)),
+            ("shin", Json::I64(self.shin as i64)),
+            ("diff", num(self.diff)),
+        ])
+    }
+}
+
+/// Cone of light from `pos` along `dir`, of half angle `angle` with a soft edge of `falloff`,
+/// or shaped by an IES profile
+#[allow(dead_code)]
+pub struct Spot {
+    frame:   Frame, // Oriented along the direction of the spot
+    angle:   f64,   // Half angle of the cone
+    falloff: f64,   // Width of the soft edge, inside the cone
+    profile: Option<IesProfile>,
+    spec:    f64,   // Specular
+    shin:    i32,   // Shininess
+    diff:    f64,   // Diffuse
+}
+
+impl Spot {
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, dir: Vec3, angle: f64, falloff: f64, profile: Option<IesProfile>, spec: f64, shin: i32, diff: f64) -> Spot {
+        Spot {
+            frame: Frame::new(pos, dir),
+            angle: angle,
+            falloff: falloff,
+            profile: profile,
+            spec: spec,
+            shin: shin,
+            diff: diff,
+        }
+    }
+
+    // Emission towards `pos`, relative to the axis of the spot
+    fn emission(&self, pos: Vec3) -> f64 {
+        let local = self.frame.to_local(pos).normalize();
+        let theta = local.y.max(-1.).min(1.).acos();
+        match self.profile {
+            Some(ref profile) => {
+                let phi = local.z.atan2(local.x);
+                profile.intensity(theta * 180. / PI, phi * 180. / PI)
+            },
+            None              => {
+                if theta >= self.angle {
+                    return 0.;
+                }
+                if self.falloff <= 0. || theta <= self.angle - self.falloff {
+                    return 1.;
+                }
+                // Smoothstep across the edge
+                let x = (self.angle - theta) / self.falloff;
+                x * x * (3. - 2. * x)
+            },
+        }
+    }
+}
+
+impl Light for Spot {
+    fn bright(&self, ray: &Ray, inter: &Inter, scene: &Scene) -> (f64, f64) {
+        let emission = self.emission(inter.pos);
+        if emission == 0. {
+            return (0., 0.);
+        }
+        let (spec, diff) = Lights::bright_helper(self.frame.pos, self.shin, ray, inter, scene);
+        (spec * self.spec * emission, diff * self.diff * emission)
+    }
+
+    fn scatter(&self, pos: Vec3, dir: Vec3, g: f64, scene: &Scene) -> f64 {
+        let emission = self.emission(pos);
+        if emission == 0. {
+            return 0.;
+        }
+        Lights::scatter_helper(self.frame.pos, pos, dir, g, scene) * self.diff * emission
+    }
+
+    fn visibility(&self, pos: Vec3, scene: &Scene) -> f64 {
+        scene.shadow(pos, self.frame.pos)
+    }
+
+    // Profiles parsed from memory are left out
+    fn to_json(&self) -> Json {
+        let mut fields = vec![
+            ("pos", vec3(self.frame.pos)),
+            ("dir", vec3(self.frame.w)),
+            ("angle", num(self.angle)),
+            ("falloff", num(self.falloff)),
+            ("spec", num(self.spec)),
+            ("shin", Json::I64(self.shin as i64)),
+            ("diff", num(self.diff)),
+        ];
+        if let Some(IesProfile { path: Some(ref path), .. }) = self.profile {
+            fields.push(("ies", string(path.as_slice())));
+        }
+        variant("spot", fields)
+    }
 }
@@ -1,29 +1,293 @@
 #![feature(core)]
-#![feature(box_syntax)]
 #![feature(old_io)]
 #![feature(old_path)]
+#![feature(std_misc)]
+#![feature(env)]
+#![feature(process)]
 
-extern crate num;
 extern crate "rustc-serialize" as serialize;
-extern crate image;
+extern crate time;
+extern crate getopts;
+extern crate rt;
 
 use std::io::Read;
+use std::env;
+use std::old_io::fs::File;
+use std::old_io::stdio;
+use getopts::{ optopt, optmulti, optflag, getopts, usage };
+use serialize::json::Json;
+use rt::{ config, schema, export, gltf };
+use rt::format::Format;
 
-mod vec;
-mod ray;
-mod material;
-mod object;
-mod light;
-mod scene;
-mod config;
+mod preview;
+mod server;
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+    let opts = [
+        optopt("o", "output", "write the image to PATH", "PATH"),
+        optopt("", "width", "override the width of the picture", "W"),
+        optopt("", "height", "override the height of the picture", "H"),
+        optopt("s", "sample", "override the subpixels per side", "N"),
+        optopt("b", "bounce", "override the number of bounces", "N"),
+        optopt("", "scale", "scale the resolution, for previews", "FACTOR"),
+        optopt("", "crop", "render only a window, in pixels", "X,Y,W,H"),
+        optopt("j", "threads", "number of rendering threads", "N"),
+        optopt("f", "format", "format of the scenes, json, toml, yaml or ron, from the extension by default", "FORMAT"),
+        optmulti("", "set", "patch a field of the scene, like eye.pos.x=10", "KEY=VALUE"),
+        optflag("q", "quiet", "do not show progress"),
+        optflag("v", "verbose", "show what is rendered and how long it takes"),
+        optflag("i", "interactive", "preview in the terminal, move with wasdqe, turn with okl;ip"),
+        optopt("", "port", "port of the render server, 7878 by default", "PORT"),
+        optflag("h", "help", "show this help"),
+    ];
+    let matches = match getopts(args.tail(), &opts) {
+        Ok(matches) => matches,
+        Err(err)    => {
+            report(err.to_string());
+            env::set_exit_status(1);
+            return;
+        },
+    };
+    if matches.opt_present("help") {
+        println!("{}", usage(format!("Usage: {} [options] [scene ...], the scene is read from stdin without files, .gltf and .glb render alone\n       \
+                                      {} serve [--port PORT], render scenes sent over HTTP\n       \
+                                      {} validate scene.json ..., report every problem of the scenes\n       \
+                                      {} migrate scene.json ..., rewrite the scenes in the latest version\n       \
+                                      {} export [options] [scene ...], print the scenes as loaded, in canonical JSON",
+                                      args[0], args[0], args[0], args[0], args[0]).as_slice(), &opts));
+        return;
+    }
+    let format = match matches.opt_str("format") {
+        Some(name) => match Format::from_name(name.as_slice()) {
+            Some(format) => Some(format),
+            None         => {
+                report(format!("Unknown format {}", name));
+                env::set_exit_status(1);
+                return;
+            },
+        },
+        None       => None,
+    };
+    if !matches.free.is_empty() && (matches.free[0] == "validate" || matches.free[0] == "migrate") {
+        let fix = matches.free[0] == "migrate";
+        for path in matches.free.tail().iter() {
+            if !validate(path.as_slice(), format, fix) {
+                env::set_exit_status(1);
+            }
+        }
+        return;
+    }
+    if matches.free.len() == 1 && matches.free[0] == "serve" {
+        let port = matches.opt_str("port").unwrap_or("7878".to_string());
+        server::serve(format!("127.0.0.1:{}", port).as_slice());
+        return;
+    }
+    if matches.free.len() > 1 && matches.opt_present("output") {
+        report("-o can only be used with a single scene".to_string());
+        env::set_exit_status(1);
+        return;
+    }
+
+    // Fields to patch in every scene, the options first so that --set wins
+    let mut sets: Vec<(String, Json)> = vec![];
+    let options = [
+        ("output", "picture.path"), ("width", "picture.w"), ("height", "picture.h"), ("sample", "picture.sample"),
+        ("bounce", "picture.bounce"), ("scale", "picture.scale"), ("threads", "picture.threads"),
+    ];
+    for &(name, path) in options.iter() {
+        if let Some(value) = matches.opt_str(name) {
+            sets.push((path.to_string(), parse_value(value.as_slice())));
+        }
+    }
+    if let Some(crop) = matches.opt_str("crop") {
+        let values: Vec<&str> = crop.split(',').collect();
+        if values.len() != 4 {
+            report("--crop expects X,Y,W,H".to_string());
+            env::set_exit_status(1);
+            return;
+        }
+        for (key, value) in ["x", "y", "w", "h"].iter().zip(values.iter()) {
+            sets.push((format!("picture.crop.{}", key), parse_value(*value)));
+        }
+    }
+    for set in matches.opt_strs("set").iter() {
+        match set.find('=') {
+            Some(i) => sets.push((set[..i].to_string(), parse_value(&set[(i + 1)..]))),
+            None    => {
+                report(format!("--set expects KEY=VALUE, got {}", set));
+                env::set_exit_status(1);
+                return;
+            },
+        }
+    }
+
+    // Print the scenes instead of rendering them
+    let exporting = !matches.free.is_empty() && matches.free[0] == "export";
+    let files = if exporting { matches.free.tail() } else { matches.free.as_slice() };
+
+    // Read scenes, from stdin without files
+    let mut inputs = vec![];
+    if files.is_empty() {
+        let mut input = String::new();
+        let _ = std::io::stdin().read_to_string(&mut input);
+        inputs.push(("stdin".to_string(), input, format.unwrap_or(Format::Json)));
+    }
+    for name in files.iter() {
+        let path = Path::new(name.as_slice());
+        // A glTF file renders alone, with its own cameras and lights
+        if gltf::is_gltf(&path) {
+            inputs.push((name.clone(), gltf::wrap(name.as_slice()).to_string(), Format::Json));
+            continue;
+        }
+        match File::open(&path).read_to_string() {
+            Ok(input) => inputs.push((name.clone(), input, format.unwrap_or(Format::from_path(&path)))),
+            Err(err)  => {
+                report(format!("{}: {}", name, err));
+                env::set_exit_status(1);
+            },
+        }
+    }
+
+    let progress = !matches.opt_present("quiet");
+    let verbose = matches.opt_present("verbose");
+    let interactive = matches.opt_present("interactive");
+    if interactive && files.is_empty() {
+        report("--interactive needs a scene file, stdin is used for the keys".to_string());
+        env::set_exit_status(1);
+        return;
+    }
+    for &(ref name, ref input, format) in inputs.iter() {
+        let root = match prepare(name.as_slice(), input.as_slice(), format, sets.as_slice()) {
+            Some(root) => root,
+            None       => {
+                env::set_exit_status(1);
+                continue;
+            },
+        };
+        if exporting {
+            let (eye, scene, picture) = config::load(&root);
+            println!("{}", export::export(&eye, &scene, &picture).pretty());
+        } else {
+            render(name.as_slice(), &root, progress, verbose, interactive);
+        }
+    }
+}
+
+// Print the problems of a scene file, false on errors
+// Migrated JSON scenes are written back with `fix`
+fn validate(path: &str, format: Option<Format>, fix: bool) -> bool {
+    let input = match File::open(&Path::new(path)).read_to_string() {
+        Ok(input) => input,
+        Err(err)  => {
+            report(format!("{}: {}", path, err));
+            return false;
+        },
+    };
+    let format = format.unwrap_or(Format::from_path(&Path::new(path)));
+    let mut root = match format.parse(input.as_slice()) {
+        Ok(root) => root,
+        Err(err) => {
+            report(format!("{}: error: {}", path, err));
+            return false;
+        },
+    };
+    let notes = schema::migrate(&mut root);
+    for note in notes.iter() {
+        println!("{}: note: {}", path, note);
+    }
+
+    // Problems are looked for in the scene once resolved, the file is written back as it was
+    let problems = match config::prepare(&root, &Path::new(path).dir_path(), &[]) {
+        Ok((_, problems)) => problems,
+        Err(err)          => {
+            report(format!("{}: error: {}", path, err));
+            return false;
+        },
+    };
+    for problem in problems.iter() {
+        report(format!("{}: {}", path, problem));
+    }
+    let ok = !problems.iter().any(|problem| problem.error);
+    if ok && fix && !notes.is_empty() && format != Format::Json {
+        report(format!("{}: not written, only JSON scenes can be migrated in place", path));
+    } else if ok && fix && !notes.is_empty() {
+        File::create(&Path::new(path)).write_str(format!("{}\n", root.pretty()).as_slice()).unwrap();
+        println!("{}: written in version {}", path, schema::VERSION);
+    } else if problems.is_empty() {
+        println!("{}: ok", path);
+    }
+    ok
+}
+
+// Values are JSON, or plain strings
+fn parse_value(value: &str) -> Json {
+    match Json::from_str(value) {
+        Ok(json) => json,
+        Err(_)   => Json::String(value.to_string()),
+    }
+}
+
+// Parse and prepare a scene, None when it has errors
+// Unknown keys are only warned about, on stderr as stdout holds the exported scenes
+fn prepare(name: &str, input: &str, format: Format, sets: &[(String, Json)]) -> Option<Json> {
+    let root = match format.parse(input) {
+        Ok(root) => root,
+        Err(err) => {
+            report(format!("{}: error: {}", name, err));
+            return None;
+        },
+    };
+    let (root, problems) = match config::prepare(&root, &Path::new(name).dir_path(), sets) {
+        Ok(prepared) => prepared,
+        Err(err)     => {
+            report(format!("{}: error: {}", name, err));
+            return None;
+        },
+    };
+    for problem in problems.iter() {
+        report(format!("{}: {}", name, problem));
+    }
+    if problems.iter().any(|problem| problem.error) {
+        return None;
+    }
+    Some(root)
+}
+
+// Errors and problems go to stderr, stdout only holds results
+fn report(message: String) {
+    let _ = stdio::stderr().write_line(message.as_slice());
+}
+
+fn render(name: &str, root: &Json, progress: bool, verbose: bool, interactive: bool) {
     // Load eye and scene
-    let mut input = String::new();
-    let _ = std::io::stdin().read_to_string(&mut input);
-    let (eye, scene, picture) = config::load(input.as_slice());
+    let (eye, scene, picture) = config::load(root);
+    if interactive {
+        preview::run(eye, &scene, &picture);
+        return;
+    }
+    if verbose {
+        println!("Rendering {} to {} ({}x{})", name, picture.path.display(), picture.w, picture.h);
+    }
+    let start = time::precise_time_s();
 
-    // Compute and save image
-    let img = picture.shot(&eye, &scene, true);
+    // Compute and save image, with the other passes
+    let (img, passes) = picture.shot_with_passes(&eye, &scene, progress);
+    let img = picture.denoise(&eye, &scene, img);
     picture.save(&img);
+
+    // Compute and save ambient occlusion pass
+    if picture.ao.is_some() {
+        let ao = picture.shot_ao(&eye, &scene, progress);
+        picture.save_ao(&ao);
+    }
+
+    // Save other passes
+    if !picture.passes.is_empty() {
+        picture.save_passes(&img, &passes);
+    }
+
+    if verbose {
+        println!("Rendered {} in {:.1}s", name, time::precise_time_s() - start);
+    }
 }
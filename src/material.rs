@@ -1,7 +1,10 @@
 use std::ops::{Add, Mul};
 use std::num::Float;
+use serialize::json::Json;
+use export::{ self, obj, num };
 
-#[derive(Copy)]
+/// Linear RGB color, 1 is full intensity
+#[derive(Copy, Clone)]
 pub struct Color {
     pub r: f64,
     pub g: f64,
@@ -13,6 +16,7 @@ impl Color {
         Color { r: r, g: g, b: b }
     }
 
+    /// Clamp each channel to 1
     pub fn normalize(&self) -> Color {
         Color::new(self.r.min(1.), self.g.min(1.), self.b.min(1.))
     }
@@ -26,6 +30,14 @@ impl Add<Color> for Color {
     }
 }
 
+impl Mul<Color> for Color {
+    type Output = Color;
+
+    fn mul(self, rhs: Color) -> Color {
+        Color::new(self.r * rhs.r, self.g * rhs.g, self.b * rhs.b)
+    }
+}
+
 impl Mul<f64> for Color {
     type Output = Color;
 
@@ -34,6 +46,8 @@ impl Mul<f64> for Color {
     }
 }
 
+/// Color and coefficients of a surface: specular, diffuse, refraction
+/// with its index, and reflection
 pub struct Material {
     pub color:     Color,
     pub spec:      f64,  // Specular
@@ -47,4 +61,21 @@ impl Material {
     pub fn new(color: Color, spec: f64, diff: f64, refr: f64, refr_idx: f64, refl: f64) -> Material {
         Material { color: color, spec: spec, diff: diff, refr: refr, refr_idx: refr_idx, refl: refl }
     }
+
+    /// Same material with another color
+    pub fn with_color(&self, color: Color) -> Material {
+        Material::new(color, self.spec, self.diff, self.refr, self.refr_idx, self.refl)
+    }
+
+    #[doc(hidden)]
+    pub fn to_json(&self) -> Json {
+        obj(vec![
+            ("color", export::color(self.color)),
+            ("spec", num(self.spec)),
+            ("diff", num(self.diff)),
+            ("refr", num(self.refr)),
+            ("refr-idx", num(self.refr_idx)),
+            ("refl", num(self.refl)),
+        ])
+    }
 }
@@ -0,0 +1,255 @@
+use std::num::Float;
+use std::f64::consts::PI;
+use std::old_io::fs::File;
+use serialize::json::Json;
+use rand;
+use vec::Vec3;
+use ray::Ray;
+use material::Color;
+use object::slab;
+use light::Light;
+use noise::Perlin;
+use scene::Scene;
+use export::{ self, obj, num, int, nums, vec3 };
+
+// Rays are marched at most this far, the fog does not hide what is past it without a distance
+const MARCH_DIST: f64 = 1000.;
+
+// Henyey-Greenstein phase function, `g` in ]-1, 1[, 0 is isotropic
+pub fn phase(cos: f64, g: f64) -> f64 {
+    let denom = 1. + g * g - 2. * g * cos;
+    (1. - g * g) / (4. * PI * denom * denom.sqrt())
+}
+
+/// Coefficients per unit length, for a density of 1
+#[derive(Copy)]
+pub struct Medium {
+    absorb:  f64,
+    scatter: f64,
+    color:   Color, // Tint of the scattered light
+    g:       f64,   // Phase function asymmetry
+}
+
+impl Medium {
+    pub fn new(absorb: f64, scatter: f64, color: Color, g: f64) -> Medium {
+        Medium { absorb: absorb, scatter: scatter, color: color, g: g }
+    }
+
+    fn extinction(&self) -> f64 {
+        self.absorb + self.scatter
+    }
+
+    fn fields(&self) -> Vec<(&'static str, Json)> {
+        vec![
+            ("absorb", num(self.absorb)),
+            ("scatter", num(self.scatter)),
+            ("color", export::color(self.color)),
+            ("g", num(self.g)),
+        ]
+    }
+}
+
+/// How dense a volume is, at each point
+pub enum Density {
+    Constant(f64),
+    Noise(Perlin, f64, u32, f64), // Noise, scale, octaves, maximum density
+    Grid(usize, usize, usize, Vec<f64>),
+}
+
+impl Density {
+    /// Load a grid, "nx ny nz" followed by nx * ny * nz values, x varying first
+    pub fn load_grid(path: &str) -> Density {
+        let content = File::open(&Path::new(path)).read_to_string().unwrap();
+        let values: Vec<f64> = content.words().map(|word| word.parse::<f64>().unwrap()).collect();
+        let (nx, ny, nz) = (values[0] as usize, values[1] as usize, values[2] as usize);
+        if values.len() != 3 + nx * ny * nz {
+            panic!("Wrong number of values in density grid");
+        }
+        Density::Grid(nx, ny, nz, values[3..].to_vec())
+    }
+
+    // Grids are written inline
+    fn to_json(&self) -> Json {
+        match *self {
+            Density::Constant(density)                     => num(density),
+            Density::Noise(ref noise, scale, octaves, max) => obj(vec![("noise", obj(vec![
+                ("seed", int(noise.seed as u64)),
+                ("scale", num(scale)),
+                ("octaves", int(octaves as u64)),
+                ("max", num(max)),
+            ]))]),
+            Density::Grid(nx, ny, nz, ref data)            => obj(vec![("grid", obj(vec![
+                ("nx", int(nx as u64)),
+                ("ny", int(ny as u64)),
+                ("nz", int(nz as u64)),
+                ("values", nums(data.as_slice())),
+            ]))]),
+        }
+    }
+
+    // Density at `pos`, local coordinates in [0, 1]
+    fn at(&self, pos: Vec3, local: Vec3) -> f64 {
+        match *self {
+            Density::Constant(density)                      => density,
+            Density::Noise(ref noise, scale, octaves, max)  => noise.fbm(pos / scale, octaves) * max,
+            Density::Grid(nx, ny, nz, ref data)             => {
+                // Trilinear interpolation
+                let coord = |x: f64, n: usize| {
+                    let f = (x * (n - 1) as f64).max(0.).min((n - 1) as f64);
+                    let i = (f as usize).min(if n > 1 { n - 2 } else { 0 });
+                    (i, f - i as f64)
+                };
+                let (x, fx) = coord(local.x, nx);
+                let (y, fy) = coord(local.y, ny);
+                let (z, fz) = coord(local.z, nz);
+                let get = |dx: usize, dy: usize, dz: usize| {
+                    let (i, j, k) = ((x + dx).min(nx - 1), (y + dy).min(ny - 1), (z + dz).min(nz - 1));
+                    data[(k * ny + j) * nx + i]
+                };
+                let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+                lerp(
+                    lerp(lerp(get(0, 0, 0), get(1, 0, 0), fx), lerp(get(0, 1, 0), get(1, 1, 0), fx), fy),
+                    lerp(lerp(get(0, 0, 1), get(1, 0, 1), fx), lerp(get(0, 1, 1), get(1, 1, 1), fx), fy),
+                    fz,
+                )
+            },
+        }
+    }
+}
+
+/// Medium bounded by an axis aligned box
+pub struct Volume {
+    min:     Vec3,
+    max:     Vec3,
+    density: Density,
+    medium:  Medium,
+}
+
+impl Volume {
+    pub fn new(pos: Vec3, dim: Vec3, density: Density, medium: Medium) -> Volume {
+        Volume { min: pos - dim / 2., max: pos + dim / 2., density: density, medium: medium }
+    }
+
+    fn density(&self, pos: Vec3) -> f64 {
+        if pos.x < self.min.x || pos.y < self.min.y || pos.z < self.min.z
+            || pos.x > self.max.x || pos.y > self.max.y || pos.z > self.max.z {
+            return 0.
+        }
+        self.density.at(pos, (pos - self.min) / (self.max - self.min))
+    }
+
+    fn to_json(&self) -> Json {
+        let mut fields = vec![
+            ("pos", vec3((self.min + self.max) / 2.)),
+            ("dim", vec3(self.max - self.min)),
+            ("density", self.density.to_json()),
+        ];
+        fields.push_all(self.medium.fields().as_slice());
+        obj(fields)
+    }
+}
+
+pub struct Media {
+    fog:      Option<Medium>, // Homogeneous, everywhere
+    fog_dist: f64,            // Fog stops at this distance, hiding the background
+    volumes:  Vec<Volume>,
+    step:     f64,            // Ray marching step
+}
+
+impl Media {
+    pub fn new(fog: Option<Medium>, fog_dist: f64, volumes: Vec<Volume>, step: f64) -> Media {
+        Media { fog: fog, fog_dist: fog_dist, volumes: volumes, step: step }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fog.is_none() && self.volumes.is_empty()
+    }
+
+    // Fields of the scene, the distance of the fog is left out when it has none
+    pub fn to_json(&self) -> Vec<(&'static str, Json)> {
+        let mut fields = vec![];
+        if let Some(ref fog) = self.fog {
+            let mut fog = fog.fields();
+            if self.fog_dist.is_finite() {
+                fog.push(("dist", num(self.fog_dist)));
+            }
+            fields.push(("fog", obj(fog)));
+        }
+        fields.push(("volumes", Json::Array(self.volumes.iter().map(|volume| volume.to_json()).collect())));
+        fields.push(("march-step", num(self.step)));
+        fields
+    }
+
+    // The fog, left out when it neither absorbs nor scatters
+    fn visible_fog(&self) -> Option<&Medium> {
+        match self.fog {
+            Some(ref fog) if fog.extinction() > 0. => Some(fog),
+            _                                      => None,
+        }
+    }
+
+    // Part of the ray to march, up to `dist`
+    fn bounds(&self, ray: &Ray, dist: f64) -> Option<(f64, f64)> {
+        let dist = dist.min(MARCH_DIST);
+        if self.visible_fog().is_some() {
+            return Some((0., dist.min(self.fog_dist)));
+        }
+        let mut bounds: Option<(f64, f64)> = None;
+        for volume in self.volumes.iter() {
+            if let Some((near, _, far, _)) = slab(ray.pos, ray.dir, volume.min, volume.max) {
+                let (near, far) = (near.max(0.), far.min(dist));
+                if near < far {
+                    bounds = Some(match bounds {
+                        Some((start, end)) => (start.min(near), end.max(far)),
+                        None               => (near, far),
+                    });
+                }
+            }
+        }
+        bounds
+    }
+
+    // Extinction and light scattered towards `-dir` at a point, summed over every medium
+    fn sample(&self, pos: Vec3, dir: Vec3, scene: &Scene) -> (f64, Color) {
+        let mut media: Vec<(&Medium, f64)> = self.volumes.iter().map(|volume| (&volume.medium, volume.density(pos))).collect();
+        if let Some(ref fog) = self.fog {
+            media.push((fog, 1.));
+        }
+
+        let mut extinction = 0.;
+        let mut light = Color::new(0., 0., 0.);
+        for &(medium, density) in media.iter().filter(|&&(_, density)| density > 0.) {
+            extinction += medium.extinction() * density;
+            let bright = scene.lights().scatter(pos, dir, medium.g, scene);
+            light = light + medium.color * (medium.scatter * density * bright);
+        }
+        (extinction, light)
+    }
+
+    // Attenuate `color`, seen at `dist` along the ray, and add the light scattered towards the eye
+    pub fn integrate(&self, ray: &Ray, dist: f64, color: Color, scene: &Scene) -> Color {
+        let (start, end) = match self.bounds(ray, dist) {
+            Some(bounds) => bounds,
+            None         => return color,
+        };
+
+        let mut transmittance = 1.;
+        let mut scattered = Color::new(0., 0., 0.);
+        let mut t = start + self.step * rand::random::<f64>();
+        while t < end {
+            let (extinction, light) = self.sample(ray.pos + ray.dir * t, ray.dir, scene);
+            scattered = scattered + light * (transmittance * self.step);
+            transmittance *= (-extinction * self.step).exp();
+            if transmittance < 0.001 {
+                break;
+            }
+            t += self.step;
+        }
+
+        // Fog hides everything past its distance
+        if self.visible_fog().is_some() && dist > self.fog_dist {
+            transmittance = 0.;
+        }
+        color * transmittance + scattered
+    }
+}
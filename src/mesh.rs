@@ -0,0 +1,190 @@
+use std::num::Float;
+use std::sync::Arc;
+use vec::{ Vec3, cross };
+use ray::{ Ray, Inter };
+use material::{ Color, Material };
+use object::{ Object, slab, facing };
+use heightfield::triangle;
+
+// Triangles per leaf of the hierarchy
+const LEAF: usize = 4;
+
+// Image multiplying the color of a material, nearest texel
+pub struct Texture {
+    w:      usize,
+    h:      usize,
+    pixels: Vec<Color>,
+}
+
+impl Texture {
+    pub fn new(w: usize, h: usize, pixels: Vec<Color>) -> Texture {
+        Texture { w: w, h: h, pixels: pixels }
+    }
+
+    // (0, 0) is the top left corner, the image repeats outside [0, 1]
+    fn at(&self, u: f64, v: f64) -> Color {
+        let x = (((u - u.floor()) * self.w as f64) as usize).min(self.w - 1);
+        let y = (((v - v.floor()) * self.h as f64) as usize).min(self.h - 1);
+        self.pixels[y * self.w + x]
+    }
+}
+
+pub struct Surface {
+    pub mat:     Arc<Material>,
+    pub texture: Option<Arc<Texture>>,
+}
+
+impl Surface {
+    pub fn new(mat: Arc<Material>, texture: Option<Arc<Texture>>) -> Surface {
+        Surface { mat: mat, texture: texture }
+    }
+}
+
+#[derive(Copy)]
+pub struct Triangle {
+    pub points:  [Vec3; 3],
+    pub normals: Option<[Vec3; 3]>,        // Flat shading without them
+    pub uvs:     Option<[(f64, f64); 3]>,
+    pub surface: usize,                    // Index in the surfaces of the mesh
+}
+
+impl Triangle {
+    fn centroid(&self) -> Vec3 {
+        (self.points[0] + self.points[1] + self.points[2]) / 3.
+    }
+}
+
+// Node of the bounding volume hierarchy, the first child of an inner node follows it
+struct Node {
+    min:   Vec3,
+    max:   Vec3,
+    start: usize, // First triangle of a leaf, or second child of an inner node
+    count: usize, // Triangles of a leaf, 0 for an inner node
+}
+
+fn axis(vec: Vec3, axis: usize) -> f64 {
+    match axis {
+        0 => vec.x,
+        1 => vec.y,
+        _ => vec.z,
+    }
+}
+
+fn min(left: Vec3, right: Vec3) -> Vec3 {
+    Vec3::new(left.x.min(right.x), left.y.min(right.y), left.z.min(right.z))
+}
+
+fn max(left: Vec3, right: Vec3) -> Vec3 {
+    Vec3::new(left.x.max(right.x), left.y.max(right.y), left.z.max(right.z))
+}
+
+// Triangle mesh, with a bounding volume hierarchy
+#[allow(dead_code)]
+pub struct Mesh {
+    triangles: Vec<Triangle>,
+    surfaces:  Vec<Surface>,
+    nodes:     Vec<Node>,
+}
+
+impl Mesh {
+    #[allow(dead_code)]
+    pub fn new(triangles: Vec<Triangle>, surfaces: Vec<Surface>) -> Mesh {
+        let mut mesh = Mesh { triangles: triangles, surfaces: surfaces, nodes: vec![] };
+        let count = mesh.triangles.len();
+        if count > 0 {
+            mesh.build(0, count);
+        }
+        mesh
+    }
+
+    pub fn len(&self) -> usize {
+        self.triangles.len()
+    }
+
+    // Node of the triangles [start, end), split at the median of their centroids along the longest axis
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let first = self.triangles[start].points[0];
+        let (mut lo, mut hi) = (first, first);
+        let (mut clo, mut chi) = (self.triangles[start].centroid(), self.triangles[start].centroid());
+        for tri in self.triangles[start..end].iter() {
+            for &point in tri.points.iter() {
+                lo = min(lo, point);
+                hi = max(hi, point);
+            }
+            clo = min(clo, tri.centroid());
+            chi = max(chi, tri.centroid());
+        }
+        let index = self.nodes.len();
+        self.nodes.push(Node { min: lo, max: hi, start: start, count: end - start });
+        if end - start <= LEAF {
+            return index;
+        }
+
+        let size = chi - clo;
+        let split = if size.x >= size.y && size.x >= size.z { 0 } else if size.y >= size.z { 1 } else { 2 };
+        self.triangles[start..end].sort_by(|l, r| {
+            axis(l.centroid(), split).partial_cmp(&axis(r.centroid(), split)).unwrap()
+        });
+        let mid = (start + end) / 2;
+        self.build(start, mid);
+        let right = self.build(mid, end);
+        self.nodes[index].start = right;
+        self.nodes[index].count = 0;
+        index
+    }
+
+    // Color of the surface of a triangle, at barycentric coordinates (u, v)
+    fn material(&self, tri: &Triangle, u: f64, v: f64) -> Arc<Material> {
+        let surface = &self.surfaces[tri.surface];
+        match (surface.texture.as_ref(), tri.uvs) {
+            (Some(texture), Some(uvs)) => {
+                let w = 1. - u - v;
+                let s = uvs[0].0 * w + uvs[1].0 * u + uvs[2].0 * v;
+                let t = uvs[0].1 * w + uvs[1].1 * u + uvs[2].1 * v;
+                Arc::new(surface.mat.with_color(surface.mat.color * texture.at(s, t)))
+            },
+            _                          => surface.mat.clone(),
+        }
+    }
+}
+
+impl Object for Mesh {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        if self.nodes.is_empty() {
+            return None
+        }
+
+        // Walk the hierarchy, skipping the boxes behind the closest hit
+        let mut best: Option<(f64, usize, f64, f64)> = None;
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            match slab(ray.pos, ray.dir, node.min, node.max) {
+                Some((near, _, _, _)) if best.map_or(true, |hit| near < hit.0) => {},
+                _                                                             => continue,
+            }
+            if node.count == 0 {
+                stack.push(node.start);
+                stack.push(i + 1);
+                continue;
+            }
+            for t in node.start..(node.start + node.count) {
+                let tri = &self.triangles[t];
+                if let Some((dist, u, v)) = triangle(ray, tri.points[0], tri.points[1], tri.points[2]) {
+                    if best.map_or(true, |hit| dist < hit.0) {
+                        best = Some((dist, t, u, v));
+                    }
+                }
+            }
+        }
+
+        best.map(|(dist, t, u, v)| {
+            let tri = &self.triangles[t];
+            let normal = match tri.normals {
+                Some(n) => n[0] * (1. - u - v) + n[1] * u + n[2] * v,
+                None    => cross(tri.points[1] - tri.points[0], tri.points[2] - tri.points[0]),
+            };
+            facing(ray, dist, normal, &self.material(tri, u, v))
+        })
+    }
+}
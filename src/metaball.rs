@@ -0,0 +1,139 @@
+use std::num::Float;
+use std::f64::INFINITY;
+use std::sync::Arc;
+use serialize::json::Json;
+use vec::{ Vec3, dot };
+use ray::{ Ray, Inter };
+use material::Material;
+use object::{ Object, EPSILON, facing };
+use solver::quadratic;
+use export::{ Export, obj, variant, num, vec3 };
+
+/// Field of `strength` at `pos`, falling to 0 at `radius`
+#[derive(Copy)]
+pub struct Ball {
+    pub pos:      Vec3,
+    pub radius:   f64, // No influence past this distance
+    pub strength: f64,
+}
+
+impl Ball {
+    pub fn new(pos: Vec3, radius: f64, strength: f64) -> Ball {
+        Ball { pos: pos, radius: radius, strength: strength }
+    }
+
+    // Wyvill falloff: strength * (1 - d^2 / r^2)^3
+    fn field(&self, pos: Vec3) -> f64 {
+        let diff = pos - self.pos;
+        let x = 1. - dot(diff, diff) / (self.radius * self.radius);
+        if x <= 0. { 0. } else { self.strength * x * x * x }
+    }
+
+    fn gradient(&self, pos: Vec3) -> Vec3 {
+        let diff = pos - self.pos;
+        let r2 = self.radius * self.radius;
+        let x = 1. - dot(diff, diff) / r2;
+        if x <= 0. {
+            return Vec3::new(0., 0., 0.);
+        }
+        diff * (-6. * self.strength * x * x / r2)
+    }
+}
+
+/// Surface where the fields of the balls add up to `threshold`
+#[allow(dead_code)]
+pub struct Metaballs {
+    balls:     Vec<Ball>,
+    threshold: f64,
+    step:      f64, // Marching step, small enough to not miss thin parts
+    mat:       Arc<Material>,
+}
+
+impl Metaballs {
+    /// Panics if a radius is not greater than 0
+    #[allow(dead_code)]
+    pub fn new(balls: Vec<Ball>, threshold: f64, mat: Arc<Material>) -> Metaballs {
+        let min_radius = balls.iter().fold(INFINITY, |acc: f64, ball| acc.min(ball.radius));
+        let step = min_radius / 16.;
+        // The marching would never move forward
+        assert!(step > 0., "Metaball radii must be greater than 0");
+        Metaballs { balls: balls, threshold: threshold, step: step, mat: mat }
+    }
+
+    fn field(&self, pos: Vec3) -> f64 {
+        self.balls.iter().fold(0., |acc, ball| acc + ball.field(pos)) - self.threshold
+    }
+
+    fn gradient(&self, pos: Vec3) -> Vec3 {
+        self.balls.iter().fold(Vec3::new(0., 0., 0.), |acc, ball| acc + ball.gradient(pos))
+    }
+
+    // Merged intervals of the ray inside the balls' influence
+    fn intervals(&self, ray: &Ray) -> Vec<(f64, f64)> {
+        let mut all: Vec<(f64, f64)> = self.balls.iter().filter_map(|ball| {
+            let oc = ray.pos - ball.pos;
+            let roots = quadratic(dot(ray.dir, ray.dir), 2. * dot(ray.dir, oc), dot(oc, oc) - ball.radius * ball.radius);
+            if roots.len() < 2 || roots[1] < EPSILON { None } else { Some((roots[0].max(EPSILON), roots[1])) }
+        }).collect();
+        all.sort_by(|l, r| l.0.partial_cmp(&r.0).unwrap());
+
+        let mut merged: Vec<(f64, f64)> = vec![];
+        for &(start, end) in all.iter() {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => {
+                    last.1 = last.1.max(end);
+                    continue;
+                },
+                _ => {},
+            }
+            merged.push((start, end));
+        }
+        merged
+    }
+
+    // Bisection between two distances where the field changes sign
+    fn refine(&self, ray: &Ray, mut low: f64, mut high: f64) -> f64 {
+        let low_inside = self.field(ray.pos + ray.dir * low) > 0.;
+        for _ in 0..50 {
+            let mid = (low + high) / 2.;
+            if (self.field(ray.pos + ray.dir * mid) > 0.) == low_inside {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        (low + high) / 2.
+    }
+}
+
+impl Object for Metaballs {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        for &(start, end) in self.intervals(ray).iter() {
+            let mut prev = start;
+            let inside = self.field(ray.pos + ray.dir * prev) > 0.;
+            while prev < end {
+                let next = (prev + self.step).min(end);
+                if (self.field(ray.pos + ray.dir * next) > 0.) != inside {
+                    let dist = self.refine(ray, prev, next);
+                    let pos = ray.pos + ray.dir * dist;
+                    return Some(facing(ray, dist, self.gradient(pos) * -1., &self.mat));
+                }
+                prev = next;
+            }
+        }
+        None
+    }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        let balls = self.balls.iter().map(|ball| obj(vec![
+            ("pos", vec3(ball.pos)),
+            ("radius", num(ball.radius)),
+            ("strength", num(ball.strength)),
+        ])).collect();
+        variant("metaballs", vec![
+            ("balls", Json::Array(balls)),
+            ("threshold", num(self.threshold)),
+            ("mat", self.mat.to_json()),
+        ])
+    }
+}
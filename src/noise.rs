@@ -0,0 +1,79 @@
+use std::num::Float;
+use vec::Vec3;
+
+/// Improved Perlin noise, with a permutation shuffled from a seed
+pub struct Perlin {
+    pub seed: u32,
+    perm:     Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u32) -> Perlin {
+        let mut perm: Vec<usize> = (0..256).collect();
+
+        // Fisher-Yates with a linear congruential generator
+        let mut state = seed as u64;
+        for i in (1..256).rev() {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let j = (state >> 33) as usize % (i + 1);
+            perm.swap(i, j);
+        }
+        let copy = perm.clone();
+        perm.push_all(copy.as_slice());
+        Perlin { seed: seed, perm: perm }
+    }
+
+    fn fade(t: f64) -> f64 {
+        t * t * t * (t * (t * 6. - 15.) + 10.)
+    }
+
+    fn lerp(t: f64, a: f64, b: f64) -> f64 {
+        a + t * (b - a)
+    }
+
+    fn grad(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+        let h = hash & 15;
+        let u = if h < 8 { x } else { y };
+        let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+        (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+    }
+
+    // Noise in [-1, 1]
+    pub fn noise(&self, pos: Vec3) -> f64 {
+        let (fx, fy, fz) = (pos.x.floor(), pos.y.floor(), pos.z.floor());
+        let (x, y, z) = (pos.x - fx, pos.y - fy, pos.z - fz);
+        let (xi, yi, zi) = ((fx as i64 & 255) as usize, (fy as i64 & 255) as usize, (fz as i64 & 255) as usize);
+        let (u, v, w) = (Perlin::fade(x), Perlin::fade(y), Perlin::fade(z));
+
+        let p = &self.perm;
+        let a = p[xi] + yi;
+        let aa = p[a] + zi;
+        let ab = p[a + 1] + zi;
+        let b = p[xi + 1] + yi;
+        let ba = p[b] + zi;
+        let bb = p[b + 1] + zi;
+
+        Perlin::lerp(w,
+            Perlin::lerp(v,
+                Perlin::lerp(u, Perlin::grad(p[aa], x, y, z), Perlin::grad(p[ba], x - 1., y, z)),
+                Perlin::lerp(u, Perlin::grad(p[ab], x, y - 1., z), Perlin::grad(p[bb], x - 1., y - 1., z))),
+            Perlin::lerp(v,
+                Perlin::lerp(u, Perlin::grad(p[aa + 1], x, y, z - 1.), Perlin::grad(p[ba + 1], x - 1., y, z - 1.)),
+                Perlin::lerp(u, Perlin::grad(p[ab + 1], x, y - 1., z - 1.), Perlin::grad(p[bb + 1], x - 1., y - 1., z - 1.))))
+    }
+
+    // Fractal sum of octaves, in [0, 1]
+    pub fn fbm(&self, pos: Vec3, octaves: u32) -> f64 {
+        let mut sum = 0.;
+        let mut amp = 0.5;
+        let mut freq = 1.;
+        let mut total = 0.;
+        for _ in 0..octaves {
+            sum += self.noise(pos * freq) * amp;
+            total += amp;
+            amp *= 0.5;
+            freq *= 2.;
+        }
+        (sum / total * 0.5 + 0.5).max(0.).min(1.)
+    }
+}
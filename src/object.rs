@@ -1,12 +1,95 @@
 use std::num::Float;
 use std::f64::consts::PI;
-use std::rc::Rc;
-use vec::{ Vec3, dot, rotate };
+use std::f64::INFINITY;
+use std::sync::Arc;
+use serialize::json::Json;
+use vec::{ Vec3, Frame, dot, cross, rotate, unrotate };
 use ray::{ Ray, Inter };
 use material::Material;
+use solver::{ quadratic, quartic };
+use export::{ Export, variant, num, string, vec3 };
 
-pub trait Object {
+pub const EPSILON: f64 = 0.00001;
+
+// Keep the nearest hit in front of the ray
+fn closest(best: &mut Option<(f64, Vec3)>, dist: f64, normal: Vec3) {
+    if dist > EPSILON && (best.is_none() || dist < best.unwrap().0) {
+        *best = Some((dist, normal));
+    }
+}
+
+// Intersect a ray with the box [min, max]
+// Return the entry and exit distances with their outward normals
+pub fn slab(pos: Vec3, dir: Vec3, min: Vec3, max: Vec3) -> Option<(f64, Vec3, f64, Vec3)> {
+    let pos = [pos.x, pos.y, pos.z];
+    let dir = [dir.x, dir.y, dir.z];
+    let min = [min.x, min.y, min.z];
+    let max = [max.x, max.y, max.z];
+    let axes = [Vec3::new(1., 0., 0.), Vec3::new(0., 1., 0.), Vec3::new(0., 0., 1.)];
+
+    let mut near = (-INFINITY, axes[0]);
+    let mut far = (INFINITY, axes[0]);
+    for i in 0..3 {
+        if dir[i] == 0. {
+            if pos[i] < min[i] || pos[i] > max[i] {
+                return None
+            }
+            continue;
+        }
+        let t1 = (min[i] - pos[i]) / dir[i];
+        let t2 = (max[i] - pos[i]) / dir[i];
+        let (t_in, n_in, t_out, n_out) = if t1 < t2 {
+            (t1, axes[i] * -1., t2, axes[i])
+        } else {
+            (t2, axes[i], t1, axes[i] * -1.)
+        };
+        if t_in > near.0 {
+            near = (t_in, n_in);
+        }
+        if t_out < far.0 {
+            far = (t_out, n_out);
+        }
+        if near.0 > far.0 || far.0 < 0. {
+            return None
+        }
+    }
+    Some((near.0, near.1, far.0, far.1))
+}
+
+// Pick the visible face of a slab intersection
+// Skyboxes only show their far faces, seen from the inside
+pub fn slab_hit(hit: Option<(f64, Vec3, f64, Vec3)>, skybox: bool) -> Option<(f64, Vec3)> {
+    match hit {
+        Some((near, n_near, _, _)) if !skybox && near > EPSILON => Some((near, n_near)),
+        Some((_, _, far, n_far)) if far > EPSILON               => Some((far, n_far)),
+        _                                                       => None,
+    }
+}
+
+// Build an intersection whose normal faces the ray
+pub fn facing(ray: &Ray, dist: f64, normal: Vec3, mat: &Arc<Material>) -> Inter {
+    let mut normal = normal.normalize();
+    if dot(normal, ray.dir) > 0. {
+        normal = normal * -1.;
+    }
+    Inter::new(dist, ray.pos + ray.dir * dist, normal, mat.clone())
+}
+
+// Same as facing, with a hit computed in a local frame
+fn local_inter(ray: &Ray, frame: &Frame, hit: Option<(f64, Vec3)>, mat: &Arc<Material>) -> Option<Inter> {
+    hit.map(|(dist, normal)| facing(ray, dist, frame.to_world_dir(normal), mat))
+}
+
+/// Anything a ray can hit, implement it to add your own shapes to a scene
+pub trait Object: Send + Sync {
+    /// Nearest intersection in front of the ray
     fn intersect(&self, ray: &Ray) -> Option<Inter>;
+
+    // Canonical JSON, null for objects that cannot be written back
+    #[doc(hidden)]
+    fn to_json(&self, _: &mut Export) -> Json {
+        Json::Null
+    }
 }
 
 pub struct Objects<'a> {
@@ -21,21 +104,31 @@ impl<'a> Objects<'a> {
     pub fn add(&mut self, object: Box<Object + 'a>) {
         self.all.push(object);
     }
-}
 
-impl<'a> Object for Objects<'a> {
-    fn intersect(&self, ray: &Ray) -> Option<Inter> {
-        let mut inter: Option<Inter> = None;
-        for object in self.all.iter() {
+    // Closest intersection, with the index of the object hit
+    pub fn intersect_index(&self, ray: &Ray) -> Option<(usize, Inter)> {
+        let mut inter: Option<(usize, Inter)> = None;
+        for (i, object) in self.all.iter().enumerate() {
             let cur_inter = object.intersect(&ray);
-            if cur_inter.is_some() && (inter.is_none() || cur_inter.as_ref().unwrap().dist < inter.as_ref().unwrap().dist) {
-                inter = cur_inter;
+            if cur_inter.is_some() && (inter.is_none() || cur_inter.as_ref().unwrap().dist < inter.as_ref().unwrap().1.dist) {
+                inter = Some((i, cur_inter.unwrap()));
             }
         }
         inter
     }
 }
 
+impl<'a> Object for Objects<'a> {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        self.intersect_index(ray).map(|(_, inter)| inter)
+    }
+
+    fn to_json(&self, export: &mut Export) -> Json {
+        Json::Array(self.all.iter().map(|object| object.to_json(export)).filter(|json| !json.is_null()).collect())
+    }
+}
+
+/// Object turned by the angles of `dir` around `pos`
 #[allow(dead_code)]
 pub struct Rotate<'a> {
     pos:    Vec3,
@@ -60,26 +153,94 @@ impl<'a> Object for Rotate<'a> {
             Some(inter) => {
                 Some(Inter::new(
                     inter.dist,
-                    rotate(inter.pos - self.pos, self.dir * -1.) + self.pos,
-                    rotate(inter.normal, self.dir * -1.),
+                    unrotate(inter.pos - self.pos, self.dir) + self.pos,
+                    unrotate(inter.normal, self.dir),
                     inter.mat,
                 ))
             },
             None        => None,
         }
     }
+
+    fn to_json(&self, export: &mut Export) -> Json {
+        variant("rotate", vec![
+            ("pos", vec3(self.pos)),
+            ("dir", vec3(self.dir)),
+            ("object", self.object.to_json(export)),
+        ])
+    }
+}
+
+/// Geometry shared between several instances
+pub type Geometry<'a> = Arc<Box<Object + 'a>>;
+
+/// Shared geometry moved to `pos`, turned by the angles of `dir` and scaled
+#[allow(dead_code)]
+pub struct Instance<'a> {
+    name:     String,
+    pos:      Vec3,
+    dir:      Vec3,
+    scale:    f64,
+    mat:      Option<Arc<Material>>, // Override the materials of the geometry
+    geometry: Geometry<'a>,
+}
+
+impl<'a> Instance<'a> {
+    /// `name` is the one of the geometry, `mat` replaces its materials when given
+    /// Panics if `scale` is not greater than 0
+    #[allow(dead_code)]
+    pub fn new(name: String, pos: Vec3, dir: Vec3, scale: f64, mat: Option<Arc<Material>>, geometry: Geometry<'a>) -> Instance<'a> {
+        // Rays are divided by the scale, and distances multiplied back
+        assert!(scale > 0., "Instance scale must be greater than 0");
+        Instance { name: name, pos: pos, dir: dir, scale: scale, mat: mat, geometry: geometry }
+    }
+}
+
+impl<'a> Object for Instance<'a> {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        let local_ray = Ray::new(
+            unrotate(ray.pos - self.pos, self.dir) / self.scale,
+            unrotate(ray.dir, self.dir),
+        );
+        match self.geometry.intersect(&local_ray) {
+            Some(inter) => {
+                let dist = inter.dist * self.scale;
+                let mat = match self.mat {
+                    Some(ref mat) => mat.clone(),
+                    None          => inter.mat,
+                };
+                Some(Inter::new(dist, ray.pos + ray.dir * dist, rotate(inter.normal, self.dir), mat))
+            },
+            None        => None,
+        }
+    }
+
+    fn to_json(&self, export: &mut Export) -> Json {
+        export.geometry(self.name.as_slice(), &self.geometry);
+        let mut fields = vec![
+            ("geometry", string(self.name.as_slice())),
+            ("pos", vec3(self.pos)),
+            ("dir", vec3(self.dir)),
+            ("scale", num(self.scale)),
+        ];
+        if let Some(ref mat) = self.mat {
+            fields.push(("mat", mat.to_json()));
+        }
+        variant("instance", fields)
+    }
 }
 
+/// Sphere of `radius` around `pos`
 #[allow(dead_code)]
 pub struct Sphere {
     pos:    Vec3,
     radius: f64,
-    mat:    Rc<Material>,
+    mat:    Arc<Material>,
 }
 
 impl Sphere {
     #[allow(dead_code)]
-    pub fn new(pos: Vec3, radius: f64, mat: Rc<Material>) -> Sphere {
+    pub fn new(pos: Vec3, radius: f64, mat: Arc<Material>) -> Sphere {
         Sphere { pos: pos, radius: radius, mat: mat }
     }
 }
@@ -110,18 +271,27 @@ impl Object for Sphere {
         };
         Some(Inter::new(dist, pos, normal, self.mat.clone()))
     }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("sphere", vec![
+            ("pos", vec3(self.pos)),
+            ("radius", num(self.radius)),
+            ("mat", self.mat.to_json()),
+        ])
+    }
 }
 
+/// Infinite plane through `pos`, facing `normal`
 #[allow(dead_code)]
 pub struct Plane {
     pos:    Vec3,
     normal: Vec3,
-    mat:    Rc<Material>,
+    mat:    Arc<Material>,
 }
 
 impl Plane {
     #[allow(dead_code)]
-    pub fn new(pos: Vec3, normal: Vec3, mat: Rc<Material>) -> Plane {
+    pub fn new(pos: Vec3, normal: Vec3, mat: Arc<Material>) -> Plane {
         Plane { pos: pos, normal: normal.normalize(), mat: mat }
     }
 }
@@ -135,6 +305,14 @@ impl Object for Plane {
         let pos = ray.pos + ray.dir * dist;
         Some(Inter::new(dist, pos, self.normal, self.mat.clone()))
     }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("plane", vec![
+            ("pos", vec3(self.pos)),
+            ("normal", vec3(self.normal)),
+            ("mat", self.mat.to_json()),
+        ])
+    }
 }
 
 #[allow(dead_code)]
@@ -147,18 +325,31 @@ pub enum Dir {
     Back,   // -Z
 }
 
+impl Dir {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Dir::Left   => "left",
+            Dir::Right  => "right",
+            Dir::Top    => "top",
+            Dir::Bottom => "bottom",
+            Dir::Front  => "front",
+            Dir::Back   => "back",
+        }
+    }
+}
+
 #[allow(dead_code)]
 pub struct AARect {
     pos:    Vec3,
     dir:    Dir,
     dim:    Vec3,
     normal: Vec3,
-    mat:    Rc<Material>,
+    mat:    Arc<Material>,
 }
 
 impl AARect {
     #[allow(dead_code)]
-    pub fn new(pos: Vec3, dir: Dir, dim: Vec3, mat: Rc<Material>) -> AARect {
+    pub fn new(pos: Vec3, dir: Dir, dim: Vec3, mat: Arc<Material>) -> AARect {
         let normal = match dir {
             Dir::Left   => Vec3::new(-1., 0., 0.),
             Dir::Right  => Vec3::new(1., 0., 0.),
@@ -188,58 +379,70 @@ impl Object for AARect {
 
         Some(Inter::new(dist, pos, self.normal, self.mat.clone()))
     }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("aarect", vec![
+            ("pos", vec3(self.pos)),
+            ("dir", string(self.dir.name())),
+            ("dim", vec3(self.dim)),
+            ("mat", self.mat.to_json()),
+        ])
+    }
 }
 
+/// Axis aligned box of size `dim` centered on `pos`, a skybox only shows its inside
 #[allow(dead_code)]
-pub struct AABox<'a> {
-    faces: Objects<'a>,
+pub struct AABox {
+    min:    Vec3,
+    max:    Vec3,
+    skybox: bool,
+    mat:    Arc<Material>,
 }
 
-impl<'a> AABox<'a> {
+impl AABox {
     #[allow(dead_code)]
-    pub fn new(pos: Vec3, dim: Vec3, mat: Rc<Material>, skybox: bool) -> AABox<'a> {
-        let sign = if skybox { -1. } else { 1. };
-
-        let left_pos = Vec3::new(pos.x - dim.x / 2. * sign, pos.y, pos.z);
-        let right_pos = Vec3::new(pos.x + dim.x / 2. * sign, pos.y, pos.z);
-        let top_pos = Vec3::new(pos.x, pos.y + dim.y / 2. * sign, pos.z);
-        let bottom_pos = Vec3::new(pos.x, pos.y - dim.y / 2. * sign, pos.z);
-        let front_pos = Vec3::new(pos.x, pos.y, pos.z + dim.z / 2. * sign);
-        let back_pos = Vec3::new(pos.x, pos.y, pos.z - dim.z / 2. * sign);
-
-        AABox { faces: Objects::new(vec![
-            box AARect::new(left_pos, Dir::Left, dim, mat.clone()),
-            box AARect::new(right_pos, Dir::Right, dim, mat.clone()),
-            box AARect::new(top_pos, Dir::Top, dim, mat.clone()),
-            box AARect::new(bottom_pos, Dir::Bottom, dim, mat.clone()),
-            box AARect::new(front_pos, Dir::Front, dim, mat.clone()),
-            box AARect::new(back_pos, Dir::Back, dim, mat.clone()),
-        ]) }
+    pub fn new(pos: Vec3, dim: Vec3, mat: Arc<Material>, skybox: bool) -> AABox {
+        AABox { min: pos - dim / 2., max: pos + dim / 2., skybox: skybox, mat: mat }
     }
 }
 
-impl<'a> Object for AABox<'a> {
+impl Object for AABox {
     fn intersect(&self, ray: &Ray) -> Option<Inter> {
-        self.faces.intersect(ray)
+        slab_hit(slab(ray.pos, ray.dir, self.min, self.max), self.skybox)
+            .map(|(dist, normal)| facing(ray, dist, normal, &self.mat))
+    }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("aabox", vec![
+            ("pos", vec3((self.min + self.max) / 2.)),
+            ("dim", vec3(self.max - self.min)),
+            ("mat", self.mat.to_json()),
+            ("skybox", Json::Boolean(self.skybox)),
+        ])
     }
 }
 
 #[allow(dead_code)]
 pub struct AAHexa<'a> {
+    pos:   Vec3,
+    x:     f64,
+    y:     f64,
+    mat:   Arc<Material>,
     faces: Objects<'a>,
 }
 
 impl<'a> AAHexa<'a> {
     #[allow(dead_code)]
-    pub fn new(pos: Vec3, x: f64, y: f64, mat: Rc<Material>) -> AAHexa<'a> {
+    pub fn new(pos: Vec3, x: f64, y: f64, mat: Arc<Material>) -> AAHexa<'a> {
         let z = (PI / 6.).tan() * x;
         let dim = Vec3::new(x, y, z);
 
-        AAHexa { faces: Objects::new(vec![
+        let faces = Objects::new(vec![
             box AABox::new(pos, dim, mat.clone(), false),
             box Rotate::new(pos, Vec3::new(0., PI / 3., 0.), box AABox::new(pos, dim, mat.clone(), false)),
             box Rotate::new(pos, Vec3::new(0., 2. * PI / 3., 0.), box AABox::new(pos, dim, mat.clone(), false)),
-        ]) }
+        ]);
+        AAHexa { pos: pos, x: x, y: y, mat: mat, faces: faces }
     }
 }
 
@@ -247,4 +450,524 @@ impl<'a> Object for AAHexa<'a> {
     fn intersect(&self, ray: &Ray) -> Option<Inter> {
         self.faces.intersect(ray)
     }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("aahexa", vec![
+            ("pos", vec3(self.pos)),
+            ("x", num(self.x)),
+            ("y", num(self.y)),
+            ("mat", self.mat.to_json()),
+        ])
+    }
+}
+
+/// Cylinder of `radius` and `height` centered on `pos` along `axis`, with or without caps
+#[allow(dead_code)]
+pub struct Cylinder {
+    frame:  Frame,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat:    Arc<Material>,
+}
+
+impl Cylinder {
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, axis: Vec3, radius: f64, height: f64, capped: bool, mat: Arc<Material>) -> Cylinder {
+        Cylinder { frame: Frame::new(pos, axis), radius: radius, height: height, capped: capped, mat: mat }
+    }
+}
+
+impl Object for Cylinder {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        let o = self.frame.to_local(ray.pos);
+        let d = self.frame.to_local_dir(ray.dir);
+        let half = self.height / 2.;
+        let mut best = None;
+
+        // Side
+        let a = d.x * d.x + d.z * d.z;
+        let b = 2. * (o.x * d.x + o.z * d.z);
+        let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+        for &t in quadratic(a, b, c).iter() {
+            let p = o + d * t;
+            if p.y.abs() <= half {
+                closest(&mut best, t, Vec3::new(p.x, 0., p.z));
+            }
+        }
+
+        // Caps
+        if self.capped && d.y != 0. {
+            for &(y, ny) in [(half, 1.), (-half, -1.)].iter() {
+                let t = (y - o.y) / d.y;
+                let p = o + d * t;
+                if p.x * p.x + p.z * p.z <= self.radius * self.radius {
+                    closest(&mut best, t, Vec3::new(0., ny, 0.));
+                }
+            }
+        }
+
+        local_inter(ray, &self.frame, best, &self.mat)
+    }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("cylinder", vec![
+            ("pos", vec3(self.frame.pos)),
+            ("axis", vec3(self.frame.w)),
+            ("radius", num(self.radius)),
+            ("height", num(self.height)),
+            ("capped", Json::Boolean(self.capped)),
+            ("mat", self.mat.to_json()),
+        ])
+    }
+}
+
+/// Cone of base `radius` and `height`, with or without a cap
+#[allow(dead_code)]
+pub struct Cone {
+    frame:  Frame,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat:    Arc<Material>,
+}
+
+impl Cone {
+    /// `pos` is the center of the base, `axis` points towards the apex
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, axis: Vec3, radius: f64, height: f64, capped: bool, mat: Arc<Material>) -> Cone {
+        Cone { frame: Frame::new(pos, axis), radius: radius, height: height, capped: capped, mat: mat }
+    }
+}
+
+impl Object for Cone {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        let o = self.frame.to_local(ray.pos);
+        let d = self.frame.to_local_dir(ray.dir);
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let mut best = None;
+
+        // Side: x^2 + z^2 = k^2 * (height - y)^2
+        let h = self.height - o.y;
+        let a = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+        let b = 2. * (o.x * d.x + o.z * d.z + k2 * h * d.y);
+        let c = o.x * o.x + o.z * o.z - k2 * h * h;
+        for &t in quadratic(a, b, c).iter() {
+            let p = o + d * t;
+            if p.y >= 0. && p.y <= self.height {
+                closest(&mut best, t, Vec3::new(p.x, k2 * (self.height - p.y), p.z));
+            }
+        }
+
+        // Base
+        if self.capped && d.y != 0. {
+            let t = -o.y / d.y;
+            let p = o + d * t;
+            if p.x * p.x + p.z * p.z <= self.radius * self.radius {
+                closest(&mut best, t, Vec3::new(0., -1., 0.));
+            }
+        }
+
+        local_inter(ray, &self.frame, best, &self.mat)
+    }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("cone", vec![
+            ("pos", vec3(self.frame.pos)),
+            ("axis", vec3(self.frame.w)),
+            ("radius", num(self.radius)),
+            ("height", num(self.height)),
+            ("capped", Json::Boolean(self.capped)),
+            ("mat", self.mat.to_json()),
+        ])
+    }
+}
+
+/// Torus around `axis` through `pos`, `major` is the radius of the ring and `minor` of the tube
+#[allow(dead_code)]
+pub struct Torus {
+    frame: Frame,
+    major: f64, // Distance from the center to the tube
+    minor: f64, // Radius of the tube
+    mat:   Arc<Material>,
+}
+
+impl Torus {
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, axis: Vec3, major: f64, minor: f64, mat: Arc<Material>) -> Torus {
+        Torus { frame: Frame::new(pos, axis), major: major, minor: minor, mat: mat }
+    }
+}
+
+impl Object for Torus {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        let o = self.frame.to_local(ray.pos);
+        let d = self.frame.to_local_dir(ray.dir);
+
+        // Bounding sphere
+        let bound = self.major + self.minor;
+        let b = dot(o, d);
+        let c = dot(o, o) - bound * bound;
+        if c > 0. && (b > 0. || b * b - dot(d, d) * c < 0.) {
+            return None
+        }
+
+        // (x^2 + y^2 + z^2 + R^2 - r^2)^2 = 4 * R^2 * (x^2 + z^2)
+        let sum_d = dot(d, d);
+        let e = dot(o, o) - self.major * self.major - self.minor * self.minor;
+        let f = dot(o, d);
+        let four_r2 = 4. * self.major * self.major;
+        let roots = quartic(
+            sum_d * sum_d,
+            4. * sum_d * f,
+            2. * sum_d * e + 4. * f * f + four_r2 * d.y * d.y,
+            4. * f * e + 2. * four_r2 * o.y * d.y,
+            e * e - four_r2 * (self.minor * self.minor - o.y * o.y),
+        );
+
+        let mut best = None;
+        for &t in roots.iter() {
+            let p = o + d * t;
+            let ring = Vec3::new(p.x, 0., p.z).normalize() * self.major;
+            closest(&mut best, t, p - ring);
+        }
+
+        local_inter(ray, &self.frame, best, &self.mat)
+    }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("torus", vec![
+            ("pos", vec3(self.frame.pos)),
+            ("axis", vec3(self.frame.w)),
+            ("major", num(self.major)),
+            ("minor", num(self.minor)),
+            ("mat", self.mat.to_json()),
+        ])
+    }
+}
+
+/// Flat disc of `radius` centered on `pos`, facing `normal`
+#[allow(dead_code)]
+pub struct Disc {
+    pos:    Vec3,
+    normal: Vec3,
+    radius: f64,
+    mat:    Arc<Material>,
+}
+
+impl Disc {
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, normal: Vec3, radius: f64, mat: Arc<Material>) -> Disc {
+        Disc { pos: pos, normal: normal.normalize(), radius: radius, mat: mat }
+    }
+}
+
+impl Object for Disc {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        let dist = dot(self.pos - ray.pos, self.normal) / dot(ray.dir, self.normal);
+        if dist < 0. {
+            return None
+        }
+        let pos = ray.pos + ray.dir * dist;
+        if (pos - self.pos).length() > self.radius {
+            return None
+        }
+        Some(Inter::new(dist, pos, self.normal, self.mat.clone()))
+    }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("disc", vec![
+            ("pos", vec3(self.pos)),
+            ("normal", vec3(self.normal)),
+            ("radius", num(self.radius)),
+            ("mat", self.mat.to_json()),
+        ])
+    }
+}
+
+/// Cylinder from `a` to `b` with round ends
+#[allow(dead_code)]
+pub struct Capsule {
+    a:      Vec3,
+    b:      Vec3,
+    radius: f64,
+    mat:    Arc<Material>,
+}
+
+impl Capsule {
+    #[allow(dead_code)]
+    pub fn new(a: Vec3, b: Vec3, radius: f64, mat: Arc<Material>) -> Capsule {
+        Capsule { a: a, b: b, radius: radius, mat: mat }
+    }
+}
+
+impl Object for Capsule {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        let ba = self.b - self.a;
+        let baba = dot(ba, ba);
+        let r2 = self.radius * self.radius;
+        let mut best = None;
+
+        // Cylinder between the two ends
+        let oa = ray.pos - self.a;
+        let bard = dot(ba, ray.dir);
+        let baoa = dot(ba, oa);
+        let a = baba * dot(ray.dir, ray.dir) - bard * bard;
+        let b = 2. * (baba * dot(ray.dir, oa) - baoa * bard);
+        let c = baba * dot(oa, oa) - baoa * baoa - r2 * baba;
+        for &t in quadratic(a, b, c).iter() {
+            let y = baoa + t * bard;
+            if y > 0. && y < baba {
+                let pos = ray.pos + ray.dir * t;
+                closest(&mut best, t, pos - (self.a + ba * (y / baba)));
+            }
+        }
+
+        // Hemispheres at each end
+        for &(center, outside) in [(self.a, -1.), (self.b, 1.)].iter() {
+            let oc = ray.pos - center;
+            for &t in quadratic(dot(ray.dir, ray.dir), 2. * dot(ray.dir, oc), dot(oc, oc) - r2).iter() {
+                let pos = ray.pos + ray.dir * t;
+                if dot(pos - center, ba) * outside >= 0. {
+                    closest(&mut best, t, pos - center);
+                }
+            }
+        }
+
+        best.map(|(dist, normal)| facing(ray, dist, normal, &self.mat))
+    }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("capsule", vec![
+            ("a", vec3(self.a)),
+            ("b", vec3(self.b)),
+            ("radius", num(self.radius)),
+            ("mat", self.mat.to_json()),
+        ])
+    }
+}
+
+/// Ellipsoid centered on `pos`, with `radii` along its axes turned by the angles of `dir`
+#[allow(dead_code)]
+pub struct Ellipsoid {
+    pos:   Vec3,
+    radii: Vec3,
+    dir:   Vec3,
+    mat:   Arc<Material>,
+}
+
+impl Ellipsoid {
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, radii: Vec3, dir: Vec3, mat: Arc<Material>) -> Ellipsoid {
+        Ellipsoid { pos: pos, radii: radii, dir: dir, mat: mat }
+    }
+}
+
+impl Object for Ellipsoid {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        // Transform the ray so that the ellipsoid becomes a unit sphere
+        let o = unrotate(ray.pos - self.pos, self.dir) / self.radii;
+        let d = unrotate(ray.dir, self.dir) / self.radii;
+
+        let mut best = None;
+        for &t in quadratic(dot(d, d), 2. * dot(o, d), dot(o, o) - 1.).iter() {
+            let p = o + d * t;
+            closest(&mut best, t, p / self.radii);
+        }
+
+        best.map(|(dist, normal)| facing(ray, dist, rotate(normal, self.dir), &self.mat))
+    }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("ellipsoid", vec![
+            ("pos", vec3(self.pos)),
+            ("radii", vec3(self.radii)),
+            ("dir", vec3(self.dir)),
+            ("mat", self.mat.to_json()),
+        ])
+    }
+}
+
+/// Box of any orientation
+#[allow(dead_code)]
+pub struct OrientedBox {
+    pos:   Vec3,
+    edges: [Vec3; 3],
+    rows:  [Vec3; 3], // Inverse of the edge matrix
+    mat:   Arc<Material>,
+}
+
+impl OrientedBox {
+    /// `pos` is a corner, `u`, `v` and `w` the three edges leaving it, panics if they are zero or in a plane
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, u: Vec3, v: Vec3, w: Vec3, mat: Arc<Material>) -> OrientedBox {
+        let det = dot(u, cross(v, w));
+        if det.abs() <= 1e-12 * u.length() * v.length() * w.length() {
+            panic!("Degenerate box, u, v and w are zero or in a plane");
+        }
+        let rows = [cross(v, w) / det, cross(w, u) / det, cross(u, v) / det];
+        OrientedBox { pos: pos, edges: [u, v, w], rows: rows, mat: mat }
+    }
+
+    fn to_local(&self, vec: Vec3) -> Vec3 {
+        Vec3::new(dot(self.rows[0], vec), dot(self.rows[1], vec), dot(self.rows[2], vec))
+    }
+}
+
+impl Object for OrientedBox {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        let o = self.to_local(ray.pos - self.pos);
+        let d = self.to_local(ray.dir);
+        let unit = Vec3::new(1., 1., 1.);
+        slab_hit(slab(o, d, Vec3::new(0., 0., 0.), unit), false).map(|(dist, n)| {
+            let normal = self.rows[0] * n.x + self.rows[1] * n.y + self.rows[2] * n.z;
+            facing(ray, dist, normal, &self.mat)
+        })
+    }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("box", vec![
+            ("pos", vec3(self.pos)),
+            ("u", vec3(self.edges[0])),
+            ("v", vec3(self.edges[1])),
+            ("w", vec3(self.edges[2])),
+            ("mat", self.mat.to_json()),
+        ])
+    }
+}
+
+/// Parallelogram
+#[allow(dead_code)]
+pub struct Quad {
+    pos:    Vec3,
+    u:      Vec3,
+    v:      Vec3,
+    normal: Vec3,
+    mat:    Arc<Material>,
+}
+
+impl Quad {
+    /// `pos` is a corner, `u` and `v` the two edges leaving it, panics if they are zero or parallel
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, u: Vec3, v: Vec3, mat: Arc<Material>) -> Quad {
+        let normal = cross(u, v);
+        if normal.length() <= 1e-12 * u.length() * v.length() {
+            panic!("Degenerate quad, u and v are zero or parallel");
+        }
+        Quad { pos: pos, u: u, v: v, normal: normal, mat: mat }
+    }
+}
+
+impl Object for Quad {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        // Plane intersection
+        let dist = dot(self.pos - ray.pos, self.normal) / dot(ray.dir, self.normal);
+        if dist < EPSILON {
+            return None
+        }
+        let pos = ray.pos + ray.dir * dist;
+
+        // Coordinates along the edges
+        let w = self.normal / dot(self.normal, self.normal);
+        let diff = pos - self.pos;
+        let alpha = dot(w, cross(diff, self.v));
+        let beta = dot(w, cross(self.u, diff));
+        if alpha < 0. || alpha > 1. || beta < 0. || beta > 1. {
+            return None
+        }
+
+        Some(facing(ray, dist, self.normal, &self.mat))
+    }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("quad", vec![
+            ("pos", vec3(self.pos)),
+            ("u", vec3(self.u)),
+            ("v", vec3(self.v)),
+            ("mat", self.mat.to_json()),
+        ])
+    }
+}
+
+/// Flat polygon
+#[allow(dead_code)]
+pub struct Polygon {
+    points: Vec<Vec3>,
+    normal: Vec3,
+    mat:    Arc<Material>,
+}
+
+impl Polygon {
+    /// `points` must be coplanar, the polygon may be concave
+    /// Panics with fewer than 3 points, or when they are aligned
+    #[allow(dead_code)]
+    pub fn new(points: Vec<Vec3>, mat: Arc<Material>) -> Polygon {
+        if points.len() < 3 {
+            panic!("A polygon needs at least 3 points, got {}", points.len());
+        }
+        // Newell's method, twice the area
+        let mut normal = Vec3::new(0., 0., 0.);
+        let mut extent: f64 = 0.;
+        for i in 0..points.len() {
+            let cur = points[i];
+            let next = points[(i + 1) % points.len()];
+            normal = normal + Vec3::new(
+                (cur.y - next.y) * (cur.z + next.z),
+                (cur.z - next.z) * (cur.x + next.x),
+                (cur.x - next.x) * (cur.y + next.y),
+            );
+            extent = extent.max((cur - points[0]).length());
+        }
+        if normal.length() <= 1e-12 * extent * extent {
+            panic!("Degenerate polygon, its points are aligned");
+        }
+        Polygon { points: points, normal: normal.normalize(), mat: mat }
+    }
+
+    // Project on the plane where the polygon is the largest
+    fn project(&self, vec: Vec3) -> (f64, f64) {
+        let n = self.normal;
+        if n.x.abs() > n.y.abs() && n.x.abs() > n.z.abs() {
+            (vec.y, vec.z)
+        } else if n.y.abs() > n.z.abs() {
+            (vec.z, vec.x)
+        } else {
+            (vec.x, vec.y)
+        }
+    }
+}
+
+impl Object for Polygon {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        // Plane intersection
+        let dist = dot(self.points[0] - ray.pos, self.normal) / dot(ray.dir, self.normal);
+        if dist < EPSILON {
+            return None
+        }
+        let pos = ray.pos + ray.dir * dist;
+
+        // Even-odd rule
+        let (px, py) = self.project(pos);
+        let mut inside = false;
+        for i in 0..self.points.len() {
+            let (ax, ay) = self.project(self.points[i]);
+            let (bx, by) = self.project(self.points[(i + 1) % self.points.len()]);
+            if (ay > py) != (by > py) && px < (bx - ax) * (py - ay) / (by - ay) + ax {
+                inside = !inside;
+            }
+        }
+        if !inside {
+            return None
+        }
+
+        Some(facing(ray, dist, self.normal, &self.mat))
+    }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("polygon", vec![
+            ("points", Json::Array(self.points.iter().map(|point| vec3(*point)).collect())),
+            ("mat", self.mat.to_json()),
+        ])
+    }
 }
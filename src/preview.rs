@@ -0,0 +1,114 @@
+use std::old_io::stdio;
+use std::old_io::timer;
+use std::time::Duration;
+use std::process::{ Command, Stdio };
+use std::sync::mpsc::channel;
+use std::thread;
+use rt::{ Vec3, Buffer, Picture, Eye, Scene };
+use rt::progressive::Accum;
+
+const MOVE:   f64 = 5.;
+const TURN:   f64 = 0.1;
+const PASSES: u32 = 256; // Stop refining after this many passes
+
+// Run stty on the terminal of stdin
+fn stty(args: &[&str]) -> String {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output();
+    match output {
+        Ok(output) => String::from_utf8_lossy(output.stdout.as_slice()).into_owned(),
+        Err(_)     => String::new(),
+    }
+}
+
+// Rows and columns of the terminal
+fn size() -> (u32, u32) {
+    let size = stty(&["size"]);
+    let values: Vec<u32> = size.words().filter_map(|word| word.parse::<u32>().ok()).collect();
+    if values.len() == 2 { (values[0], values[1]) } else { (24, 80) }
+}
+
+// Two pixels per character, the upper half in the foreground color
+fn draw(image: &Buffer) {
+    let mut out = String::from_str("\x1b[H");
+    for y in 0..(image.h / 2) {
+        for x in 0..image.w {
+            let top = image.get(x, y * 2).normalize() * 255.;
+            let bottom = image.get(x, y * 2 + 1).normalize() * 255.;
+            out.push_str(format!("\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                top.r as u8, top.g as u8, top.b as u8, bottom.r as u8, bottom.g as u8, bottom.b as u8).as_slice());
+        }
+        out.push_str("\x1b[0m\r\n");
+    }
+    print!("{}", out);
+    stdio::flush();
+}
+
+// Move the eye with a key, false if it is not bound
+fn control(eye: &mut Eye, key: u8) -> bool {
+    match key as char {
+        'w' => eye.walk(Vec3::new(0., 0., -MOVE)),
+        's' => eye.walk(Vec3::new(0., 0., MOVE)),
+        'a' => eye.walk(Vec3::new(-MOVE, 0., 0.)),
+        'd' => eye.walk(Vec3::new(MOVE, 0., 0.)),
+        'q' => eye.walk(Vec3::new(0., MOVE, 0.)),
+        'e' => eye.walk(Vec3::new(0., -MOVE, 0.)),
+        'o' => eye.turn(Vec3::new(-TURN, 0., 0.)),
+        'l' => eye.turn(Vec3::new(TURN, 0., 0.)),
+        'k' => eye.turn(Vec3::new(0., TURN, 0.)),
+        ';' => eye.turn(Vec3::new(0., -TURN, 0.)),
+        'i' => eye.turn(Vec3::new(0., 0., TURN)),
+        'p' => eye.turn(Vec3::new(0., 0., -TURN)),
+        _   => return false,
+    }
+    true
+}
+
+// Interactive preview in the terminal, refined while the eye stands still
+// Escape quits, enter renders the full picture from the current eye
+pub fn run(mut eye: Eye, scene: &Scene, picture: &Picture) {
+    let (rows, cols) = size();
+    let preview = picture.preview(cols.max(1), (rows.max(2) - 1) * 2);
+
+    // Keys are read in their own thread, to render while waiting
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        let mut stdin = stdio::stdin_raw();
+        loop {
+            match stdin.read_byte() {
+                Ok(key) => if tx.send(key).is_err() { break },
+                Err(_)  => break,
+            }
+        }
+    });
+
+    stty(&["raw", "-echo"]);
+    print!("\x1b[2J\x1b[?25l");
+    let mut accum = Accum::new(preview.w, preview.h);
+    'main: loop {
+        while let Ok(key) = rx.try_recv() {
+            match key {
+                27 | 3 => break 'main, // Escape or ctrl-c
+                13     => {
+                    stty(&["sane"]);
+                    print!("\x1b[2J\x1b[H");
+                    let img = picture.shot(&eye, scene, true);
+                    picture.save(&picture.denoise(&eye, scene, img));
+                    stty(&["raw", "-echo"]);
+                },
+                _      => if control(&mut eye, key) {
+                    accum = Accum::new(preview.w, preview.h);
+                },
+            }
+        }
+
+        if accum.passes < PASSES {
+            accum.add_pass(&preview.shot_pass(&eye, scene));
+            draw(&accum.image());
+        } else {
+            timer::sleep(Duration::milliseconds(20));
+        }
+    }
+    stty(&["sane"]);
+    print!("\x1b[0m\x1b[?25h\x1b[2J\x1b[H");
+    stdio::flush();
+}
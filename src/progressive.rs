@@ -0,0 +1,163 @@
+use std::num::Float;
+use std::f64::INFINITY;
+use std::old_io::{ stdio, Reader, Writer, IoResult, IoError, InvalidInput };
+use std::old_io::fs::{ self, File, PathExtensions };
+use serialize::json::Json;
+use material::Color;
+use buffer::Buffer;
+use export::{ obj, num, int, string };
+
+// Passes rendered when no budget is given
+const DEFAULT_PASSES: u32 = 16;
+
+/// When to stop and when to write checkpoints, 0 means never
+#[derive(Clone)]
+pub struct Progressive {
+    pub passes:            u32,
+    pub time:              f64,  // Seconds
+    pub noise:             f64,  // Mean standard error of the pixels
+    pub checkpoint_time:   f64,  // Seconds between checkpoints
+    pub checkpoint_passes: u32,  // Passes between checkpoints
+    pub accum:             Path, // Accumulation buffer, written at each checkpoint and at the end with `resume`
+    pub resume:            bool, // Start from the accumulation buffer if it exists, and keep it up to date
+}
+
+impl Progressive {
+    /// Without passes, time or noise, the budget is DEFAULT_PASSES passes
+    pub fn new(passes: u32, time: f64, noise: f64, checkpoint_time: f64, checkpoint_passes: u32, accum: &str, resume: bool) -> Progressive {
+        let unbounded = passes == 0 && time <= 0. && noise <= 0.;
+        Progressive {
+            passes: if unbounded { DEFAULT_PASSES } else { passes },
+            time: time,
+            noise: noise,
+            checkpoint_time: checkpoint_time,
+            checkpoint_passes: checkpoint_passes,
+            accum: Path::new(accum),
+            resume: resume,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn to_json(&self) -> Json {
+        obj(vec![
+            ("passes", int(self.passes as u64)),
+            ("time", num(self.time)),
+            ("noise", num(self.noise)),
+            ("checkpoint-time", num(self.checkpoint_time)),
+            ("checkpoint-passes", int(self.checkpoint_passes as u64)),
+            ("accum", string(self.accum.as_str().unwrap())),
+            ("resume", Json::Boolean(self.resume)),
+        ])
+    }
+}
+
+// Write a file next to `path` then rename it, a crash never leaves a truncated file
+pub fn replace<F: FnOnce(&mut File)>(path: &Path, write: F) {
+    let tmp = path.with_filename(format!("{}.tmp", path.filename_str().unwrap()));
+    write(&mut File::create(&tmp).unwrap());
+    fs::rename(&tmp, path).unwrap();
+}
+
+// Sum of every pass, and of the squared luminances to estimate the noise
+pub struct Accum {
+    pub passes: u32,
+    sum:        Buffer,
+    sum_sq:     Vec<f64>,
+}
+
+fn luminance(color: Color) -> f64 {
+    0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b
+}
+
+impl Accum {
+    pub fn new(w: u32, h: u32) -> Accum {
+        Accum { passes: 0, sum: Buffer::new(w, h), sum_sq: vec![0.; (w * h) as usize] }
+    }
+
+    // Load a saved buffer, a new one if missing or of another size
+    // A short or corrupt file is only warned about, the render starts over
+    pub fn load(path: &Path, w: u32, h: u32) -> Accum {
+        if !path.exists() {
+            return Accum::new(w, h);
+        }
+        match Accum::read(path, w, h) {
+            Ok(accum) => accum,
+            Err(err)  => {
+                let warning = format!("warning: {}: {}, starting over", path.display(), err);
+                let _ = stdio::stderr().write_line(warning.as_slice());
+                Accum::new(w, h)
+            },
+        }
+    }
+
+    fn read(path: &Path, w: u32, h: u32) -> IoResult<Accum> {
+        let mut file = try!(File::open(path));
+        let magic = try!(file.read_exact(4));
+        if &magic[..] != &b"RTAC"[..] {
+            return Err(IoError { kind: InvalidInput, desc: "not an accumulation buffer", detail: None });
+        }
+        let (fw, fh) = (try!(file.read_le_u32()), try!(file.read_le_u32()));
+        if fw != w || fh != h {
+            return Ok(Accum::new(w, h));
+        }
+        let mut accum = Accum::new(w, h);
+        accum.passes = try!(file.read_le_u32());
+        for i in 0..((w * h) as usize) {
+            let r = try!(file.read_le_f64());
+            let g = try!(file.read_le_f64());
+            let b = try!(file.read_le_f64());
+            accum.sum.pixels[i] = Color::new(r, g, b);
+            accum.sum_sq[i] = try!(file.read_le_f64());
+        }
+        Ok(accum)
+    }
+
+    pub fn save(&self, path: &Path) {
+        let mut out: Vec<u8> = vec![];
+        out.push_all(b"RTAC");
+        out.write_le_u32(self.sum.w).unwrap();
+        out.write_le_u32(self.sum.h).unwrap();
+        out.write_le_u32(self.passes).unwrap();
+        for (color, sq) in self.sum.pixels.iter().zip(self.sum_sq.iter()) {
+            out.write_le_f64(color.r).unwrap();
+            out.write_le_f64(color.g).unwrap();
+            out.write_le_f64(color.b).unwrap();
+            out.write_le_f64(*sq).unwrap();
+        }
+        replace(path, |file| file.write_all(out.as_slice()).unwrap());
+    }
+
+    pub fn add_pass(&mut self, pass: &Buffer) {
+        for (i, &color) in pass.pixels.iter().enumerate() {
+            self.sum.pixels[i] = self.sum.pixels[i] + color;
+            let l = luminance(color);
+            self.sum_sq[i] += l * l;
+        }
+        self.passes += 1;
+    }
+
+    // Mean of the passes
+    pub fn image(&self) -> Buffer {
+        let mut image = self.sum.clone();
+        if self.passes > 0 {
+            for color in image.pixels.iter_mut() {
+                *color = *color * (1. / self.passes as f64);
+            }
+        }
+        image
+    }
+
+    // Standard error of the mean luminance, averaged over the rendered pixels, given by index
+    pub fn noise(&self, pixels: &[usize]) -> f64 {
+        if self.passes < 2 || pixels.is_empty() {
+            return INFINITY;
+        }
+        let n = self.passes as f64;
+        let total = pixels.iter().fold(0., |acc, &i| {
+            let mean = luminance(self.sum.pixels[i]) / n;
+            let var = (self.sum_sq[i] / n - mean * mean).max(0.);
+            acc + (var / n).sqrt()
+        });
+        total / pixels.len() as f64
+    }
+}
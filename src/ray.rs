@@ -1,7 +1,8 @@
-use std::rc::Rc;
+use std::sync::Arc;
 use vec::Vec3;
 use material::Material;
 
+/// Half line starting at `pos`, along `dir`
 pub struct Ray {
     pub pos: Vec3,
     pub dir: Vec3,
@@ -13,15 +14,16 @@ impl Ray {
     }
 }
 
+/// Where a ray hits an object, `dist` along the ray, with the normal facing the ray
 pub struct Inter {
     pub dist:   f64,
     pub pos:    Vec3,
     pub normal: Vec3,
-    pub mat:    Rc<Material>,
+    pub mat:    Arc<Material>,
 }
 
 impl Inter {
-    pub fn new(dist: f64, pos: Vec3, normal: Vec3, mat: Rc<Material>) -> Inter {
+    pub fn new(dist: f64, pos: Vec3, normal: Vec3, mat: Arc<Material>) -> Inter {
         Inter { dist: dist, pos: pos, normal: normal, mat: mat }
     }
 }
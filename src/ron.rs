@@ -0,0 +1,322 @@
+// RON scenes, parsed into the JSON read by `config`
+//   Sphere(pos: (0, 0, 0), radius: 10)  a type, { "sphere": { "pos": [0, 0, 0], "radius": 10 } }
+//   (w: 800, h: 600)                     a struct, { "w": 800, "h": 600 }
+//   (0, 0, 0) or [0, 0, 0]               an array
+//   { "name": Sphere(...) }              a map, for geometries
+//   Depth, Some(x), None                 "depth", x, and a field left out
+// Names are lowercased and underscores become hyphens, `refr_idx` is "refr-idx"
+
+use std::collections::BTreeMap;
+use serialize::json::Json;
+
+pub fn parse(input: &str) -> Result<Json, String> {
+    let mut parser = Parser { chars: input.chars().collect(), pos: 0 };
+    let value = try!(parser.value());
+    parser.skip();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("expected the end of the file"));
+    }
+    Ok(value)
+}
+
+fn name(ident: &str) -> String {
+    ident.to_lowercase().replace("_", "-")
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos:   usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).map(|c| *c)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).map(|c| *c)
+    }
+
+    fn error(&self, message: &str) -> String {
+        let before = &self.chars[..self.pos.min(self.chars.len())];
+        let line = before.iter().filter(|c| **c == '\n').count() + 1;
+        let col = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+        format!("{} at line {} column {}", message, line, col)
+    }
+
+    // Whitespace and comments
+    fn skip(&mut self) {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(c), _) if c.is_whitespace() => self.pos += 1,
+                (Some('/'), Some('/'))            => {
+                    while self.peek().map_or(false, |c| c != '\n') {
+                        self.pos += 1;
+                    }
+                },
+                (Some('/'), Some('*'))            => {
+                    self.pos += 2;
+                    while self.peek().is_some() && (self.peek(), self.peek_at(1)) != (Some('*'), Some('/')) {
+                        self.pos += 1;
+                    }
+                    self.pos = (self.pos + 2).min(self.chars.len());
+                },
+                _                                 => return,
+            }
+        }
+    }
+
+    // Consume `c` if it comes next
+    fn eat(&mut self, c: char) -> bool {
+        self.skip();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) { Ok(()) } else { Err(self.error(format!("expected '{}'", c).as_slice())) }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip();
+        match self.peek() {
+            Some('(')                                     => {
+                self.pos += 1;
+                self.parens()
+            },
+            Some('[')                                     => {
+                self.pos += 1;
+                Ok(Json::Array(try!(self.items(']'))))
+            },
+            Some('{')                                     => {
+                self.pos += 1;
+                self.map()
+            },
+            Some('"')                                     => Ok(Json::String(try!(self.string()))),
+            Some(c) if c.is_digit(10) || c == '-' || c == '+' || c == '.' => self.number(),
+            Some(c) if c.is_alphabetic() || c == '_'      => self.named(),
+            Some(_)                                       => Err(self.error("unexpected character")),
+            None                                          => Err(self.error("unexpected end of file")),
+        }
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        let start = self.pos;
+        if self.peek().map_or(true, |c| c.is_digit(10)) {
+            return None;
+        }
+        while self.peek().map_or(false, |c| c.is_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            None
+        } else {
+            Some(self.chars[start..self.pos].iter().map(|c| *c).collect())
+        }
+    }
+
+    // After an opening parenthesis, a struct when it starts with `name:`, a tuple otherwise
+    fn parens(&mut self) -> Result<Json, String> {
+        if self.eat(')') {
+            return Ok(Json::Object(BTreeMap::new()));
+        }
+        let start = self.pos;
+        let fields = self.ident().is_some() && self.eat(':');
+        self.pos = start;
+        if fields {
+            self.fields()
+        } else {
+            Ok(Json::Array(try!(self.items(')'))))
+        }
+    }
+
+    fn fields(&mut self) -> Result<Json, String> {
+        let mut obj = BTreeMap::new();
+        loop {
+            if self.eat(')') {
+                break;
+            }
+            let key = match self.ident() {
+                Some(key) => name(key.as_slice()),
+                None      => return Err(self.error("expected a field name")),
+            };
+            try!(self.expect(':'));
+            let value = try!(self.value());
+            if !value.is_null() {
+                obj.insert(key, value);
+            }
+            if !self.eat(',') {
+                try!(self.expect(')'));
+                break;
+            }
+        }
+        Ok(Json::Object(obj))
+    }
+
+    fn items(&mut self, close: char) -> Result<Vec<Json>, String> {
+        let mut items = vec![];
+        loop {
+            if self.eat(close) {
+                break;
+            }
+            items.push(try!(self.value()));
+            if !self.eat(',') {
+                try!(self.expect(close));
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    fn map(&mut self) -> Result<Json, String> {
+        let mut obj = BTreeMap::new();
+        loop {
+            if self.eat('}') {
+                break;
+            }
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = try!(self.string());
+            try!(self.expect(':'));
+            obj.insert(key, try!(self.value()));
+            if !self.eat(',') {
+                try!(self.expect('}'));
+                break;
+            }
+        }
+        Ok(Json::Object(obj))
+    }
+
+    // Booleans, options, unit variants and types
+    fn named(&mut self) -> Result<Json, String> {
+        let ident = self.ident().unwrap();
+        match ident.as_slice() {
+            "true"  => return Ok(Json::Boolean(true)),
+            "false" => return Ok(Json::Boolean(false)),
+            "None"  => return Ok(Json::Null),
+            _       => {},
+        }
+        if !self.eat('(') {
+            return Ok(Json::String(name(ident.as_slice())));
+        }
+        if ident == "Some" {
+            let value = try!(self.value());
+            try!(self.expect(')'));
+            return Ok(value);
+        }
+        let mut obj = BTreeMap::new();
+        obj.insert(name(ident.as_slice()), try!(self.parens()));
+        Ok(Json::Object(obj))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let c = match self.peek() {
+                Some('"')  => {
+                    self.pos += 1;
+                    return Ok(s);
+                },
+                Some('\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some(c)   => c,
+                        None      => return Err(self.error("unterminated string")),
+                    }
+                },
+                Some(c)    => c,
+                None       => return Err(self.error("unterminated string")),
+            };
+            s.push(c);
+            self.pos += 1;
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while self.peek().map_or(false, |c| match c { '0'...'9' | '+' | '-' | '.' | 'e' | 'E' | '_' => true, _ => false }) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().map(|c| *c).filter(|c| *c != '_').collect();
+        if let Ok(n) = text.parse::<u64>() {
+            return Ok(Json::U64(n));
+        }
+        if let Ok(n) = text.parse::<i64>() {
+            return Ok(Json::I64(n));
+        }
+        match text.parse::<f64>() {
+            Ok(n)  => Ok(Json::F64(n)),
+            Err(_) => {
+                self.pos = start;
+                Err(self.error("invalid number"))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serialize::json::Json;
+    use super::parse;
+
+    fn json(text: &str) -> Json {
+        Json::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn structs() {
+        assert_eq!(parse("(w: 800, h: -2, refr_idx: 1.5)").unwrap(), json(r#"{ "w": 800, "h": -2, "refr-idx": 1.5 }"#));
+        assert_eq!(parse("(w: 800,)").unwrap(), json(r#"{ "w": 800 }"#));
+        assert_eq!(parse("()").unwrap(), json("{}"));
+    }
+
+    #[test]
+    fn tuples_and_arrays() {
+        assert_eq!(parse("(0, -1, 2.5)").unwrap(), json("[0, -1, 2.5]"));
+        assert_eq!(parse("[(0, 0, 0), (1, 1, 1),]").unwrap(), json("[[0, 0, 0], [1, 1, 1]]"));
+        assert_eq!(parse("(1_000, 1e-3)").unwrap(), json("[1000, 0.001]"));
+    }
+
+    #[test]
+    fn types() {
+        assert_eq!(parse("Sphere(pos: (0, 0, 0), radius: 10)").unwrap(),
+                   json(r#"{ "sphere": { "pos": [0, 0, 0], "radius": 10 } }"#));
+        assert_eq!(parse(r#"{ "ball": Sphere(radius: 1) }"#).unwrap(), json(r#"{ "ball": { "sphere": { "radius": 1 } } }"#));
+    }
+
+    #[test]
+    fn unit_variants() {
+        assert_eq!(parse("[Depth, Normal]").unwrap(), json(r#"["depth", "normal"]"#));
+        assert_eq!(parse("(skybox: true, capped: false)").unwrap(), json(r#"{ "skybox": true, "capped": false }"#));
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(parse(r#"(ao: Some("ao.png"), tiles: None)"#).unwrap(), json(r#"{ "ao": "ao.png" }"#));
+        assert_eq!(parse("Some((1, 2, 3))").unwrap(), json("[1, 2, 3]"));
+        assert_eq!(parse("[None]").unwrap(), json("[null]"));
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(parse("// size\n(w: 1 /* px */, h: 2)").unwrap(), json(r#"{ "w": 1, "h": 2 }"#));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse("(w: 800"), Err("expected ')' at line 1 column 8".to_string()));
+        assert_eq!(parse("(\n  w: @)"), Err("unexpected character at line 2 column 6".to_string()));
+        assert_eq!(parse("{ w: 1 }"), Err("expected a string key at line 1 column 3".to_string()));
+        assert_eq!(parse("(w: \"image)"), Err("unterminated string at line 1 column 12".to_string()));
+        assert_eq!(parse("(w: 1) 2"), Err("expected the end of the file at line 1 column 8".to_string()));
+    }
+}
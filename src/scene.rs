@@ -1,90 +1,559 @@
 use std::num::Float;
-use std::old_io::fs::File;
+use std::f64::INFINITY;
 use std::old_io::stdio;
-use image::*;
-use vec::{ Vec3, rotate, dot };
+use std::thread;
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicUsize, AtomicBool, Ordering };
+use image::{ DynamicImage, PNG };
+use serialize::json::Json;
+use rand;
+use time;
+use vec::{ Vec3, rotate, dot, cosine_sample };
 use ray::{ Ray, Inter };
-use material::Color;
+use material::{ Color, Material };
+use buffer::Buffer;
+use aov::{ Aov, Pass };
+use exr;
+use denoise::Denoiser;
+use progressive::{ self, Progressive, Accum };
 use object::{ Object, Objects };
 use light::{ Light, Lights };
+use media::Media;
+use sky::Sky;
+use export::{ Export, obj, num, int, string, vec3 };
 
+// Part of the picture to render
+pub struct Region {
+    crop:  Option<(f64, f64, f64, f64)>, // x, y, w, h in [0, 1], the image is cut to it
+    tiles: Option<(u32, Vec<(u32, u32)>)>, // Size and coordinates, rendered into the previous image
+}
+
+impl Region {
+    pub fn new(crop: Option<(f64, f64, f64, f64)>, tiles: Option<(u32, Vec<(u32, u32)>)>) -> Region {
+        Region { crop: crop, tiles: tiles }
+    }
+}
+
+/// Progress and cancellation of a render, shared with other threads
+pub struct Status {
+    progress: AtomicUsize, // Per mille of the current pass
+    cancel:   AtomicBool,
+}
+
+impl Status {
+    /// Not cancelled, nothing rendered
+    pub fn new() -> Status {
+        Status { progress: AtomicUsize::new(0), cancel: AtomicBool::new(false) }
+    }
+
+    /// Progress of the current render, in [0, 1]
+    pub fn progress(&self) -> f64 {
+        self.progress.load(Ordering::SeqCst) as f64 / 1000.
+    }
+
+    /// Stop the renders as soon as possible, their pictures are left unfinished
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether `cancel` was called
+    pub fn cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+}
+
+/// Size, quality and outputs of a render, made with `PictureBuilder`
 pub struct Picture {
-    pub w:    u32,
-    pub h:    u32,
-    pub path: Path,
-    bounce:   u32,
-    sample:   u32,
+    pub w:       u32,
+    pub h:       u32,
+    pub path:    Path,
+    pub ao:      Option<Path>, // Standalone ambient occlusion pass
+    pub passes:  Vec<Pass>,
+    exr:         bool,         // Write passes as layers of a single file
+    denoiser:    Option<Denoiser>,
+    progressive: Option<Progressive>,
+    region:      Region,
+    threads:     u32,
+    bounce:      u32,
+    sample:      u32,
+    status:      Option<Arc<Status>>,
 }
 
 impl Picture {
-    pub fn new(w: u32, h: u32, path: &str, bounce: u32, sample: u32) -> Picture {
-        Picture { w: w, h: h, path: Path::new(path), bounce: bounce, sample: sample }
+    // Every setting in order, `PictureBuilder` names them
+    #[doc(hidden)]
+    pub fn new(w: u32, h: u32, path: &str, ao: Option<&str>, passes: Vec<Pass>, exr: bool, denoiser: Option<Denoiser>,
+               progressive: Option<Progressive>, region: Region, threads: u32, bounce: u32, sample: u32) -> Picture {
+        Picture {
+            w: w,
+            h: h,
+            path: Path::new(path),
+            ao: ao.map(|ao| Path::new(ao)),
+            passes: passes,
+            exr: exr,
+            denoiser: denoiser,
+            progressive: progressive,
+            region: region,
+            threads: threads.max(1),
+            bounce: bounce,
+            sample: sample,
+            status: None,
+        }
+    }
+
+    // Every field explicit, the crop window normalized
+    #[doc(hidden)]
+    pub fn to_json(&self) -> Json {
+        let mut fields = vec![
+            ("w", int(self.w as u64)),
+            ("h", int(self.h as u64)),
+            ("scale", num(1.)),
+            ("path", string(self.path.as_str().unwrap())),
+        ];
+        if let Some(ref ao) = self.ao {
+            fields.push(("ao", string(ao.as_str().unwrap())));
+        }
+        fields.push(("passes", Json::Array(self.passes.iter().map(|pass| string(pass.name())).collect())));
+        fields.push(("passes-format", string(if self.exr { "exr" } else { "png" })));
+        fields.push(("denoise", match self.denoiser {
+            Some(ref denoiser) => denoiser.to_json(),
+            None               => Json::Boolean(false),
+        }));
+        if let Some(ref progressive) = self.progressive {
+            fields.push(("progressive", progressive.to_json()));
+        }
+        if let Some((x, y, w, h)) = self.region.crop {
+            fields.push(("crop", obj(vec![
+                ("x", num(x)),
+                ("y", num(y)),
+                ("w", num(w)),
+                ("h", num(h)),
+                ("normalized", Json::Boolean(true)),
+            ])));
+        }
+        if let Some((size, ref list)) = self.region.tiles {
+            let list = list.iter().map(|&(x, y)| Json::Array(vec![int(x as u64), int(y as u64)])).collect();
+            fields.push(("tiles", obj(vec![("size", int(size as u64)), ("list", Json::Array(list))])));
+        }
+        fields.push(("threads", int(self.threads as u64)));
+        fields.push(("bounce", int(self.bounce as u64)));
+        fields.push(("sample", int(self.sample as u64)));
+        obj(fields)
+    }
+
+    // Plain picture of another size, with the same path and quality
+    #[doc(hidden)]
+    pub fn preview(&self, w: u32, h: u32) -> Picture {
+        Picture::new(w, h, self.path.as_str().unwrap(), None, vec![], false, None, None, Region::new(None, None),
+                     self.threads, self.bounce, 1)
+    }
+
+    // Ray through subpixel (sx, sy) of pixel (px, py)
+    fn make_ray(&self, eye: &Eye, px: u32, py: u32, sx: u32, sy: u32) -> Ray {
+        let sample = self.sample as f64;
+        self.ray_at(eye, px as f64 + (sx as f64 + 0.5) / sample, py as f64 + (sy as f64 + 0.5) / sample)
     }
 
-    // Picture a scene
-    pub fn shot(&self, eye: &Eye, scene: &Scene, progress: bool) -> DynamicImage {
-        // Initialize variables used to compute ray
-        let w = (self.w * self.sample) as f64;
-        let h = (self.h * self.sample) as f64;
+    // Ray through a point of the picture, in pixels from the top left corner
+    fn ray_at(&self, eye: &Eye, x: f64, y: f64) -> Ray {
         let dist = 100.;
         let screen_x = (eye.fov / 2.).tan() * dist;
-        let screen_y = screen_x * h / w;
-        let step = Vec3::new(screen_x / w, -screen_y / h, 0.);
-        let start = Vec3::new(-screen_x / 2., screen_y / 2., -dist) + step / 2.;
-
-        // Make ray
-        let make_ray = |px, py, sx, sy| {
-            let x = px * self.sample + sx;
-            let y = py * self.sample + sy;
-            let cur = Vec3::new(x as f64, y as f64, 0.);
-            let mut dir = start + cur * step;
-            dir = rotate(dir, eye.dir).normalize();
-            Ray::new(eye.pos, dir)
+        let screen_y = screen_x * self.h as f64 / self.w as f64;
+        let dir = Vec3::new(screen_x * (x / self.w as f64 - 0.5), screen_y * (0.5 - y / self.h as f64), -dist);
+        Ray::new(eye.pos, rotate(dir, eye.dir).normalize())
+    }
+
+    // Pixel rectangle of the crop window, x0, y0, x1, y1 with the ends excluded
+    fn rect(&self) -> (u32, u32, u32, u32) {
+        match self.region.crop {
+            Some((x, y, w, h)) => {
+                let (fw, fh) = (self.w as f64, self.h as f64);
+                let x0 = (x * fw).floor().max(0.).min(fw) as u32;
+                let y0 = (y * fh).floor().max(0.).min(fh) as u32;
+                let x1 = ((x + w) * fw).ceil().max(0.).min(fw) as u32;
+                let y1 = ((y + h) * fh).ceil().max(0.).min(fh) as u32;
+                (x0, y0, x1.max(x0), y1.max(y0))
+            },
+            None               => (0, 0, self.w, self.h),
+        }
+    }
+
+    // Check that a pixel belongs to one of the tiles to render
+    fn in_tiles(&self, px: u32, py: u32) -> bool {
+        match self.region.tiles {
+            Some((size, ref tiles)) => tiles.iter().any(|&tile| tile == (px / size, py / size)),
+            None                    => true,
+        }
+    }
+
+    // Call `f` on every pixel to render, row by row
+    fn visit<F: FnMut(u32, u32)>(&self, progress: bool, mut f: F) {
+        let (x0, y0, x1, y1) = self.rect();
+        for py in y0..y1 {
+            for px in (x0..x1).filter(|&px| self.in_tiles(px, py)) {
+                f(px, py);
+            }
+            self.progress(progress, py - y0 + 1, y1 - y0);
+        }
+        if progress {
+            println!("");
+        }
+    }
+
+    // Same as `visit`, with the rows spread over the threads, the values of the pixels are returned row by row
+    fn visit_parallel<T: Send, F: Fn(u32, u32) -> T + Sync>(&self, progress: bool, f: F) -> Vec<(u32, u32, T)> {
+        let (x0, y0, x1, y1) = self.rect();
+        let done = AtomicUsize::new(0);
+        let rows: Vec<Vec<(u32, u32, T)>> = {
+            let (f, done) = (&f, &done);
+            let guards: Vec<_> = (0..self.threads).map(|t| thread::scoped(move || {
+                let mut pixels = vec![];
+                for py in (y0..y1).filter(|&py| (py - y0) % self.threads == t) {
+                    if self.cancelled() {
+                        break;
+                    }
+                    for px in (x0..x1).filter(|&px| self.in_tiles(px, py)) {
+                        pixels.push((px, py, f(px, py)));
+                    }
+                    let row = done.fetch_add(1, Ordering::SeqCst) as u32 + 1;
+                    self.progress(progress, row, y1 - y0);
+                }
+                pixels
+            })).collect();
+            guards.into_iter().map(|guard| guard.join()).collect()
         };
+        if progress {
+            println!("");
+        }
+        let mut pixels: Vec<(u32, u32, T)> = rows.into_iter().flat_map(|pixels| pixels.into_iter()).collect();
+        pixels.sort_by(|a, b| (a.1, a.0).cmp(&(b.1, b.0)));
+        pixels
+    }
+
+    // Cut a full size buffer to the crop window
+    fn crop(&self, buffer: Buffer) -> Buffer {
+        if self.region.crop.is_none() {
+            return buffer;
+        }
+        let (x0, y0, x1, y1) = self.rect();
+        let mut out = Buffer::new(x1 - x0, y1 - y0);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                out.set(x - x0, y - y0, buffer.get(x, y));
+            }
+        }
+        out
+    }
+
+    // Index of every pixel to render, in a full size buffer
+    fn pixels(&self) -> Vec<usize> {
+        let (x0, y0, x1, y1) = self.rect();
+        let mut pixels = vec![];
+        for py in y0..y1 {
+            for px in (x0..x1).filter(|&px| self.in_tiles(px, py)) {
+                pixels.push((py * self.w + px) as usize);
+            }
+        }
+        pixels
+    }
+
+    // Starting image, the previous one at `path` when rendering tiles
+    // It must be the whole picture, not a crop or a picture of another size
+    fn base(&self, path: &Path) -> Buffer {
+        if self.region.tiles.is_some() {
+            if let Some(buffer) = Buffer::load(path) {
+                if buffer.w != self.w || buffer.h != self.h {
+                    panic!("{} is {}x{}, tiles are rendered into the whole {}x{} picture",
+                           path.display(), buffer.w, buffer.h, self.w, self.h);
+                }
+                return buffer;
+            }
+        }
+        Buffer::new(self.w, self.h)
+    }
+
+    /// Picture a scene
+    pub fn shot(&self, eye: &Eye, scene: &Scene, progress: bool) -> Buffer {
+        self.shoot(eye, scene, progress, false).0
+    }
+
+    /// Picture a scene and every pass, the passes are read along the ray through the center of each pixel
+    pub fn shot_with_passes(&self, eye: &Eye, scene: &Scene, progress: bool) -> (Buffer, Vec<(Pass, Buffer)>) {
+        let (image, aovs) = self.shoot(eye, scene, progress, !self.passes.is_empty());
+        (image, self.pass_buffers(aovs))
+    }
 
-        // Create raw buffer of pixels
-        let mut pixels = Vec::with_capacity((self.h * self.w) as usize);
-        let to_u8 = 255. / (self.sample * self.sample) as f64;
-        for py in 0..self.h {
-            for px in 0..self.w {
-                let color = (0..(self.sample * self.sample))
-                    .map(|c| (c / self.sample, c % self.sample))
-                    .map(|(sx, sy)| {
-                        // Compute color
-                        scene.raytrace(make_ray(px, py, sx, sy), 1. /* Air */, self.bounce)
-                    })
-                    .fold(Color::new(0., 0., 0.), |acc, item| acc + item) * to_u8;
-
-                // Push pixel's colors to raw buffer
-                pixels.push(color.r as u8);
-                pixels.push(color.g as u8);
-                pixels.push(color.b as u8);
+    // Picture a scene, with the arbitrary output variables of the rendered pixels when `aovs` is set
+    fn shoot(&self, eye: &Eye, scene: &Scene, progress: bool, aovs: bool) -> (Buffer, Vec<(u32, u32, Option<Aov>)>) {
+        if let Some(ref progressive) = self.progressive {
+            return self.shot_progressive(eye, scene, progressive, progress, aovs);
+        }
+        let mut buffer = self.base(&self.path);
+        let scale = 1. / (self.sample * self.sample) as f64;
+        let center = self.sample / 2;
+        let pixels = self.visit_parallel(progress, |px, py| {
+            let mut color = Color::new(0., 0., 0.);
+            let mut aov = None;
+            for c in 0..(self.sample * self.sample) {
+                let (sx, sy) = (c / self.sample, c % self.sample);
+                let ray = self.make_ray(eye, px, py, sx, sy);
+                if aovs && sx == center && sy == center {
+                    let (sub, hit) = scene.raytrace_aov(ray, self.bounce);
+                    color = color + sub;
+                    aov = Some(hit);
+                } else {
+                    color = color + scene.raytrace(ray, 1. /* Air */, self.bounce);
+                }
+            }
+            (color * scale, aov)
+        });
+        let mut hits = vec![];
+        for (px, py, (color, aov)) in pixels.into_iter() {
+            buffer.set(px, py, color);
+            if let Some(aov) = aov {
+                hits.push((px, py, aov));
+            }
+        }
+        (self.crop(buffer), hits)
+    }
+
+    // Accumulate passes of one jittered ray per pixel, until a budget is spent
+    // The arbitrary output variables come from the first new pass, or from one more pass when the budget was already spent
+    fn shot_progressive(&self, eye: &Eye, scene: &Scene, budget: &Progressive, progress: bool, aovs: bool)
+                        -> (Buffer, Vec<(u32, u32, Option<Aov>)>) {
+        let mut accum = if budget.resume { Accum::load(&budget.accum, self.w, self.h) } else { Accum::new(self.w, self.h) };
+        let base = self.base(&self.path);
+        let pixels = self.pixels();
+        let image = |accum: &Accum| {
+            let mut image = accum.image();
+            if self.region.tiles.is_some() {
+                let mut merged = base.clone();
+                self.visit(false, |px, py| merged.set(px, py, image.get(px, py)));
+                image = merged;
+            }
+            self.crop(image)
+        };
+        let mut hits = None;
+        let start = time::precise_time_s();
+        let mut checkpoint = (start, accum.passes);
+        loop {
+            // Check budget
+            let now = time::precise_time_s();
+            let noise = accum.noise(pixels.as_slice());
+            if (budget.passes > 0 && accum.passes >= budget.passes)
+                || (budget.time > 0. && now - start >= budget.time)
+                || (budget.noise > 0. && noise <= budget.noise)
+                || self.cancelled() {
+                break;
+            }
+
+            // Add a pass
+            let (pass, pass_hits) = self.trace_pass(eye, scene, aovs && hits.is_none());
+            accum.add_pass(&pass);
+            if aovs && hits.is_none() {
+                hits = Some(pass_hits);
             }
 
             // Show progress
+            let now = time::precise_time_s();
             if progress {
-                print!("\r{:03}%", (py + 1) * 100 / self.h);
+                print!("\rpass {}, {:.0}s, noise {:.4}", accum.passes, now - start, accum.noise(pixels.as_slice()));
                 stdio::flush();
             }
-        }
 
-        // Show progress
+            // Write checkpoint
+            if (budget.checkpoint_time > 0. && now - checkpoint.0 >= budget.checkpoint_time)
+                || (budget.checkpoint_passes > 0 && accum.passes - checkpoint.1 >= budget.checkpoint_passes) {
+                self.save(&image(&accum));
+                if budget.resume {
+                    accum.save(&budget.accum);
+                }
+                checkpoint = (now, accum.passes);
+            }
+        }
         if progress {
             println!("");
         }
+        if budget.resume {
+            accum.save(&budget.accum);
+        }
+        if aovs && hits.is_none() {
+            hits = Some(self.trace_pass(eye, scene, true).1);
+        }
+        (image(&accum), hits.unwrap_or(vec![]))
+    }
+
+    // One jittered ray per pixel, full size
+    #[doc(hidden)]
+    pub fn shot_pass(&self, eye: &Eye, scene: &Scene) -> Buffer {
+        self.trace_pass(eye, scene, false).0
+    }
+
+    // One jittered ray per pixel, full size, with the arbitrary output variables along the rays when `aovs` is set
+    fn trace_pass(&self, eye: &Eye, scene: &Scene, aovs: bool) -> (Buffer, Vec<(u32, u32, Option<Aov>)>) {
+        let mut pass = Buffer::new(self.w, self.h);
+        let pixels = self.visit_parallel(false, |px, py| {
+            let ray = self.ray_at(eye, px as f64 + rand::random::<f64>(), py as f64 + rand::random::<f64>());
+            if aovs {
+                let (color, aov) = scene.raytrace_aov(ray, self.bounce);
+                (color, Some(aov))
+            } else {
+                (scene.raytrace(ray, 1. /* Air */, self.bounce), None)
+            }
+        });
+        let mut hits = vec![];
+        for (px, py, (color, aov)) in pixels.into_iter() {
+            pass.set(px, py, color);
+            if let Some(aov) = aov {
+                hits.push((px, py, aov));
+            }
+        }
+        (pass, hits)
+    }
+
+    /// Picture the ambient occlusion of a scene, white where nothing is hit
+    pub fn shot_ao(&self, eye: &Eye, scene: &Scene, progress: bool) -> Buffer {
+        let mut buffer = match self.ao {
+            Some(ref path) => self.base(path),
+            None           => Buffer::new(self.w, self.h),
+        };
+        self.render(eye, progress, &mut buffer, |ray| {
+            let ao = scene.occlusion_along(&ray);
+            Color::new(ao, ao, ao)
+        });
+        self.crop(buffer)
+    }
+
+    // Write the arbitrary output variables of the rendered pixels to one buffer per pass
+    fn pass_buffers(&self, aovs: Vec<(u32, u32, Option<Aov>)>) -> Vec<(Pass, Buffer)> {
+        let mut buffers: Vec<(Pass, Buffer)> = self.passes.iter().map(|&pass| (pass, Buffer::new(self.w, self.h))).collect();
+        let mut materials: Vec<*const Material> = vec![];
+        for &(px, py, ref aov) in aovs.iter() {
+            // Number materials in order of appearance, row by row
+            let mat_id = match *aov {
+                Some(ref aov) => {
+                    let ptr = &*aov.mat as *const Material;
+                    match materials.iter().position(|&mat| mat == ptr) {
+                        Some(i) => i + 1,
+                        None    => {
+                            materials.push(ptr);
+                            materials.len()
+                        },
+                    }
+                },
+                None          => 0,
+            };
+
+            for &mut (pass, ref mut buffer) in buffers.iter_mut() {
+                buffer.set(px, py, pass.value(aov, mat_id as f64));
+            }
+        }
+        buffers.into_iter().map(|(pass, buffer)| (pass, self.crop(buffer))).collect()
+    }
+
+    /// Filter the noise of an image, guided by the albedo and the normals of the scene
+    pub fn denoise(&self, eye: &Eye, scene: &Scene, image: Buffer) -> Buffer {
+        let denoiser = match self.denoiser {
+            Some(denoiser) => denoiser,
+            None           => return image,
+        };
+        let mut albedo = Buffer::new(self.w, self.h);
+        let mut normal = Buffer::new(self.w, self.h);
+        self.visit(false, |px, py| {
+            let (color, n) = scene.guide(&self.make_ray(eye, px, py, self.sample / 2, self.sample / 2));
+            albedo.set(px, py, color);
+            normal.set(px, py, Color::new(n.x, n.y, n.z));
+        });
+        denoiser.apply(&image, &self.crop(albedo), &self.crop(normal))
+    }
+
+    // Render the pixels of the region into `buffer`, averaging the subpixels
+    fn render<F: Fn(Ray) -> Color + Sync>(&self, eye: &Eye, progress: bool, buffer: &mut Buffer, trace: F) {
+        let scale = 1. / (self.sample * self.sample) as f64;
+        let pixels = self.visit_parallel(progress, |px, py| {
+            (0..(self.sample * self.sample))
+                .map(|c| (c / self.sample, c % self.sample))
+                .map(|(sx, sy)| {
+                    // Compute color
+                    trace(self.make_ray(eye, px, py, sx, sy))
+                })
+                .fold(Color::new(0., 0., 0.), |acc, item| acc + item) * scale
+        });
+        for &(px, py, color) in pixels.iter() {
+            buffer.set(px, py, color);
+        }
+    }
+
+    /// Follow the progress of the renders, and allow to cancel them
+    pub fn watch(&mut self, status: Arc<Status>) {
+        self.status = Some(status);
+    }
+
+    fn cancelled(&self) -> bool {
+        self.status.as_ref().map_or(false, |status| status.cancelled())
+    }
+
+    // Show progress after `row` of `rows`
+    fn progress(&self, progress: bool, row: u32, rows: u32) {
+        if let Some(ref status) = self.status {
+            status.progress.store((row as f64 / rows as f64 * 1000.) as usize, Ordering::SeqCst);
+        }
+        if progress {
+            print!("\r{:03}%", row * 100 / rows);
+            stdio::flush();
+        }
+    }
 
-        // Create image from raw buffer
-        let img_buf = ImageBuffer::from_raw(self.w, self.h, pixels).unwrap();
-        ImageRgb8(img_buf)
+    /// Write image to file
+    pub fn save(&self, buffer: &Buffer) {
+        Picture::save_to(&self.path, &buffer.to_image());
     }
 
-    // Write image to file
-    pub fn save(&self, img: &DynamicImage) {
-        let mut out = File::create(&self.path).unwrap();
-        let _ = img.save(&mut out, PNG);
+    /// Write ambient occlusion pass to file
+    pub fn save_ao(&self, buffer: &Buffer) {
+        if let Some(ref path) = self.ao {
+            Picture::save_to(path, &buffer.to_image());
+        }
+    }
+
+    /// Write passes next to the image, "image-depth.png", or all in "image.exr" with the image itself
+    pub fn save_passes(&self, image: &Buffer, passes: &Vec<(Pass, Buffer)>) {
+        if self.exr {
+            let mut channels = vec![];
+            for (i, name) in ["R", "G", "B"].iter().enumerate() {
+                channels.push((name.to_string(), image.channel(i)));
+            }
+            for &(pass, ref buffer) in passes.iter() {
+                for (i, name) in pass.channels().iter().enumerate() {
+                    channels.push((format!("{}.{}", pass.name(), name), buffer.channel(i)));
+                }
+            }
+            exr::save(&self.path.with_extension("exr"), image.w as usize, image.h as usize, channels.as_slice());
+            return;
+        }
+
+        for &(pass, ref buffer) in passes.iter() {
+            let max_depth = buffer.pixels.iter()
+                .filter(|color| color.r.is_finite())
+                .fold(0., |acc: f64, color| acc.max(color.r));
+            let mut display = buffer.clone();
+            for color in display.pixels.iter_mut() {
+                *color = pass.display(*color, max_depth);
+            }
+            let name = format!("{}-{}.png", self.path.filestem_str().unwrap(), pass.name());
+            Picture::save_to(&self.path.with_filename(name), &display.to_image());
+        }
+    }
+
+    // Checkpoints overwrite the image, it is replaced whole
+    fn save_to(path: &Path, img: &DynamicImage) {
+        progressive::replace(path, |out| {
+            let _ = img.save(out, PNG);
+        });
     }
 }
 
+/// Point of view at `pos`, turned by the angles of `dir`, with a field of view of `fov` radians
 pub struct Eye {
     pos: Vec3,
     dir: Vec3,
@@ -95,54 +564,228 @@ impl Eye {
     pub fn new(pos: Vec3, dir: Vec3, fov: f64) -> Eye {
         Eye { pos: pos, dir: dir, fov: fov }
     }
+
+    /// Move along the axes of the view
+    pub fn walk(&mut self, step: Vec3) {
+        self.pos = self.pos + rotate(step, self.dir);
+    }
+
+    /// Turn around the axes, in radians
+    pub fn turn(&mut self, angles: Vec3) {
+        self.dir = self.dir + angles;
+    }
+
+    #[doc(hidden)]
+    pub fn to_json(&self) -> Json {
+        obj(vec![("pos", vec3(self.pos)), ("dir", vec3(self.dir)), ("fov", num(self.fov))])
+    }
 }
 
+/// Ambient occlusion, sampled over the hemisphere around the normal
+#[derive(Copy)]
+pub struct Occlusion {
+    radius:  f64, // Only objects closer than this occlude
+    samples: u32,
+}
+
+impl Occlusion {
+    /// Objects closer than `radius` occlude, seen with `samples` rays
+    pub fn new(radius: f64, samples: u32) -> Occlusion {
+        Occlusion { radius: radius, samples: samples }
+    }
+}
+
+/// Objects, lights and environment, made with `SceneBuilder`
 pub struct Scene<'a> {
-    objects: Objects<'a>,
-    lights:  Lights<'a>,
-    ambient: f64,
-    back:    Color,
+    objects:   Objects<'a>,
+    lights:    Lights<'a>,
+    media:     Media,
+    ambient:   f64,               // Ambient light
+    occlusion: Option<Occlusion>, // Darkens the ambient light
+    sky:       Box<Sky + 'a>,
+    ibl:       u32,               // Number of samples of the sky for image based lighting
 }
 
 impl<'a> Scene<'a> {
-    pub fn new(objects: Objects<'a>, lights: Lights<'a>, ambient: f64, back: Color) -> Scene<'a> {
-        Scene { objects: objects, lights: lights, ambient: ambient, back: back }
+    #[doc(hidden)]
+    pub fn new(objects: Objects<'a>, lights: Lights<'a>, media: Media, ambient: f64, occlusion: Option<Occlusion>,
+               sky: Box<Sky + 'a>, ibl: u32) -> Scene<'a> {
+        Scene {
+            objects: objects,
+            lights: lights,
+            media: media,
+            ambient: ambient,
+            occlusion: occlusion,
+            sky: sky,
+            ibl: ibl,
+        }
     }
 
-    #[allow(dead_code)]
+    #[doc(hidden)]
+    pub fn lights(&self) -> &Lights<'a> {
+        &self.lights
+    }
+
+    // Geometries of the instances are gathered in `export`
+    #[doc(hidden)]
+    pub fn to_json(&self, export: &mut Export) -> Json {
+        let mut fields = vec![
+            ("objects", self.objects.to_json(export)),
+            ("lights", self.lights.to_json()),
+            ("ambient", num(self.ambient)),
+        ];
+        if let Some(occlusion) = self.occlusion {
+            fields.push(("occlusion", obj(vec![
+                ("radius", num(occlusion.radius)),
+                ("samples", int(occlusion.samples as u64)),
+            ])));
+        }
+        let sky = self.sky.to_json();
+        if !sky.is_null() {
+            fields.push(("sky", sky));
+        }
+        fields.push(("ibl", int(self.ibl as u64)));
+        fields.push_all(self.media.to_json().as_slice());
+        obj(fields)
+    }
+
+    /// Add an object to a built scene
     pub fn add_object(&mut self, object: Box<Object + 'a>) {
         self.objects.add(object);
     }
 
-    #[allow(dead_code)]
+    /// Add a light to a built scene
     pub fn add_light(&mut self, light: Box<Light + 'a>) {
         self.lights.add(light);
     }
 
+    /// Color seen along a ray, in a medium of refractive index `refr_idx`,
+    /// following at most `count` reflections and refractions
     pub fn raytrace(&self, ray: Ray, refr_idx: f64, count: u32) -> Color {
         // Compute intersection
         let inter = self.objects.intersect(&ray);
-        if inter.is_none() {
-            return self.back;
+        let color = match inter {
+            Some(ref inter) => self.shade(&ray, inter, refr_idx, count),
+            None            => self.sky.color(ray.dir),
+        };
+        let dist = inter.map_or(INFINITY, |inter| inter.dist);
+        self.through_media(&ray, dist, color)
+    }
+
+    // Same as `raytrace` from the air, with the arbitrary output variables at the first intersection
+    #[doc(hidden)]
+    pub fn raytrace_aov(&self, ray: Ray, count: u32) -> (Color, Option<Aov>) {
+        let hit = self.objects.intersect_index(&ray);
+        let dist = hit.as_ref().map_or(INFINITY, |&(_, ref inter)| inter.dist);
+        let (color, aov) = match hit {
+            Some((i, inter)) => {
+                let (direct, indirect) = self.shade_parts(&ray, &inter, 1. /* Air */, count);
+                let aov = Aov {
+                    depth: inter.dist,
+                    normal: inter.normal,
+                    albedo: inter.mat.color,
+                    object: i + 1,
+                    mat: inter.mat.clone(),
+                    direct: direct,
+                    indirect: indirect,
+                    shadow: 1. - self.lights.visibility(inter.pos, self),
+                };
+                ((direct + indirect).normalize(), Some(aov))
+            },
+            None             => (self.sky.color(ray.dir), None),
+        };
+        (self.through_media(&ray, dist, color), aov)
+    }
+
+    // Compute participating media, between the origin of a ray and what it hits at `dist`
+    fn through_media(&self, ray: &Ray, dist: f64, color: Color) -> Color {
+        if self.media.is_empty() {
+            return color;
         }
+        self.media.integrate(ray, dist, color, self).normalize()
+    }
+
+    fn shade(&self, ray: &Ray, inter: &Inter, refr_idx: f64, count: u32) -> Color {
+        let (direct, indirect) = self.shade_parts(ray, inter, refr_idx, count);
+        (direct + indirect).normalize()
+    }
 
+    // Light coming straight from the lights, and everything else
+    fn shade_parts(&self, ray: &Ray, inter: &Inter, refr_idx: f64, count: u32) -> (Color, Color) {
         // Compute lighting
-        let mat = &inter.as_ref().unwrap().mat;
-        let mut color = mat.color;
-        let (spec, diff) = self.lights.bright(&ray, inter.as_ref().unwrap(), self);
-        color = color * diff * mat.diff + Color::new(1., 1., 1.) * spec * mat.spec;
+        let mat = &inter.mat;
+        let (spec, diff) = self.lights.bright(ray, inter, self);
+        let direct = mat.color * diff * mat.diff + Color::new(1., 1., 1.) * spec * mat.spec;
+
+        // Compute ambient light
+        let ambient = if self.ambient != 0. { self.ambient * self.occlusion(inter) } else { 0. };
+        let mut indirect = mat.color * ambient;
+
+        // Compute image based lighting
+        if self.ibl > 0 && mat.diff != 0. {
+            indirect = indirect + mat.color * self.sky_light(inter) * mat.diff;
+        }
 
         // Compute refraction
         if mat.refr != 0. && count > 0 {
-            color = color + self.refraction(ray.dir, refr_idx, inter.as_ref().unwrap(), count);
+            indirect = indirect + self.refraction(ray.dir, refr_idx, inter, count);
         }
 
         // Compute reflection
         if mat.refl != 0. && count > 0 {
-            color = color + self.reflection(ray.dir, refr_idx, inter.as_ref().unwrap(), count);
+            indirect = indirect + self.reflection(ray.dir, refr_idx, inter, count);
         }
 
-        color.normalize()
+        (direct, indirect)
+    }
+
+    // Light received from the unoccluded sky, cosine weighted
+    fn sky_light(&self, inter: &Inter) -> Color {
+        let mut light = Color::new(0., 0., 0.);
+        for _ in 0..self.ibl {
+            let dir = cosine_sample(inter.normal, rand::random::<f64>(), rand::random::<f64>());
+            let ray = Ray::new(inter.pos + dir * 0.00001, dir);
+            if self.objects.intersect(&ray).is_none() {
+                light = light + self.sky.color(dir);
+            }
+        }
+        light * (1. / self.ibl as f64)
+    }
+
+    // Fraction of the hemisphere left open around the intersection, 1 without occlusion
+    fn occlusion(&self, inter: &Inter) -> f64 {
+        let occlusion = match self.occlusion {
+            Some(occlusion) if occlusion.samples > 0 => occlusion,
+            _                                        => return 1.,
+        };
+        let mut open = 0;
+        for _ in 0..occlusion.samples {
+            let dir = cosine_sample(inter.normal, rand::random::<f64>(), rand::random::<f64>());
+            let ray = Ray::new(inter.pos + dir * 0.00001, dir);
+            match self.objects.intersect(&ray) {
+                Some(ref hit) if hit.dist < occlusion.radius => {},
+                _                                            => open += 1,
+            }
+        }
+        open as f64 / occlusion.samples as f64
+    }
+
+    // Albedo and normal seen along a ray, the sky faces the eye
+    #[doc(hidden)]
+    pub fn guide(&self, ray: &Ray) -> (Color, Vec3) {
+        match self.objects.intersect(ray) {
+            Some(ref inter) => (inter.mat.color, inter.normal),
+            None            => (self.sky.color(ray.dir), ray.dir * -1.),
+        }
+    }
+
+    // Ambient occlusion at the first intersection of a ray
+    #[doc(hidden)]
+    pub fn occlusion_along(&self, ray: &Ray) -> f64 {
+        match self.objects.intersect(ray) {
+            Some(ref inter) => self.occlusion(inter),
+            None            => 1.,
+        }
     }
 
     fn refraction(&self, ray_dir: Vec3, refr_idx: f64, inter: &Inter, count: u32) -> Color {
@@ -163,15 +806,20 @@ impl<'a> Scene<'a> {
         self.raytrace(ray, refr_idx, count - 1) * inter.mat.refl
     }
 
+    /// Visibility of a light, 1 when lit and 0 in its shadow
     pub fn shadow(&self, from: Vec3, to: Vec3) -> f64 {
+        if self.visible(from, to) { 1. } else { 0. }
+    }
+
+    /// Check that nothing stands between two points
+    pub fn visible(&self, from: Vec3, to: Vec3) -> bool {
         let dir = (to - from).normalize();
         let ray = Ray::new(from + dir * 0.00001, dir);
 
         // Compute intersection
-        let inter = self.objects.intersect(&ray);
-        if inter.is_none() || (to - inter.unwrap().pos).x * dir.x < 0. {
-            return 1.;
+        match self.objects.intersect(&ray) {
+            Some(inter) => inter.dist > (to - from).length(),
+            None        => true,
         }
-        self.ambient
     }
 }
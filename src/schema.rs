@@ -0,0 +1,486 @@
+// Versioned scene format, checked by `rt validate`, documented in doc/scene.md
+// Files without "version" are version 1, they are migrated when loaded
+
+use std::fmt;
+use std::collections::BTreeMap;
+use serialize::json::Json;
+use aov::Pass;
+
+pub const VERSION: u64 = 2;
+
+static DIRS:    [&'static str; 6] = ["left", "right", "top", "bottom", "front", "back"];
+static FORMATS: [&'static str; 2] = ["png", "exr"];
+static FACES:   [&'static str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+// Expected value of a field
+#[derive(Copy)]
+enum Kind {
+    Number,
+    Positive,               // Number greater than 0
+    Integer,
+    Bool,
+    Str,
+    OneOf(&'static [&'static str]),
+    Tile,                   // [x, y]
+    Numbers,
+    Tiles,
+    Passes,
+    Struct(&'static str),   // Object with the fields of a schema
+    Structs(&'static str),
+    BoolOr(&'static str),   // Boolean, or a struct
+    NumberOr(&'static str), // Number, or a struct
+    StrOr(&'static str),    // String, or a struct
+    Triple(&'static str),   // Struct of three numbers, or [a, b, c]
+    Triples(&'static str),
+    Variant(&'static str),  // Single key object, the key is a type of the group
+    Variants(&'static str),
+    Named(&'static str),    // Variants by name
+}
+
+struct Field {
+    key:      &'static str,
+    kind:     Kind,
+    required: bool,
+}
+
+fn req(key: &'static str, kind: Kind) -> Field {
+    Field { key: key, kind: kind, required: true }
+}
+
+fn opt(key: &'static str, kind: Kind) -> Field {
+    Field { key: key, kind: kind, required: false }
+}
+
+// Fields of every struct, variants are named "<group>.<type>"
+fn schema(name: &str) -> Option<Vec<Field>> {
+    use self::Kind::{ Number, Positive, Integer, Bool, Str, OneOf, Numbers, Tiles, Passes };
+    use self::Kind::{ Struct, Structs, BoolOr, NumberOr, StrOr, Triple, Triples, Variant, Variants, Named };
+
+    let vec3 = Triple("vec3");
+    let color = Triple("color");
+    let mat = req("mat", Struct("mat"));
+    let fields = match name {
+        "root"             => vec![opt("version", Integer), req("picture", Struct("picture")), opt("eye", Struct("eye")),
+                                   req("scene", Struct("scene"))],
+        "vec3"             => vec![req("x", Number), req("y", Number), req("z", Number)],
+        "color"            => vec![req("r", Number), req("g", Number), req("b", Number)],
+
+        "picture"          => vec![req("w", Integer), req("h", Integer), req("path", Str), opt("scale", Number),
+                                   opt("ao", Str), opt("passes", Passes), opt("passes-format", OneOf(&FORMATS)),
+                                   opt("denoise", BoolOr("denoise")), opt("progressive", Struct("progressive")),
+                                   opt("crop", Struct("crop")), opt("tiles", Struct("tiles")), opt("threads", Integer),
+                                   opt("bounce", Integer), opt("sample", Integer)],
+        "denoise"          => vec![opt("radius", Integer), opt("sigma-space", Positive), opt("sigma-color", Positive),
+                                   opt("sigma-normal", Positive), opt("sigma-albedo", Positive)],
+        "progressive"      => vec![opt("passes", Integer), opt("time", Number), opt("noise", Number),
+                                   opt("checkpoint-time", Number), opt("checkpoint-passes", Integer), opt("accum", Str),
+                                   opt("resume", Bool)],
+        "crop"             => vec![req("x", Number), req("y", Number), req("w", Number), req("h", Number),
+                                   opt("normalized", Bool)],
+        "tiles"            => vec![req("size", Integer), req("list", Tiles)],
+
+        "eye"              => vec![req("pos", vec3), req("dir", vec3), req("fov", Number)],
+
+        "scene"            => vec![opt("objects", Variants("object")), opt("lights", Variants("light")),
+                                   opt("gltf", StrOr("gltf")), opt("geometries", Named("object")), opt("fog", Struct("fog")),
+                                   opt("volumes", Structs("volume")), opt("march-step", Number), opt("ambient", Number),
+                                   opt("occlusion", Struct("occlusion")), opt("sky", Variant("sky")), opt("ibl", Integer)],
+        "gltf"             => vec![req("path", Str), opt("scene", Integer), opt("camera", Integer),
+                                   opt("intensity", Number)],
+        "occlusion"        => vec![opt("radius", Number), opt("samples", Integer)],
+        "fog"              => vec![opt("absorb", Number), opt("scatter", Number), opt("color", color), opt("g", Number),
+                                   opt("dist", Number)],
+        "volume"           => vec![req("pos", vec3), req("dim", vec3), req("density", NumberOr("density")),
+                                   opt("absorb", Number), opt("scatter", Number), opt("color", color), opt("g", Number)],
+        "density"          => vec![opt("noise", Struct("noise")), opt("grid", StrOr("grid"))],
+        "grid"             => vec![req("nx", Integer), req("ny", Integer), req("nz", Integer), req("values", Numbers)],
+        "noise"            => vec![opt("seed", Integer), opt("scale", Number), opt("octaves", Integer), opt("max", Number)],
+        "mat"              => vec![req("color", color), opt("spec", Number), opt("diff", Number), opt("refr", Number),
+                                   opt("refr-idx", Number), opt("refl", Number)],
+
+        "sky.uniform"      => vec![opt("color", color)],
+        "sky.equirect"     => vec![req("path", Str), opt("intensity", Number), opt("rotate", Number)],
+        "sky.cubemap"      => {
+            let mut fields: Vec<Field> = FACES.iter().map(|face| req(*face, Str)).collect();
+            fields.push(opt("intensity", Number));
+            fields
+        },
+        "sky.preetham"     => vec![opt("dir", vec3), opt("turbidity", Number), opt("intensity", Number)],
+
+        "light.bulb"       => vec![req("pos", vec3), req("spec", Number), req("shin", Integer), req("diff", Number)],
+        "light.sun"        => vec![req("dir", vec3), req("spec", Number), req("shin", Integer), req("diff", Number)],
+        "light.spot"       => vec![req("pos", vec3), req("dir", vec3), opt("angle", Number), opt("falloff", Number),
+                                   opt("ies", Str), req("spec", Number), req("shin", Integer), req("diff", Number)],
+
+        "object.instance"  => vec![req("geometry", Str), opt("pos", vec3), opt("dir", vec3), opt("scale", Positive),
+                                   opt("mat", Struct("mat"))],
+        "object.gltf"      => vec![req("path", Str), opt("scene", Integer), opt("pos", vec3), opt("dir", vec3),
+                                   opt("scale", Number)],
+        "object.rotate"    => vec![req("pos", vec3), req("dir", vec3), req("object", Variant("object"))],
+        "object.sphere"    => vec![req("pos", vec3), req("radius", Number), mat],
+        "object.plane"     => vec![req("pos", vec3), req("normal", vec3), mat],
+        "object.aarect"    => vec![req("pos", vec3), req("dir", OneOf(&DIRS)), req("dim", vec3), mat],
+        "object.aabox"     => vec![req("pos", vec3), req("dim", vec3), mat, req("skybox", Bool)],
+        "object.aahexa"    => vec![req("pos", vec3), req("x", Number), req("y", Number), mat],
+        "object.cylinder"  |
+        "object.cone"      => vec![req("pos", vec3), req("axis", vec3), req("radius", Number), req("height", Number),
+                                   opt("capped", Bool), mat],
+        "object.torus"     => vec![req("pos", vec3), req("axis", vec3), req("major", Number), req("minor", Number), mat],
+        "object.disc"      => vec![req("pos", vec3), req("normal", vec3), req("radius", Number), mat],
+        "object.capsule"   => vec![req("a", vec3), req("b", vec3), req("radius", Number), mat],
+        "object.ellipsoid" => vec![req("pos", vec3), req("radii", vec3), opt("dir", vec3), mat],
+        "object.box"       => vec![req("pos", vec3), req("u", vec3), req("v", vec3), req("w", vec3), mat],
+        "object.quad"      => vec![req("pos", vec3), req("u", vec3), req("v", vec3), mat],
+        "object.polygon"   => vec![req("points", Triples("vec3")), mat],
+        "object.heightfield" => vec![req("pos", vec3), req("size", vec3), opt("image", Str), opt("grid", Str),
+                                     opt("nx", Integer), opt("nz", Integer), opt("heights", Numbers), mat],
+        "object.hexgrid"   => vec![req("pos", vec3), req("size", Number), opt("image", Str), opt("height", Number),
+                                   opt("colored", Bool), opt("cols", Integer), opt("rows", Integer),
+                                   opt("heights", Numbers), opt("colors", Triples("color")), mat],
+        "object.sdf"       => vec![req("shape", Variant("sdf")), mat, opt("steps", Integer), opt("eps", Number),
+                                   opt("max-dist", Number), opt("step", Number)],
+        "object.metaballs" => vec![req("balls", Structs("ball")), opt("threshold", Number), mat],
+        "ball"             => vec![req("pos", vec3), req("radius", Positive), opt("strength", Number)],
+
+        "sdf.sphere"       => vec![req("pos", vec3), req("radius", Number)],
+        "sdf.box"          => vec![req("pos", vec3), req("dim", vec3)],
+        "sdf.torus"        => vec![req("pos", vec3), req("major", Number), req("minor", Number)],
+        "sdf.cylinder"     => vec![req("pos", vec3), req("radius", Number), req("height", Number)],
+        "sdf.plane"        => vec![req("pos", vec3), req("normal", vec3)],
+        "sdf.union"        => vec![req("shapes", Variants("sdf")), opt("k", Number)],
+        "sdf.intersection" => vec![req("shapes", Variants("sdf"))],
+        "sdf.subtraction"  => vec![req("from", Variant("sdf")), req("shape", Variant("sdf")), opt("k", Number)],
+        "sdf.translate"    => vec![req("pos", vec3), req("shape", Variant("sdf"))],
+        "sdf.repeat"       => vec![req("period", vec3), req("shape", Variant("sdf"))],
+        "sdf.twist"        |
+        "sdf.bend"         => vec![req("k", Number), req("shape", Variant("sdf"))],
+        "sdf.mandelbulb"   => vec![req("pos", vec3), opt("scale", Number), opt("power", Number), opt("iter", Integer)],
+        "sdf.menger"       => vec![req("pos", vec3), req("size", Number), opt("iter", Integer)],
+        _                  => return None,
+    };
+    Some(fields)
+}
+
+// Groups of fields standing for each other, exactly one of them is given, whole
+fn alternatives(name: &str) -> &'static [&'static [&'static str]] {
+    static HEIGHTFIELD: &'static [&'static [&'static str]] = &[&["image"], &["grid"], &["nx", "nz", "heights"]];
+    static HEXGRID:     &'static [&'static [&'static str]] = &[&["image", "height"], &["cols", "rows", "heights"]];
+    static DENSITY:     &'static [&'static [&'static str]] = &[&["noise"], &["grid"]];
+    match name {
+        "object.heightfield" => HEIGHTFIELD,
+        "object.hexgrid"     => HEXGRID,
+        "density"            => DENSITY,
+        _                    => &[],
+    }
+}
+
+// Something wrong in a scene, at a dotted path like "scene.objects[2].sphere"
+pub struct Problem {
+    pub error:   bool, // A warning otherwise, the scene still loads
+    pub path:    String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}: {}", if self.error { "error" } else { "warning" }, self.path, self.message)
+    }
+}
+
+// Every problem of a scene at once, migrate it first
+pub fn check(root: &Json) -> Vec<Problem> {
+    let mut checker = Checker { problems: vec![] };
+    if let Some(version) = root.find("version").and_then(|version| version.as_u64()) {
+        if version > VERSION {
+            checker.add(true, "version", format!("{} is newer than {}, the latest supported version", version, VERSION));
+        }
+    }
+    checker.value("", root, Kind::Struct("root"));
+    // Without eye, the camera comes from the glTF file of the scene
+    if root.find("eye").is_none() && root.find_path(&["scene", "gltf"]).is_none() {
+        checker.add(true, "", "missing \"eye\", needed without \"gltf\" in the scene".to_string());
+    }
+    checker.problems
+}
+
+struct Checker {
+    problems: Vec<Problem>,
+}
+
+impl Checker {
+    fn add(&mut self, error: bool, path: &str, message: String) {
+        let path = if path.is_empty() { "root" } else { path };
+        self.problems.push(Problem { error: error, path: path.to_string(), message: message });
+    }
+
+    fn expected(&mut self, path: &str, what: &str) {
+        self.add(true, path, format!("expected {}", what));
+    }
+
+    fn value(&mut self, path: &str, value: &Json, kind: Kind) {
+        match kind {
+            Kind::Number          => if !value.is_number() { self.expected(path, "a number") },
+            Kind::Positive        => if !value.as_f64().map_or(false, |n| n > 0.) {
+                self.expected(path, "a number greater than 0")
+            },
+            Kind::Integer         => if value.as_i64().is_none() { self.expected(path, "an integer") },
+            Kind::Bool            => if !value.is_boolean() { self.expected(path, "true or false") },
+            Kind::Str             => if !value.is_string() { self.expected(path, "a string") },
+            Kind::OneOf(names)    => match value.as_string() {
+                Some(name) if names.iter().any(|known| *known == name) => {},
+                _                                                      => {
+                    self.expected(path, format!("one of {}", names.connect(", ")).as_slice())
+                },
+            },
+            Kind::Tile            => match value.as_array() {
+                Some(tile) if tile.len() == 2 && tile.iter().all(|i| i.as_u64().is_some()) => {},
+                _                                                                          => {
+                    self.expected(path, "a tile, [x, y]")
+                },
+            },
+            Kind::Numbers         => self.array(path, value, Kind::Number),
+            Kind::Tiles           => self.array(path, value, Kind::Tile),
+            Kind::Passes          => self.array(path, value, Kind::OneOf(Pass::names())),
+            Kind::Struct(name)    => self.fields(path, value, name),
+            Kind::Structs(name)   => self.array(path, value, Kind::Struct(name)),
+            Kind::BoolOr(name)    => if !value.is_boolean() { self.fields(path, value, name) },
+            Kind::NumberOr(name)  => if !value.is_number() { self.fields(path, value, name) },
+            Kind::StrOr(name)     => if !value.is_string() { self.fields(path, value, name) },
+            Kind::Triple(name)    => match value.as_array() {
+                Some(array) if array.len() == 3 && array.iter().all(|n| n.is_number()) => {},
+                Some(_)                                                                => {
+                    self.expected(path, "an array of 3 numbers")
+                },
+                None                                                                   => self.fields(path, value, name),
+            },
+            Kind::Triples(name)   => self.array(path, value, Kind::Triple(name)),
+            Kind::Variant(group)  => self.variant(path, value, group),
+            Kind::Variants(group) => self.array(path, value, Kind::Variant(group)),
+            Kind::Named(group)    => match value.as_object() {
+                Some(obj) => for (name, item) in obj.iter() {
+                    self.variant(join(path, name.as_slice()).as_slice(), item, group);
+                },
+                None      => self.expected(path, "an object of names"),
+            },
+        }
+    }
+
+    fn array(&mut self, path: &str, value: &Json, kind: Kind) {
+        match value.as_array() {
+            Some(array) => for (i, item) in array.iter().enumerate() {
+                self.value(format!("{}[{}]", path, i).as_slice(), item, kind);
+            },
+            None        => self.expected(path, "an array"),
+        }
+    }
+
+    // Missing fields are errors, unknown ones only warnings as the loader ignores them
+    fn fields(&mut self, path: &str, value: &Json, name: &str) {
+        let obj = match value.as_object() {
+            Some(obj) => obj,
+            None      => return self.expected(path, "an object"),
+        };
+        let fields = schema(name).unwrap();
+        for field in fields.iter() {
+            match obj.get(field.key) {
+                Some(item)             => self.value(join(path, field.key).as_slice(), item, field.kind),
+                None if field.required => self.add(true, path, format!("missing \"{}\"", field.key)),
+                None                   => {},
+            }
+        }
+        for key in obj.keys() {
+            if !fields.iter().any(|field| field.key == key.as_slice()) {
+                self.add(false, join(path, key.as_slice()).as_slice(), "unknown key, ignored".to_string());
+            }
+        }
+        self.alternatives(path, obj, name);
+    }
+
+    fn alternatives(&mut self, path: &str, obj: &BTreeMap<String, Json>, name: &str) {
+        let groups = alternatives(name);
+        if groups.is_empty() {
+            return;
+        }
+        let given: Vec<&[&str]> = groups.iter().map(|group| *group)
+            .filter(|group| group.iter().any(|key| obj.contains_key(*key)))
+            .collect();
+        let names: Vec<String> = groups.iter().map(|group| {
+            let keys: Vec<String> = group.iter().map(|key| format!("\"{}\"", key)).collect();
+            keys.connect(" with ")
+        }).collect();
+        match given.len() {
+            0 => self.add(true, path, format!("expected {}", names.connect(" or "))),
+            1 => for key in given[0].iter().filter(|key| !obj.contains_key(**key)) {
+                self.add(true, path, format!("missing \"{}\", needed with \"{}\"", key, given[0].connect("\", \"")));
+            },
+            _ => self.add(true, path, format!("expected only one of {}", names.connect(" or "))),
+        }
+    }
+
+    // The type is the only key, like { "sphere": { ... } }
+    fn variant(&mut self, path: &str, value: &Json, group: &str) {
+        let (key, inner) = match value.as_object() {
+            Some(obj) if obj.len() == 1 => obj.iter().next().unwrap(),
+            Some(obj) if obj.len() > 1  => {
+                let keys: Vec<&str> = obj.keys().map(|key| key.as_slice()).collect();
+                return self.add(true, path, format!("expected a single {} type, got {}", group, keys.connect(", ")));
+            },
+            _                           => return self.expected(path, format!("a {}, like {{ \"type\": {{ ... }} }}", group).as_slice()),
+        };
+        let name = format!("{}.{}", group, key);
+        let path = join(path, key.as_slice());
+        if schema(name.as_slice()).is_some() {
+            self.fields(path.as_slice(), inner, name.as_slice());
+        } else {
+            self.add(true, path.as_slice(), format!("unknown {} type", group));
+        }
+    }
+}
+
+pub fn join(path: &str, key: &str) -> String {
+    if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) }
+}
+
+// Upgrade a scene to the latest version, returns what changed
+// A version that is not an integer is left for `check` to report
+pub fn migrate(root: &mut Json) -> Vec<String> {
+    let mut notes = vec![];
+    let version = match root.find("version") {
+        Some(version) => match version.as_u64() {
+            Some(version) => version,
+            None          => return notes,
+        },
+        None          => 1,
+    };
+    if version >= VERSION {
+        return notes;
+    }
+    if version < 2 {
+        migrate_1(root, &mut notes);
+    }
+    if let Json::Object(ref mut obj) = *root {
+        obj.insert("version".to_string(), Json::U64(VERSION));
+    }
+    notes.push(format!("migrated from version {} to {}", version, VERSION));
+    notes
+}
+
+// 1 to 2: the background color "back" becomes a uniform sky
+// Ambient light defaulted to 0.2, it is none by default now
+fn migrate_1(root: &mut Json, notes: &mut Vec<String>) {
+    let scene = match *root {
+        Json::Object(ref mut obj) => match obj.get_mut("scene") {
+            Some(&mut Json::Object(ref mut scene)) => scene,
+            _                                      => return,
+        },
+        _                         => return,
+    };
+    if !scene.contains_key("ambient") {
+        scene.insert("ambient".to_string(), Json::F64(0.2));
+        notes.push("scene.ambient set to 0.2, the default of version 1".to_string());
+    }
+    let back = match scene.remove("back") {
+        Some(back) => back,
+        None       => return,
+    };
+    if scene.contains_key("sky") {
+        notes.push("scene.back removed, scene.sky was used instead".to_string());
+        return;
+    }
+    let mut uniform = BTreeMap::new();
+    uniform.insert("color".to_string(), back);
+    let mut sky = BTreeMap::new();
+    sky.insert("uniform".to_string(), Json::Object(uniform));
+    scene.insert("sky".to_string(), Json::Object(sky));
+    notes.push("scene.back moved to scene.sky.uniform.color".to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use serialize::json::Json;
+    use super::{ check, migrate, VERSION };
+
+    // Problems of a scene with the given "scene" object, printed
+    fn problems(scene: &str) -> Vec<String> {
+        let root = format!(r#"{{
+            "picture": {{ "w": 10, "h": 10, "path": "image.png" }},
+            "eye": {{ "pos": [0, 0, 0], "dir": [0, 0, 1], "fov": 1 }},
+            "scene": {}
+        }}"#, scene);
+        check(&Json::from_str(root.as_slice()).unwrap()).iter().map(|problem| problem.to_string()).collect()
+    }
+
+    #[test]
+    fn valid() {
+        assert!(problems("{}").is_empty());
+        assert!(problems(r#"{ "objects": [{ "sphere": { "pos": [0, 0, 0], "radius": 1, "mat": { "color": [1, 1, 1] } } }] }"#).is_empty());
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(problems(r#"{ "ambient": "a lot", "colour": 1 }"#), vec![
+            "error: scene.ambient: expected a number".to_string(),
+            "warning: scene.colour: unknown key, ignored".to_string(),
+        ]);
+        assert_eq!(problems(r#"{ "objects": [{ "sphere": { "pos": [0, 0], "mat": { "color": [1, 1, 1] } } }] }"#), vec![
+            "error: scene.objects[0].sphere.pos: expected an array of 3 numbers".to_string(),
+            "error: scene.objects[0].sphere: missing \"radius\"".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn positive() {
+        assert_eq!(problems(r#"{ "objects": [{ "metaballs": { "balls": [{ "pos": [0, 0, 0], "radius": 0 }], "mat": { "color": [1, 1, 1] } } }] }"#), vec![
+            "error: scene.objects[0].metaballs.balls[0].radius: expected a number greater than 0".to_string(),
+        ]);
+        assert_eq!(problems(r#"{ "objects": [{ "instance": { "geometry": "ball", "scale": -1 } }] }"#), vec![
+            "error: scene.objects[0].instance.scale: expected a number greater than 0".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn variants() {
+        assert_eq!(problems(r#"{ "objects": [{ "ball": {} }, { "sphere": {}, "plane": {} }] }"#), vec![
+            "error: scene.objects[0].ball: unknown object type".to_string(),
+            "error: scene.objects[1]: expected a single object type, got plane, sphere".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn alternatives() {
+        let field = |fields: &str| problems(format!(
+            r#"{{ "objects": [{{ "heightfield": {{ "pos": [0, 0, 0], "size": [1, 1, 1], "mat": {{ "color": [1, 1, 1] }}{} }} }}] }}"#,
+            fields).as_slice());
+        assert!(field(r#", "image": "a.png""#).is_empty());
+        assert!(field(r#", "nx": 2, "nz": 2, "heights": [0, 0, 0, 0]"#).is_empty());
+        assert_eq!(field(""), vec![
+            "error: scene.objects[0].heightfield: expected \"image\" or \"grid\" or \"nx\" with \"nz\" with \"heights\"".to_string(),
+        ]);
+        assert_eq!(field(r#", "nx": 2, "heights": [0, 0]"#), vec![
+            "error: scene.objects[0].heightfield: missing \"nz\", needed with \"nx\", \"nz\", \"heights\"".to_string(),
+        ]);
+        assert_eq!(field(r#", "image": "a.png", "grid": "a.grid""#), vec![
+            "error: scene.objects[0].heightfield: expected only one of \"image\" or \"grid\" or \"nx\" with \"nz\" with \"heights\"".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn versions() {
+        let mut root = Json::from_str(r#"{ "scene": { "back": [0, 0, 0] } }"#).unwrap();
+        assert_eq!(migrate(&mut root).len(), 3);
+        assert_eq!(root, Json::from_str(format!(
+            r#"{{ "version": {}, "scene": {{ "ambient": 0.2, "sky": {{ "uniform": {{ "color": [0, 0, 0] }} }} }} }}"#, VERSION).as_slice()).unwrap());
+
+        let mut root = Json::from_str(r#"{ "scene": { "ambient": 0 } }"#).unwrap();
+        assert_eq!(migrate(&mut root), vec![format!("migrated from version 1 to {}", VERSION)]);
+
+        let mut root = Json::from_str(r#"{ "version": "2" }"#).unwrap();
+        assert!(migrate(&mut root).is_empty());
+        let errors: Vec<String> = check(&root).iter().map(|problem| problem.to_string()).collect();
+        assert!(errors.contains(&"error: version: expected an integer".to_string()), "{:?}", errors);
+
+        let root = Json::from_str(format!(r#"{{ "version": {} }}"#, VERSION + 1).as_slice()).unwrap();
+        let errors: Vec<String> = check(&root).iter().map(|problem| problem.to_string()).collect();
+        assert_eq!(errors[0], format!("error: version: {} is newer than {}, the latest supported version", VERSION + 1, VERSION));
+    }
+}
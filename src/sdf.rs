@@ -0,0 +1,540 @@
+//! Signed distance fields, drawn by `SdfObject` with sphere tracing
+
+use std::num::Float;
+use std::f64::INFINITY;
+use std::sync::Arc;
+use serialize::json::Json;
+use vec::{ Vec3, dot };
+use ray::{ Ray, Inter };
+use material::Material;
+use object::{ Object, facing };
+use export::{ Export, variant, num, int, vec3 };
+
+/// Signed distance field: negative inside, positive outside
+pub trait Sdf: Send + Sync {
+    /// Distance from `pos` to the surface, or a lower bound of it
+    fn dist(&self, pos: Vec3) -> f64;
+
+    // Canonical JSON, null for shapes that cannot be written back
+    #[doc(hidden)]
+    fn to_json(&self) -> Json {
+        Json::Null
+    }
+}
+
+fn shapes<'a>(shapes: &[Box<Sdf + 'a>]) -> Json {
+    Json::Array(shapes.iter().map(|shape| shape.to_json()).collect())
+}
+
+/// Ray marched object (sphere tracing)
+#[allow(dead_code)]
+pub struct SdfObject<'a> {
+    sdf:      Box<Sdf + 'a>,
+    mat:      Arc<Material>,
+    steps:    u32, // Maximum number of steps
+    eps:      f64, // Distance considered as a hit
+    max_dist: f64,
+    step:     f64, // Scale each step, lower than 1 for twisted or bent fields
+}
+
+impl<'a> SdfObject<'a> {
+    /// Marches at most `steps` steps up to `max_dist`, a hit is closer than `eps`
+    #[allow(dead_code)]
+    pub fn new(sdf: Box<Sdf + 'a>, mat: Arc<Material>, steps: u32, eps: f64, max_dist: f64, step: f64) -> SdfObject<'a> {
+        SdfObject { sdf: sdf, mat: mat, steps: steps, eps: eps, max_dist: max_dist, step: step }
+    }
+
+    fn normal(&self, pos: Vec3) -> Vec3 {
+        let h = self.eps;
+        let dx = Vec3::new(h, 0., 0.);
+        let dy = Vec3::new(0., h, 0.);
+        let dz = Vec3::new(0., 0., h);
+        Vec3::new(
+            self.sdf.dist(pos + dx) - self.sdf.dist(pos - dx),
+            self.sdf.dist(pos + dy) - self.sdf.dist(pos - dy),
+            self.sdf.dist(pos + dz) - self.sdf.dist(pos - dz),
+        )
+    }
+}
+
+impl<'a> Object for SdfObject<'a> {
+    fn intersect(&self, ray: &Ray) -> Option<Inter> {
+        // Rays leaving the surface start slightly off it
+        let mut dist = 2. * self.eps;
+        let sign = if self.sdf.dist(ray.pos) < 0. { -1. } else { 1. };
+        for _ in 0..self.steps {
+            let pos = ray.pos + ray.dir * dist;
+            let d = self.sdf.dist(pos) * sign;
+            if d < self.eps {
+                return Some(facing(ray, dist, self.normal(pos), &self.mat));
+            }
+            dist += d * self.step;
+            if dist > self.max_dist {
+                return None
+            }
+        }
+        None
+    }
+
+    fn to_json(&self, _: &mut Export) -> Json {
+        variant("sdf", vec![
+            ("shape", self.sdf.to_json()),
+            ("mat", self.mat.to_json()),
+            ("steps", int(self.steps as u64)),
+            ("eps", num(self.eps)),
+            ("max-dist", num(self.max_dist)),
+            ("step", num(self.step)),
+        ])
+    }
+}
+
+fn abs(vec: Vec3) -> Vec3 {
+    Vec3::new(vec.x.abs(), vec.y.abs(), vec.z.abs())
+}
+
+fn max(vec: Vec3, val: f64) -> Vec3 {
+    Vec3::new(vec.x.max(val), vec.y.max(val), vec.z.max(val))
+}
+
+// Modulo with the sign of the divisor
+fn modulo(x: f64, y: f64) -> f64 {
+    x - y * (x / y).floor()
+}
+
+fn mix(a: f64, b: f64, h: f64) -> f64 {
+    a * (1. - h) + b * h
+}
+
+fn clamp(x: f64) -> f64 {
+    x.max(0.).min(1.)
+}
+
+fn box_dist(pos: Vec3, half: Vec3) -> f64 {
+    let q = abs(pos) - half;
+    max(q, 0.).length() + q.x.max(q.y.max(q.z)).min(0.)
+}
+
+// Shapes
+
+/// Sphere of `radius` around `pos`
+#[allow(dead_code)]
+pub struct Sphere {
+    pos:    Vec3,
+    radius: f64,
+}
+
+impl Sphere {
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, radius: f64) -> Sphere {
+        Sphere { pos: pos, radius: radius }
+    }
+}
+
+impl Sdf for Sphere {
+    fn dist(&self, pos: Vec3) -> f64 {
+        (pos - self.pos).length() - self.radius
+    }
+
+    fn to_json(&self) -> Json {
+        variant("sphere", vec![("pos", vec3(self.pos)), ("radius", num(self.radius))])
+    }
+}
+
+/// Box of size `dim` centered on `pos`
+#[allow(dead_code)]
+pub struct Cuboid {
+    pos: Vec3,
+    dim: Vec3,
+}
+
+impl Cuboid {
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, dim: Vec3) -> Cuboid {
+        Cuboid { pos: pos, dim: dim }
+    }
+}
+
+impl Sdf for Cuboid {
+    fn dist(&self, pos: Vec3) -> f64 {
+        box_dist(pos - self.pos, self.dim / 2.)
+    }
+
+    fn to_json(&self) -> Json {
+        variant("box", vec![("pos", vec3(self.pos)), ("dim", vec3(self.dim))])
+    }
+}
+
+/// Torus around the y axis through `pos`, `major` is the radius of the ring and `minor` of the tube
+#[allow(dead_code)]
+pub struct Torus {
+    pos:   Vec3,
+    major: f64,
+    minor: f64,
+}
+
+impl Torus {
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, major: f64, minor: f64) -> Torus {
+        Torus { pos: pos, major: major, minor: minor }
+    }
+}
+
+impl Sdf for Torus {
+    fn dist(&self, pos: Vec3) -> f64 {
+        let p = pos - self.pos;
+        let ring = (p.x * p.x + p.z * p.z).sqrt() - self.major;
+        (ring * ring + p.y * p.y).sqrt() - self.minor
+    }
+
+    fn to_json(&self) -> Json {
+        variant("torus", vec![("pos", vec3(self.pos)), ("major", num(self.major)), ("minor", num(self.minor))])
+    }
+}
+
+/// Capped cylinder of `radius` and `height` centered on `pos`, along the y axis
+#[allow(dead_code)]
+pub struct Cylinder {
+    pos:    Vec3,
+    radius: f64,
+    height: f64,
+}
+
+impl Cylinder {
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, radius: f64, height: f64) -> Cylinder {
+        Cylinder { pos: pos, radius: radius, height: height }
+    }
+}
+
+impl Sdf for Cylinder {
+    fn dist(&self, pos: Vec3) -> f64 {
+        let p = pos - self.pos;
+        let dx = (p.x * p.x + p.z * p.z).sqrt() - self.radius;
+        let dy = p.y.abs() - self.height / 2.;
+        dx.max(dy).min(0.) + (dx.max(0.) * dx.max(0.) + dy.max(0.) * dy.max(0.)).sqrt()
+    }
+
+    fn to_json(&self) -> Json {
+        variant("cylinder", vec![("pos", vec3(self.pos)), ("radius", num(self.radius)), ("height", num(self.height))])
+    }
+}
+
+/// Plane through `pos`, facing `normal`
+#[allow(dead_code)]
+pub struct Plane {
+    pos:    Vec3,
+    normal: Vec3,
+}
+
+impl Plane {
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, normal: Vec3) -> Plane {
+        Plane { pos: pos, normal: normal.normalize() }
+    }
+}
+
+impl Sdf for Plane {
+    fn dist(&self, pos: Vec3) -> f64 {
+        dot(pos - self.pos, self.normal)
+    }
+
+    fn to_json(&self) -> Json {
+        variant("plane", vec![("pos", vec3(self.pos)), ("normal", vec3(self.normal))])
+    }
+}
+
+// Operators
+
+/// Inside any of the shapes
+#[allow(dead_code)]
+pub struct Union<'a> {
+    shapes: Vec<Box<Sdf + 'a>>,
+    k:      f64, // Smoothness, 0 for a sharp union
+}
+
+impl<'a> Union<'a> {
+    #[allow(dead_code)]
+    pub fn new(shapes: Vec<Box<Sdf + 'a>>, k: f64) -> Union<'a> {
+        Union { shapes: shapes, k: k }
+    }
+}
+
+impl<'a> Sdf for Union<'a> {
+    fn dist(&self, pos: Vec3) -> f64 {
+        let mut shapes = self.shapes.iter().map(|shape| shape.dist(pos));
+        // The union of nothing is never hit
+        let first = match shapes.next() {
+            Some(first) => first,
+            None        => return INFINITY,
+        };
+        shapes.fold(first, |d1, d2| {
+            if self.k <= 0. {
+                return d1.min(d2);
+            }
+            let h = clamp(0.5 + 0.5 * (d2 - d1) / self.k);
+            mix(d2, d1, h) - self.k * h * (1. - h)
+        })
+    }
+
+    fn to_json(&self) -> Json {
+        variant("union", vec![("shapes", shapes(self.shapes.as_slice())), ("k", num(self.k))])
+    }
+}
+
+/// Inside all of the shapes
+#[allow(dead_code)]
+pub struct Intersection<'a> {
+    shapes: Vec<Box<Sdf + 'a>>,
+}
+
+impl<'a> Intersection<'a> {
+    #[allow(dead_code)]
+    pub fn new(shapes: Vec<Box<Sdf + 'a>>) -> Intersection<'a> {
+        Intersection { shapes: shapes }
+    }
+}
+
+impl<'a> Sdf for Intersection<'a> {
+    fn dist(&self, pos: Vec3) -> f64 {
+        self.shapes.iter().map(|shape| shape.dist(pos)).fold(-INFINITY, |acc: f64, d| acc.max(d))
+    }
+
+    fn to_json(&self) -> Json {
+        variant("intersection", vec![("shapes", shapes(self.shapes.as_slice()))])
+    }
+}
+
+/// `from` with `shape` cut out
+#[allow(dead_code)]
+pub struct Subtraction<'a> {
+    from:  Box<Sdf + 'a>,
+    shape: Box<Sdf + 'a>,
+    k:     f64, // Smoothness, 0 for a sharp subtraction
+}
+
+impl<'a> Subtraction<'a> {
+    #[allow(dead_code)]
+    pub fn new(from: Box<Sdf + 'a>, shape: Box<Sdf + 'a>, k: f64) -> Subtraction<'a> {
+        Subtraction { from: from, shape: shape, k: k }
+    }
+}
+
+impl<'a> Sdf for Subtraction<'a> {
+    fn dist(&self, pos: Vec3) -> f64 {
+        let d1 = self.shape.dist(pos);
+        let d2 = self.from.dist(pos);
+        if self.k <= 0. {
+            return d2.max(-d1);
+        }
+        let h = clamp(0.5 - 0.5 * (d2 + d1) / self.k);
+        mix(d2, -d1, h) + self.k * h * (1. - h)
+    }
+
+    fn to_json(&self) -> Json {
+        variant("subtraction", vec![("from", self.from.to_json()), ("shape", self.shape.to_json()), ("k", num(self.k))])
+    }
+}
+
+/// Shape moved by `pos`
+#[allow(dead_code)]
+pub struct Translate<'a> {
+    pos:   Vec3,
+    shape: Box<Sdf + 'a>,
+}
+
+impl<'a> Translate<'a> {
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, shape: Box<Sdf + 'a>) -> Translate<'a> {
+        Translate { pos: pos, shape: shape }
+    }
+}
+
+impl<'a> Sdf for Translate<'a> {
+    fn dist(&self, pos: Vec3) -> f64 {
+        self.shape.dist(pos - self.pos)
+    }
+
+    fn to_json(&self) -> Json {
+        variant("translate", vec![("pos", vec3(self.pos)), ("shape", self.shape.to_json())])
+    }
+}
+
+/// Infinite repetition, a period of 0 disables an axis
+#[allow(dead_code)]
+pub struct Repeat<'a> {
+    period: Vec3,
+    shape:  Box<Sdf + 'a>,
+}
+
+impl<'a> Repeat<'a> {
+    #[allow(dead_code)]
+    pub fn new(period: Vec3, shape: Box<Sdf + 'a>) -> Repeat<'a> {
+        Repeat { period: period, shape: shape }
+    }
+}
+
+impl<'a> Sdf for Repeat<'a> {
+    fn dist(&self, pos: Vec3) -> f64 {
+        let repeat = |x: f64, c: f64| if c > 0. { modulo(x + 0.5 * c, c) - 0.5 * c } else { x };
+        self.shape.dist(Vec3::new(
+            repeat(pos.x, self.period.x),
+            repeat(pos.y, self.period.y),
+            repeat(pos.z, self.period.z),
+        ))
+    }
+
+    fn to_json(&self) -> Json {
+        variant("repeat", vec![("period", vec3(self.period)), ("shape", self.shape.to_json())])
+    }
+}
+
+/// Twist around the y axis, `k` radians per unit
+#[allow(dead_code)]
+pub struct Twist<'a> {
+    k:     f64,
+    shape: Box<Sdf + 'a>,
+}
+
+impl<'a> Twist<'a> {
+    #[allow(dead_code)]
+    pub fn new(k: f64, shape: Box<Sdf + 'a>) -> Twist<'a> {
+        Twist { k: k, shape: shape }
+    }
+}
+
+impl<'a> Sdf for Twist<'a> {
+    fn dist(&self, pos: Vec3) -> f64 {
+        let (s, c) = (self.k * pos.y).sin_cos();
+        self.shape.dist(Vec3::new(c * pos.x - s * pos.z, pos.y, s * pos.x + c * pos.z))
+    }
+
+    fn to_json(&self) -> Json {
+        variant("twist", vec![("k", num(self.k)), ("shape", self.shape.to_json())])
+    }
+}
+
+/// Bend the x axis towards y, `k` radians per unit
+#[allow(dead_code)]
+pub struct Bend<'a> {
+    k:     f64,
+    shape: Box<Sdf + 'a>,
+}
+
+impl<'a> Bend<'a> {
+    #[allow(dead_code)]
+    pub fn new(k: f64, shape: Box<Sdf + 'a>) -> Bend<'a> {
+        Bend { k: k, shape: shape }
+    }
+}
+
+impl<'a> Sdf for Bend<'a> {
+    fn dist(&self, pos: Vec3) -> f64 {
+        let (s, c) = (self.k * pos.x).sin_cos();
+        self.shape.dist(Vec3::new(c * pos.x - s * pos.y, s * pos.x + c * pos.y, pos.z))
+    }
+
+    fn to_json(&self) -> Json {
+        variant("bend", vec![("k", num(self.k)), ("shape", self.shape.to_json())])
+    }
+}
+
+// Fractals
+
+/// Mandelbulb of `power` around `pos`, `scale` times its usual size
+#[allow(dead_code)]
+pub struct Mandelbulb {
+    pos:   Vec3,
+    scale: f64,
+    power: f64,
+    iter:  u32,
+}
+
+impl Mandelbulb {
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, scale: f64, power: f64, iter: u32) -> Mandelbulb {
+        Mandelbulb { pos: pos, scale: scale, power: power, iter: iter }
+    }
+}
+
+impl Sdf for Mandelbulb {
+    fn dist(&self, pos: Vec3) -> f64 {
+        let c = (pos - self.pos) / self.scale;
+        let mut z = c;
+        let mut dr = 1.;
+        let mut r = z.length();
+        for _ in 0..self.iter {
+            if r > 2. {
+                break;
+            }
+
+            // Zero raised to the power, the angles are undefined
+            if r == 0. {
+                z = c;
+                dr = 1.;
+                r = z.length();
+                continue;
+            }
+
+            // Spherical coordinates, raised to the power
+            let theta = (z.y / r).acos() * self.power;
+            let phi = z.z.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.) * self.power * dr + 1.;
+            let zr = r.powf(self.power);
+            z = Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()) * zr + c;
+            r = z.length();
+        }
+        // The estimate tends to 0 at the center, where r.ln() * r is NaN
+        if r == 0. {
+            return 0.;
+        }
+        0.5 * r.ln() * r / dr * self.scale
+    }
+
+    fn to_json(&self) -> Json {
+        variant("mandelbulb", vec![("pos", vec3(self.pos)), ("scale", num(self.scale)), ("power", num(self.power)), ("iter", int(self.iter as u64))])
+    }
+}
+
+/// Menger sponge of side `size` centered on `pos`
+#[allow(dead_code)]
+pub struct Menger {
+    pos:  Vec3,
+    size: f64,
+    iter: u32,
+}
+
+impl Menger {
+    #[allow(dead_code)]
+    pub fn new(pos: Vec3, size: f64, iter: u32) -> Menger {
+        Menger { pos: pos, size: size, iter: iter }
+    }
+}
+
+impl Sdf for Menger {
+    fn dist(&self, pos: Vec3) -> f64 {
+        // Work on a cube of size 2
+        let scale = self.size / 2.;
+        let p = (pos - self.pos) / scale;
+        let mut d = box_dist(p, Vec3::new(1., 1., 1.));
+        let mut s = 1.;
+        for _ in 0..self.iter {
+            let a = Vec3::new(modulo(p.x * s, 2.) - 1., modulo(p.y * s, 2.) - 1., modulo(p.z * s, 2.) - 1.);
+            s *= 3.;
+            let r = Vec3::new(
+                (1. - 3. * a.x.abs()).abs(),
+                (1. - 3. * a.y.abs()).abs(),
+                (1. - 3. * a.z.abs()).abs(),
+            );
+            let da = r.x.max(r.y);
+            let db = r.y.max(r.z);
+            let dc = r.z.max(r.x);
+            let c = (da.min(db.min(dc)) - 1.) / s;
+            d = d.max(c);
+        }
+        d * scale
+    }
+
+    fn to_json(&self) -> Json {
+        variant("menger", vec![("pos", vec3(self.pos)), ("size", num(self.size)), ("iter", int(self.iter as u64))])
+    }
+}
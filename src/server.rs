@@ -0,0 +1,350 @@
+use std::mem;
+use std::collections::BTreeMap;
+use std::old_io::{ BufferedReader, Listener, Acceptor };
+use std::old_io::fs::{ self, File };
+use std::old_io::net::tcp::{ TcpListener, TcpStream };
+use std::sync::{ Arc, Mutex, Condvar };
+use std::thread;
+use serialize::json::Json;
+use rt::Status;
+use rt::config;
+
+// Largest scene accepted, in bytes
+const MAX_BODY: usize = 16 << 20;
+
+// Finished jobs kept, the oldest ones are forgotten and their images removed
+const MAX_FINISHED: usize = 100;
+
+#[derive(Copy, PartialEq)]
+enum State {
+    Queued,
+    Running,
+    Done,
+    Cancelled,
+    Failed,
+}
+
+impl State {
+    fn name(&self) -> &'static str {
+        match *self {
+            State::Queued    => "queued",
+            State::Running   => "running",
+            State::Done      => "done",
+            State::Cancelled => "cancelled",
+            State::Failed    => "failed",
+        }
+    }
+}
+
+struct Job {
+    id:     u32,
+    source: Json,                // Scene as posted, patches are applied to it again
+    sets:   Vec<(String, Json)>, // Patches, from the first job to this one
+    root:   Json,                // Scene prepared for rendering
+    state:  State,
+    status: Arc<Status>,
+    path:   String,              // Where the image is written
+}
+
+impl Job {
+    fn finished(&self) -> bool {
+        self.state != State::Queued && self.state != State::Running
+    }
+
+    fn to_json(&self) -> Json {
+        let mut obj = BTreeMap::new();
+        obj.insert("id".to_string(), Json::U64(self.id as u64));
+        obj.insert("state".to_string(), Json::String(self.state.name().to_string()));
+        obj.insert("progress".to_string(), Json::F64(match self.state {
+            State::Running => self.status.progress(),
+            State::Done    => 1.,
+            _              => 0.,
+        }));
+        obj.insert("path".to_string(), Json::String(self.path.clone()));
+        Json::Object(obj)
+    }
+}
+
+struct Jobs {
+    all:  Vec<Job>,
+    next: u32,
+}
+
+impl Jobs {
+    fn evict(&mut self) {
+        let finished = self.all.iter().filter(|job| job.finished()).count();
+        if finished <= MAX_FINISHED {
+            return;
+        }
+        let mut excess = finished - MAX_FINISHED;
+        for job in mem::replace(&mut self.all, vec![]).into_iter() {
+            if excess > 0 && job.finished() {
+                let _ = fs::unlink(&Path::new(job.path.as_slice()));
+                excess -= 1;
+            } else {
+                self.all.push(job);
+            }
+        }
+    }
+}
+
+// Image path of a job, "image.png" becomes "image-<id>.png" so that jobs do not overwrite each other
+fn job_path(path: &str, id: u32) -> String {
+    let path = Path::new(path);
+    let name = match path.extension_str() {
+        Some(ext) => format!("{}-{}.{}", path.filestem_str().unwrap(), id, ext),
+        None      => format!("{}-{}", path.filename_str().unwrap(), id),
+    };
+    path.with_filename(name).as_str().unwrap().to_string()
+}
+
+type Shared = Arc<(Mutex<Jobs>, Condvar)>;
+
+// Local HTTP API, jobs are rendered one at a time in the order they come
+//   POST   /jobs            scene, or { "base": id, "set": { "eye.pos.x": 10 } } to patch a previous job
+//   GET    /jobs            every job
+//   GET    /jobs/<id>       state and progress
+//   GET    /jobs/<id>/image rendered PNG
+//   DELETE /jobs/<id>       cancel
+// Images are written next to picture.path, with the id of the job, "image-3.png"
+pub fn serve(addr: &str) {
+    let shared: Shared = Arc::new((Mutex::new(Jobs { all: vec![], next: 1 }), Condvar::new()));
+
+    let worker = shared.clone();
+    thread::spawn(move || work(worker));
+
+    let mut acceptor = TcpListener::bind(addr).unwrap().listen().unwrap();
+    println!("Listening on http://{}", addr);
+    for stream in acceptor.incoming() {
+        if let Ok(stream) = stream {
+            let shared = shared.clone();
+            thread::spawn(move || handle(stream, shared));
+        }
+    }
+}
+
+// Render queued jobs, a panic only fails its own job
+fn work(shared: Shared) {
+    loop {
+        let (id, root, status) = {
+            let &(ref lock, ref cvar) = &*shared;
+            let mut jobs = lock.lock().unwrap();
+            loop {
+                if let Some(job) = jobs.all.iter_mut().find(|job| job.state == State::Queued) {
+                    job.state = State::Running;
+                    break;
+                }
+                jobs = cvar.wait(jobs).unwrap();
+            }
+            let job = jobs.all.iter().find(|job| job.state == State::Running).unwrap();
+            (job.id, job.root.clone(), job.status.clone())
+        };
+
+        let cancel = status.clone();
+        let result = thread::spawn(move || {
+            let (eye, scene, mut picture) = config::load(&root);
+            picture.watch(status.clone());
+            let (img, passes) = picture.shot_with_passes(&eye, &scene, false);
+            if status.cancelled() {
+                return;
+            }
+            let img = picture.denoise(&eye, &scene, img);
+            picture.save(&img);
+            if picture.ao.is_some() {
+                let ao = picture.shot_ao(&eye, &scene, false);
+                picture.save_ao(&ao);
+            }
+            if !picture.passes.is_empty() {
+                picture.save_passes(&img, &passes);
+            }
+        }).join();
+
+        let &(ref lock, _) = &*shared;
+        let mut jobs = lock.lock().unwrap();
+        if let Some(job) = jobs.all.iter_mut().find(|job| job.id == id) {
+            job.state = match result {
+                Ok(_) if cancel.cancelled() => State::Cancelled,
+                Ok(_)                       => State::Done,
+                Err(_)                      => State::Failed,
+            };
+        }
+        jobs.evict();
+    }
+}
+
+fn respond(stream: &mut TcpStream, code: u32, kind: &str, body: &[u8]) {
+    let reason = match code {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        413 => "Payload Too Large",
+        _   => "Error",
+    };
+    let head = format!("HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                       code, reason, kind, body.len());
+    let _ = stream.write_str(head.as_slice());
+    let _ = stream.write_all(body);
+}
+
+fn respond_json(stream: &mut TcpStream, code: u32, json: &Json) {
+    respond(stream, code, "application/json", json.to_string().as_bytes());
+}
+
+fn error(stream: &mut TcpStream, code: u32, message: &str) {
+    let mut obj = BTreeMap::new();
+    obj.insert("error".to_string(), Json::String(message.to_string()));
+    respond_json(stream, code, &Json::Object(obj));
+}
+
+// Read one request, method, path and body
+// Err(None) when the connection breaks, Err(Some(message)) when the request is refused
+fn read_request(stream: TcpStream) -> Result<(String, String, String), Option<&'static str>> {
+    let mut reader = BufferedReader::new(stream);
+    let line = match reader.read_line() {
+        Ok(line) => line,
+        Err(_)   => return Err(None),
+    };
+    let words: Vec<&str> = line.words().collect();
+    if words.len() < 2 {
+        return Err(None);
+    }
+
+    // Headers, only the length of the body matters
+    let mut length = 0;
+    loop {
+        let header = match reader.read_line() {
+            Ok(header) => header,
+            Err(_)     => return Err(None),
+        };
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(i) = header.find(':') {
+            if header[..i].trim().to_lowercase() == "content-length" {
+                length = header[(i + 1)..].trim().parse::<usize>().unwrap_or(0);
+            }
+        }
+    }
+    if length > MAX_BODY {
+        return Err(Some("Scene too large"));
+    }
+    let body = match reader.read_exact(length) {
+        Ok(body) => String::from_utf8_lossy(body.as_slice()).into_owned(),
+        Err(_)   => return Err(None),
+    };
+    Ok((words[0].to_string(), words[1].to_string(), body))
+}
+
+fn handle(stream: TcpStream, shared: Shared) {
+    let mut out = stream.clone();
+    let (method, path, body) = match read_request(stream) {
+        Ok(request)        => request,
+        Err(Some(message)) => return error(&mut out, 413, message),
+        Err(None)          => return,
+    };
+    let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+    let &(ref lock, ref cvar) = &*shared;
+
+    if parts.is_empty() || parts[0] != "jobs" {
+        return error(&mut out, 404, "Unknown path");
+    }
+    if parts.len() == 1 {
+        match method.as_slice() {
+            "GET"  => {
+                let jobs = lock.lock().unwrap();
+                respond_json(&mut out, 200, &Json::Array(jobs.all.iter().map(|job| job.to_json()).collect()));
+            },
+            "POST" => {
+                let json = match Json::from_str(body.as_slice()) {
+                    Ok(json) => json,
+                    Err(err) => return error(&mut out, 400, format!("{}", err).as_slice()),
+                };
+                let mut jobs = lock.lock().unwrap();
+
+                // A patch starts from the scene of a previous job, its fields are set before variables are resolved
+                let (source, sets) = if json.find("base").is_some() {
+                    let base = json.find("base").unwrap().as_u64().unwrap_or(0) as u32;
+                    let (source, mut sets) = match jobs.all.iter().find(|job| job.id == base) {
+                        Some(job) => (job.source.clone(), job.sets.clone()),
+                        None      => return error(&mut out, 404, "Unknown base job"),
+                    };
+                    if let Some(patch) = json.find("set").and_then(|sets| sets.as_object()) {
+                        sets.extend(patch.iter().map(|(path, value)| (path.clone(), value.clone())));
+                    }
+                    (source, sets)
+                } else {
+                    (json, vec![])
+                };
+                let (root, problems) = match config::prepare(&source, &Path::new("."), sets.as_slice()) {
+                    Ok(prepared) => prepared,
+                    Err(err)     => return error(&mut out, 400, err.as_slice()),
+                };
+                let problems: Vec<Json> = problems.iter()
+                    .filter(|problem| problem.error)
+                    .map(|problem| Json::String(problem.to_string()))
+                    .collect();
+                if !problems.is_empty() {
+                    let mut obj = BTreeMap::new();
+                    obj.insert("error".to_string(), Json::String("Invalid scene".to_string()));
+                    obj.insert("problems".to_string(), Json::Array(problems));
+                    return respond_json(&mut out, 400, &Json::Object(obj));
+                }
+                let id = jobs.next;
+                let mut root = root;
+                let path = match root.find_path(&["picture", "path"]).and_then(|path| path.as_string()) {
+                    Some(path) => job_path(path, id),
+                    None       => return error(&mut out, 400, "Missing picture.path"),
+                };
+                config::set(&mut root, "picture.path", Json::String(path.clone()));
+                if let Some(ao) = root.find_path(&["picture", "ao"]).and_then(|ao| ao.as_string()).map(|ao| job_path(ao, id)) {
+                    config::set(&mut root, "picture.ao", Json::String(ao));
+                }
+                jobs.next += 1;
+                let job = Job {
+                    id: id,
+                    source: source,
+                    sets: sets,
+                    root: root,
+                    state: State::Queued,
+                    status: Arc::new(Status::new()),
+                    path: path,
+                };
+                respond_json(&mut out, 201, &job.to_json());
+                jobs.all.push(job);
+                cvar.notify_all();
+            },
+            _      => error(&mut out, 400, "Unknown method"),
+        }
+        return;
+    }
+
+    let id = parts[1].parse::<u32>().unwrap_or(0);
+    let mut jobs = lock.lock().unwrap();
+    let job = match jobs.all.iter_mut().find(|job| job.id == id) {
+        Some(job) => job,
+        None      => return error(&mut out, 404, "Unknown job"),
+    };
+    match (method.as_slice(), parts.len()) {
+        ("GET", 2)                        => respond_json(&mut out, 200, &job.to_json()),
+        ("GET", 3) if parts[2] == "image" => {
+            if job.state != State::Done {
+                return error(&mut out, 404, "Job not done");
+            }
+            match File::open(&Path::new(job.path.as_slice())).read_to_end() {
+                Ok(data) => respond(&mut out, 200, "image/png", data.as_slice()),
+                Err(_)   => error(&mut out, 404, "Image not found"),
+            }
+        },
+        ("DELETE", 2)                     => {
+            match job.state {
+                State::Queued  => job.state = State::Cancelled,
+                State::Running => job.status.cancel(),
+                _              => {},
+            }
+            respond_json(&mut out, 200, &job.to_json());
+        },
+        _                                 => error(&mut out, 400, "Unknown request"),
+    }
+}
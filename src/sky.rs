@@ -0,0 +1,314 @@
+use std::num::Float;
+use std::f64::consts::PI;
+use std::old_io::fs::File;
+use serialize::json::Json;
+use image::{ self, GenericImage };
+use vec::{ Vec3, dot };
+use material::Color;
+use export::{ self, variant, num, string, vec3 };
+
+/// Light coming from infinitely far away, seen in direction `dir`
+pub trait Sky: Send + Sync {
+    /// Light seen in direction `dir`
+    fn color(&self, dir: Vec3) -> Color;
+
+    // Canonical JSON, null for skies that cannot be written back
+    #[doc(hidden)]
+    fn to_json(&self) -> Json {
+        Json::Null
+    }
+}
+
+// Load an image as linear colors, Radiance .hdr files keep their full range
+#[doc(hidden)]
+pub fn load_image(path: &str) -> (usize, usize, Vec<Color>) {
+    if path.ends_with(".hdr") {
+        return load_hdr(path);
+    }
+    let img = image::open(&Path::new(path)).unwrap().to_rgb();
+    let (w, h) = img.dimensions();
+    let mut pixels = Vec::with_capacity((w * h) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            let data = img.get_pixel(x, y).data;
+            pixels.push(Color::new(data[0] as f64 / 255., data[1] as f64 / 255., data[2] as f64 / 255.));
+        }
+    }
+    (w as usize, h as usize, pixels)
+}
+
+// Radiance RGBE, flat or run length encoded scanlines
+fn load_hdr(path: &str) -> (usize, usize, Vec<Color>) {
+    let data = File::open(&Path::new(path)).read_to_end().unwrap();
+
+    // Header, ends with an empty line, followed by the resolution
+    let mut pos = 0;
+    let mut line = String::new();
+    let mut lines = vec![];
+    while lines.len() < 2 || !lines[lines.len() - 2].is_empty() {
+        let c = data[pos] as char;
+        pos += 1;
+        if c == '\n' {
+            lines.push(line.clone());
+            line.clear();
+        } else {
+            line.push(c);
+        }
+    }
+    let res: Vec<&str> = lines[lines.len() - 1].words().collect();
+    if res.len() != 4 || res[0] != "-Y" || res[2] != "+X" {
+        panic!("Unsupported hdr orientation: {}", path);
+    }
+    let h = res[1].parse::<usize>().unwrap();
+    let w = res[3].parse::<usize>().unwrap();
+
+    let mut pixels = Vec::with_capacity(w * h);
+    let mut scanline = vec![0u8; w * 4];
+    for _ in 0..h {
+        if w >= 8 && w < 32768 && data[pos] == 2 && data[pos + 1] == 2 && data[pos + 2] & 0x80 == 0 {
+            // Each channel is run length encoded separately
+            pos += 4;
+            for channel in 0..4 {
+                let mut x = 0;
+                while x < w {
+                    let count = data[pos] as usize;
+                    pos += 1;
+                    if count > 128 {
+                        for _ in 0..(count - 128) {
+                            scanline[x * 4 + channel] = data[pos];
+                            x += 1;
+                        }
+                        pos += 1;
+                    } else {
+                        for _ in 0..count {
+                            scanline[x * 4 + channel] = data[pos];
+                            x += 1;
+                            pos += 1;
+                        }
+                    }
+                }
+            }
+        } else {
+            for i in 0..(w * 4) {
+                scanline[i] = data[pos + i];
+            }
+            pos += w * 4;
+        }
+
+        for x in 0..w {
+            let e = scanline[x * 4 + 3];
+            if e == 0 {
+                pixels.push(Color::new(0., 0., 0.));
+                continue;
+            }
+            let f = 2f64.powi(e as i32 - 136);
+            pixels.push(Color::new(
+                scanline[x * 4] as f64 * f,
+                scanline[x * 4 + 1] as f64 * f,
+                scanline[x * 4 + 2] as f64 * f,
+            ));
+        }
+    }
+    (w, h, pixels)
+}
+
+/// Same color in every direction
+#[allow(dead_code)]
+pub struct Uniform {
+    color: Color,
+}
+
+impl Uniform {
+    pub fn new(color: Color) -> Uniform {
+        Uniform { color: color }
+    }
+}
+
+impl Sky for Uniform {
+    fn color(&self, _: Vec3) -> Color {
+        self.color
+    }
+
+    fn to_json(&self) -> Json {
+        variant("uniform", vec![("color", export::color(self.color))])
+    }
+}
+
+/// Equirectangular environment map, +y is up
+#[allow(dead_code)]
+pub struct EnvMap {
+    path:      String,
+    w:         usize,
+    h:         usize,
+    pixels:    Vec<Color>,
+    intensity: f64,
+    rotate:    f64, // Rotation around the y axis
+}
+
+impl EnvMap {
+    /// `rotate` turns the map around the y axis, in radians
+    pub fn new(path: &str, intensity: f64, rotate: f64) -> EnvMap {
+        let (w, h, pixels) = load_image(path);
+        EnvMap { path: path.to_string(), w: w, h: h, pixels: pixels, intensity: intensity, rotate: rotate }
+    }
+}
+
+impl Sky for EnvMap {
+    fn color(&self, dir: Vec3) -> Color {
+        let u = ((dir.x.atan2(-dir.z) + self.rotate) / (2. * PI) + 0.5).fract();
+        let v = dir.y.max(-1.).min(1.).acos() / PI;
+        let x = ((u * self.w as f64) as usize).min(self.w - 1);
+        let y = ((v * self.h as f64) as usize).min(self.h - 1);
+        self.pixels[y * self.w + x] * self.intensity
+    }
+
+    fn to_json(&self) -> Json {
+        variant("equirect", vec![
+            ("path", string(self.path.as_slice())),
+            ("intensity", num(self.intensity)),
+            ("rotate", num(self.rotate)),
+        ])
+    }
+}
+
+/// Six square faces, in the order +x, -x, +y, -y, +z, -z
+#[allow(dead_code)]
+pub struct CubeMap {
+    paths:     Vec<String>,
+    faces:     Vec<(usize, usize, Vec<Color>)>,
+    intensity: f64,
+}
+
+impl CubeMap {
+    /// Panics without exactly six faces
+    pub fn new(paths: &[&str], intensity: f64) -> CubeMap {
+        if paths.len() != 6 {
+            panic!("A cube map needs six faces");
+        }
+        CubeMap {
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+            faces: paths.iter().map(|path| load_image(*path)).collect(),
+            intensity: intensity,
+        }
+    }
+}
+
+impl Sky for CubeMap {
+    fn color(&self, dir: Vec3) -> Color {
+        let (ax, ay, az) = (dir.x.abs(), dir.y.abs(), dir.z.abs());
+        let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+            if dir.x > 0. { (0, -dir.z, -dir.y, ax) } else { (1, dir.z, -dir.y, ax) }
+        } else if ay >= az {
+            if dir.y > 0. { (2, dir.x, dir.z, ay) } else { (3, dir.x, -dir.z, ay) }
+        } else {
+            if dir.z > 0. { (4, dir.x, -dir.y, az) } else { (5, -dir.x, -dir.y, az) }
+        };
+        let (w, h, ref pixels) = self.faces[face];
+        let x = (((sc / ma + 1.) / 2. * w as f64) as usize).min(w - 1);
+        let y = (((tc / ma + 1.) / 2. * h as f64) as usize).min(h - 1);
+        pixels[y * w + x] * self.intensity
+    }
+
+    fn to_json(&self) -> Json {
+        let names = ["px", "nx", "py", "ny", "pz", "nz"];
+        let mut fields: Vec<(&str, Json)> = names.iter().zip(self.paths.iter())
+            .map(|(name, path)| (*name, string(path.as_slice())))
+            .collect();
+        fields.push(("intensity", num(self.intensity)));
+        variant("cubemap", fields)
+    }
+}
+
+/// Preetham analytic daylight model
+#[allow(dead_code)]
+pub struct Preetham {
+    sun:       Vec3,     // Direction towards the sun
+    turbidity: f64,
+    theta_s:   f64,      // Zenith angle of the sun
+    zenith:    [f64; 3], // Y, x and y at the zenith
+    perez:     [[f64; 5]; 3],
+    intensity: f64,
+}
+
+impl Preetham {
+    /// `sun` points towards the sun, `turbidity` goes from 2 for a clear sky to 10 for haze
+    pub fn new(sun: Vec3, turbidity: f64, intensity: f64) -> Preetham {
+        let sun = sun.normalize();
+        let t = turbidity;
+        let theta_s = sun.y.max(0.).min(1.).acos();
+
+        // Zenith luminance and chromaticity
+        let chi = (4. / 9. - t / 120.) * (PI - 2. * theta_s);
+        let big_y = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chroma = |m: [[f64; 4]; 3]| {
+            let th = [theta_s * theta_s * theta_s, theta_s * theta_s, theta_s, 1.];
+            let row = |r: [f64; 4]| r[0] * th[0] + r[1] * th[1] + r[2] * th[2] + r[3] * th[3];
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+        let x = chroma([
+            [0.00166, -0.00375, 0.00209, 0.],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let y = chroma([
+            [0.00275, -0.00610, 0.00317, 0.],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        // Distribution coefficients for Y, x and y
+        let perez = [
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529],
+        ];
+
+        Preetham {
+            sun: sun,
+            turbidity: turbidity,
+            theta_s: theta_s,
+            zenith: [big_y, x, y],
+            perez: perez,
+            intensity: intensity,
+        }
+    }
+
+    fn distribution(coefs: [f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+        (1. + coefs[0] * (coefs[1] / cos_theta).exp())
+            * (1. + coefs[2] * (coefs[3] * gamma).exp() + coefs[4] * gamma.cos() * gamma.cos())
+    }
+}
+
+impl Sky for Preetham {
+    fn color(&self, dir: Vec3) -> Color {
+        // Below the horizon, keep the color of the horizon
+        let cos_theta = dir.y.max(0.01);
+        let gamma = dot(dir.normalize(), self.sun).max(-1.).min(1.).acos();
+
+        let mut xyy = [0.; 3];
+        for i in 0..3 {
+            let num = Preetham::distribution(self.perez[i], cos_theta, gamma);
+            let den = Preetham::distribution(self.perez[i], 1., self.theta_s);
+            xyy[i] = self.zenith[i] * num / den;
+        }
+
+        // xyY to XYZ to linear sRGB
+        let (big_y, x, y) = (xyy[0] * self.intensity, xyy[1], xyy[2]);
+        let big_x = x / y * big_y;
+        let big_z = (1. - x - y) / y * big_y;
+        Color::new(
+            (3.2406 * big_x - 1.5372 * big_y - 0.4986 * big_z).max(0.),
+            (-0.9689 * big_x + 1.8758 * big_y + 0.0415 * big_z).max(0.),
+            (0.0557 * big_x - 0.2040 * big_y + 1.0570 * big_z).max(0.),
+        )
+    }
+
+    // Direction of the light, like the sun it follows
+    fn to_json(&self) -> Json {
+        variant("preetham", vec![
+            ("dir", vec3(self.sun * -1.)),
+            ("turbidity", num(self.turbidity)),
+            ("intensity", num(self.intensity)),
+        ])
+    }
+}
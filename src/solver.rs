@@ -0,0 +1,278 @@
+use std::num::Float;
+use std::f64::consts::PI;
+
+const EPSILON: f64 = 1e-9;
+
+// When the root the leading coefficient adds is this much larger than the others,
+// the closed forms would lose the others to rounding
+const DEGENERATE: f64 = 1e-4;
+
+fn is_zero(x: f64) -> bool {
+    x > -EPSILON && x < EPSILON
+}
+
+// Newton steps on the polynomial, coefficients highest degree first, for the precision lost in the closed forms
+fn polish(coefs: &[f64], root: f64) -> f64 {
+    let value = |x: f64| coefs.iter().fold(0., |acc, &c| acc * x + c);
+    let mut x = root;
+    for _ in 0..4 {
+        let (v, dv) = coefs.iter().fold((0., 0.), |(v, dv), &c| (v * x + c, dv * x + v));
+        if dv == 0. {
+            break;
+        }
+        let next = x - v / dv;
+        if !next.is_finite() || value(next).abs() > v.abs() {
+            break;
+        }
+        x = next;
+    }
+    x
+}
+
+// Polish and sort
+fn refine(coefs: &[f64], mut roots: Vec<f64>) -> Vec<f64> {
+    for root in roots.iter_mut() {
+        *root = polish(coefs, *root);
+    }
+    roots.sort_by(|l, r| l.partial_cmp(r).unwrap());
+    roots
+}
+
+// Whether the leading coefficient only adds a root far from the others, found with the lower degree
+// Fujiwara's bound on the roots of the lower degree, so that it does not depend on the scale of the coefficients
+fn degenerate(coefs: &[f64]) -> bool {
+    let lead = coefs[1];
+    if lead == 0. {
+        return false;
+    }
+    let bound = coefs[2..].iter().enumerate().fold(0., |acc: f64, (k, &c)| acc.max((c / lead).abs().powf(1. / (k + 1) as f64)));
+    coefs[0].abs() * 2. * bound < DEGENERATE * lead.abs()
+}
+
+// Roots of the lower degree are kept when each of them is one of the polynomial
+fn accurate(coefs: &[f64], roots: &[f64]) -> bool {
+    let degree = coefs.len() - 1;
+    roots.iter().all(|&x| {
+        let value = coefs.iter().fold(0., |acc, &c| acc * x + c);
+        let scale = coefs.iter().enumerate().fold(0., |acc, (i, &c)| acc + (c * x.powi((degree - i) as i32)).abs());
+        value.abs() <= 1e-6 * scale
+    })
+}
+
+// Real roots of a * x^2 + b * x + c, sorted
+pub fn quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if is_zero(a) {
+        if is_zero(b) {
+            return vec![];
+        }
+        return vec![-c / b];
+    }
+    let disc = b * b - 4. * a * c;
+    if disc < 0. {
+        return vec![];
+    }
+    let sqrt_disc = disc.sqrt();
+    let t1 = (-b - sqrt_disc) / (2. * a);
+    let t2 = (-b + sqrt_disc) / (2. * a);
+    if t1 < t2 { vec![t1, t2] } else { vec![t2, t1] }
+}
+
+// Real roots of a * x^3 + b * x^2 + c * x + d, sorted
+pub fn cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if is_zero(a) {
+        return quadratic(b, c, d);
+    }
+    if degenerate(&[a, b, c, d]) {
+        // Roots of the quadratic, and the one that makes the sum of the roots -b / a
+        let mut roots = quadratic(b, c, d);
+        roots.push(c / b - b / a);
+        let roots = refine(&[a, b, c, d], roots);
+        if accurate(&[a, b, c, d], roots.as_slice()) {
+            return roots;
+        }
+    }
+    refine(&[a, b, c, d], cardano(a, b, c, d))
+}
+
+fn cardano(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // Normal form x^3 + A * x^2 + B * x + C
+    let (a, b, c) = (b / a, c / a, d / a);
+
+    // Substitute x = y - A / 3 to eliminate quadric term: y^3 + p * y + q
+    let sq_a = a * a;
+    let p = 1. / 3. * (-1. / 3. * sq_a + b);
+    let q = 1. / 2. * (2. / 27. * a * sq_a - 1. / 3. * a * b + c);
+
+    // Cardano's formula
+    let cb_p = p * p * p;
+    let disc = q * q + cb_p;
+    let mut roots = if is_zero(disc) {
+        if is_zero(q) {
+            vec![0.]
+        } else {
+            let u = (-q).cbrt();
+            vec![2. * u, -u]
+        }
+    } else if disc < 0. {
+        let phi = 1. / 3. * (-q / (-cb_p).sqrt()).acos();
+        let t = 2. * (-p).sqrt();
+        vec![t * phi.cos(), -t * (phi + PI / 3.).cos(), -t * (phi - PI / 3.).cos()]
+    } else {
+        let sqrt_disc = disc.sqrt();
+        let u = (sqrt_disc - q).cbrt();
+        let v = -(sqrt_disc + q).cbrt();
+        vec![u + v]
+    };
+
+    // Resubstitute
+    for root in roots.iter_mut() {
+        *root -= 1. / 3. * a;
+    }
+    roots
+}
+
+// Real roots of a * x^4 + b * x^3 + c * x^2 + d * x + e, sorted
+pub fn quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if is_zero(a) {
+        return cubic(b, c, d, e);
+    }
+    if degenerate(&[a, b, c, d, e]) {
+        // Roots of the cubic, and the one that makes the sum of the roots -b / a
+        let mut roots = cubic(b, c, d, e);
+        roots.push(c / b - b / a);
+        let roots = refine(&[a, b, c, d, e], roots);
+        if accurate(&[a, b, c, d, e], roots.as_slice()) {
+            return roots;
+        }
+    }
+    refine(&[a, b, c, d, e], ferrari(a, b, c, d, e))
+}
+
+fn ferrari(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    // Normal form x^4 + A * x^3 + B * x^2 + C * x + D
+    let (a, b, c, d) = (b / a, c / a, d / a, e / a);
+
+    // Substitute x = y - A / 4 to eliminate cubic term: y^4 + p * y^2 + q * y + r
+    let sq_a = a * a;
+    let p = -3. / 8. * sq_a + b;
+    let q = 1. / 8. * sq_a * a - 1. / 2. * a * b + c;
+    let r = -3. / 256. * sq_a * sq_a + 1. / 16. * sq_a * b - 1. / 4. * a * c + d;
+
+    let mut roots = if is_zero(r) {
+        // No absolute term: y * (y^3 + p * y + q) = 0
+        let mut roots = cubic(1., 0., p, q);
+        roots.push(0.);
+        roots
+    } else {
+        // Solve the resolvent cubic and take one real root
+        let z = cubic(1., -1. / 2. * p, -r, 1. / 2. * r * p - 1. / 8. * q * q)[0];
+
+        // Build two quadratic equations
+        let mut u = z * z - r;
+        let mut v = 2. * z - p;
+        if is_zero(u) {
+            u = 0.;
+        } else if u > 0. {
+            u = u.sqrt();
+        } else {
+            return vec![];
+        }
+        if is_zero(v) {
+            v = 0.;
+        } else if v > 0. {
+            v = v.sqrt();
+        } else {
+            return vec![];
+        }
+
+        let mut roots = quadratic(1., if q < 0. { -v } else { v }, z - u);
+        roots.push_all(quadratic(1., if q < 0. { v } else { -v }, z + u).as_slice());
+        roots
+    };
+
+    // Resubstitute
+    for root in roots.iter_mut() {
+        *root -= 1. / 4. * a;
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::Float;
+    use super::{ quadratic, cubic, quartic };
+
+    fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+        assert_eq!(roots.len(), expected.len());
+        for (root, want) in roots.iter().zip(expected.iter()) {
+            assert!((root - want).abs() <= 1e-9 * want.abs().max(1.), "{:?} instead of {:?}", roots, expected);
+        }
+    }
+
+    #[test]
+    fn quadratics() {
+        assert_roots(quadratic(1., -3., 2.), &[1., 2.]);
+        assert_roots(quadratic(-1., 3., -2.), &[1., 2.]);
+        assert_roots(quadratic(1., -2., 1.), &[1., 1.]);
+        assert_roots(quadratic(1., 0., 1.), &[]);
+        assert_roots(quadratic(0., 2., -4.), &[2.]);
+    }
+
+    #[test]
+    fn cubics() {
+        assert_roots(cubic(1., -6., 11., -6.), &[1., 2., 3.]);
+        assert_roots(cubic(-2., 12., -22., 12.), &[1., 2., 3.]);
+        assert_roots(cubic(1., 0., 0., -8.), &[2.]);
+        assert_roots(cubic(1., 0., 1., 0.), &[0.]);
+    }
+
+    #[test]
+    fn cubics_with_repeated_roots() {
+        // (x - 1)^2 (x - 2) and (x - 1)^3, each root once
+        assert_roots(cubic(1., -4., 5., -2.), &[1., 2.]);
+        assert_roots(cubic(1., -3., 3., -1.), &[1.]);
+    }
+
+    #[test]
+    fn quartics() {
+        assert_roots(quartic(1., -10., 35., -50., 24.), &[1., 2., 3., 4.]);
+        assert_roots(quartic(2., -20., 70., -100., 48.), &[1., 2., 3., 4.]);
+        assert_roots(quartic(1., 0., -5., 0., 4.), &[-2., -1., 1., 2.]);
+        assert_roots(quartic(1., -3., 3., -3., 2.), &[1., 2.]);
+        assert_roots(quartic(1., 0., 0., 0., 1.), &[]);
+    }
+
+    #[test]
+    fn quartics_with_repeated_roots() {
+        // (x - 1)^2 (x - 3)^2 and x^2 (x - 1) (x + 1)
+        assert_roots(quartic(1., -8., 22., -24., 9.), &[1., 1., 3., 3.]);
+        assert_roots(quartic(1., 0., -1., 0., 0.), &[-1., 0., 0., 1.]);
+    }
+
+    #[test]
+    fn degenerate_leading_coefficient() {
+        assert_roots(cubic(1e-12, 1., -3., 2.), &[1., 2.]);
+        assert_roots(quartic(1e-12, 1., -6., 11., -6.), &[1., 2., 3.]);
+    }
+
+    #[test]
+    fn near_degenerate_leading_coefficient() {
+        // A huge root, the others close to the roots of the lower degree
+        assert_roots(cubic(1e-6, 1., -3., 2.), &[-1000002.9999930001, 1.0000010000040001, 1.9999920000319997]);
+        assert_roots(quartic(1e-3, 1., -6., 11., -6.), &[-1005.9752371329367, 0.99950136952581181, 2.0165404210217779, 2.9591953423891391]);
+        assert_roots(quartic(1e-6, 1., -6., 11., -6.), &[-1000005.9999750003, 0.99999950000137494, 2.0000160005120264, 2.9999594997265953]);
+        assert_roots(quartic(1e-8, 1., -6., 11., -6.), &[-100000005.99999975, 0.99999999500000014, 2.000000160000051, 2.9999995949999727]);
+    }
+
+    #[test]
+    fn far_torus() {
+        // Coefficients of Torus::intersect, for a ray through the middle of a torus of radii 10 and 2,
+        // 5000 units away: the leading coefficient is small next to the others, but no root is huge
+        let (dist, major, minor) = (5000., 10., 2.);
+        let e = dist * dist - major * major - minor * minor;
+        let f = -dist;
+        let four_r2 = 4. * major * major;
+        let roots = quartic(1., 4. * f, 2. * e + 4. * f * f, 4. * f * e, e * e - four_r2 * minor * minor);
+        assert_roots(roots, &[4988., 4992., 5008., 5012.]);
+    }
+}
@@ -1,6 +1,8 @@
 use std::num::Float;
+use std::f64::consts::PI;
 use std::ops::{Add, Sub, Mul, Div};
 
+/// Point or direction in space
 #[derive(Copy)]
 pub struct Vec3 {
     pub x: f64,
@@ -13,10 +15,12 @@ impl Vec3 {
         Vec3 { x: x, y: y, z: z }
     }
 
+    /// Euclidean length
     pub fn length(&self) -> f64 {
         (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
     }
 
+    /// Same direction, of length 1
     pub fn normalize(&self) -> Vec3 {
         let len = self.length();
         Vec3::new(self.x / len, self.y / len, self.z / len)
@@ -75,6 +79,14 @@ pub fn dot(left: Vec3, right: Vec3) -> f64 {
     left.x * right.x + left.y * right.y + left.z * right.z
 }
 
+pub fn cross(left: Vec3, right: Vec3) -> Vec3 {
+    Vec3::new(
+        left.y * right.z - left.z * right.y,
+        left.z * right.x - left.x * right.z,
+        left.x * right.y - left.y * right.x,
+    )
+}
+
 pub fn rotate(vec: Vec3, dir: Vec3) -> Vec3 {
     let mut res = vec;
     if dir.x != 0. {
@@ -100,3 +112,57 @@ pub fn rotate(vec: Vec3, dir: Vec3) -> Vec3 {
     }
     res
 }
+
+// Inverse of rotate
+pub fn unrotate(vec: Vec3, dir: Vec3) -> Vec3 {
+    let mut res = vec;
+    if dir.z != 0. {
+        res = rotate(res, Vec3::new(0., 0., -dir.z));
+    }
+    if dir.y != 0. {
+        res = rotate(res, Vec3::new(0., -dir.y, 0.));
+    }
+    if dir.x != 0. {
+        res = rotate(res, Vec3::new(-dir.x, 0., 0.));
+    }
+    res
+}
+
+// Orthonormal frame whose y axis is `axis`
+#[derive(Copy)]
+pub struct Frame {
+    pub pos: Vec3,
+    pub u:   Vec3,
+    pub w:   Vec3,
+    pub v:   Vec3,
+}
+
+impl Frame {
+    pub fn new(pos: Vec3, axis: Vec3) -> Frame {
+        let w = axis.normalize();
+        let other = if w.x.abs() > 0.9 { Vec3::new(0., 1., 0.) } else { Vec3::new(1., 0., 0.) };
+        let u = cross(w, other).normalize();
+        let v = cross(u, w);
+        Frame { pos: pos, u: u, w: w, v: v }
+    }
+
+    pub fn to_local(&self, pos: Vec3) -> Vec3 {
+        self.to_local_dir(pos - self.pos)
+    }
+
+    pub fn to_local_dir(&self, dir: Vec3) -> Vec3 {
+        Vec3::new(dot(dir, self.u), dot(dir, self.w), dot(dir, self.v))
+    }
+
+    pub fn to_world_dir(&self, dir: Vec3) -> Vec3 {
+        self.u * dir.x + self.w * dir.y + self.v * dir.z
+    }
+}
+
+// Cosine weighted direction around `normal`, from two numbers in [0, 1[
+pub fn cosine_sample(normal: Vec3, u1: f64, u2: f64) -> Vec3 {
+    let frame = Frame::new(Vec3::new(0., 0., 0.), normal);
+    let r = u1.sqrt();
+    let phi = 2. * PI * u2;
+    frame.to_world_dir(Vec3::new(r * phi.cos(), (1. - u1).sqrt(), r * phi.sin()))
+}
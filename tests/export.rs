@@ -0,0 +1,81 @@
+// Every scene of scene/ is exported, parsed again, validated and loaded back
+// Exporting the loaded export gives the same scene, nothing is lost
+
+#![feature(old_io)]
+#![feature(old_path)]
+
+extern crate "rustc-serialize" as serialize;
+extern crate rt;
+
+use std::old_io::fs::{ self, File };
+use serialize::json::Json;
+use rt::{ config, schema, export };
+use rt::schema::Problem;
+use rt::format::Format;
+
+// Scene files, in every format
+fn scenes() -> Vec<Path> {
+    let mut paths: Vec<Path> = fs::readdir(&Path::new("scene")).unwrap().into_iter().filter(|path| {
+        match path.extension_str() {
+            Some("json") | Some("toml") | Some("yaml") | Some("ron") => true,
+            _                                                        => false,
+        }
+    }).collect();
+    paths.sort();
+    paths
+}
+
+// Prepared, None for files that are only included, without picture
+fn prepare(path: &Path) -> Option<Json> {
+    let input = File::open(path).read_to_string().unwrap();
+    let root = Format::from_path(path).parse(input.as_slice()).unwrap();
+    if root.find("picture").is_none() {
+        return None;
+    }
+    let (root, problems) = config::prepare(&root, &path.dir_path(), &[]).unwrap();
+    assert_valid(format!("{}", path.display()).as_slice(), problems.as_slice());
+    Some(root)
+}
+
+// Equal, numbers up to the rounding of normalized vectors and angles
+fn same(left: &Json, right: &Json) -> bool {
+    match (left, right) {
+        (&Json::Object(ref l), &Json::Object(ref r)) => {
+            l.len() == r.len() && l.iter().zip(r.iter()).all(|((lk, lv), (rk, rv))| lk == rk && same(lv, rv))
+        },
+        (&Json::Array(ref l), &Json::Array(ref r))   => l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| same(l, r)),
+        _ if left.is_number() && right.is_number()   => {
+            let (l, r) = (left.as_f64().unwrap(), right.as_f64().unwrap());
+            (l - r).abs() <= 1e-12 * l.abs().max(r.abs()).max(1.)
+        },
+        _                                            => left == right,
+    }
+}
+
+fn assert_valid(name: &str, problems: &[Problem]) {
+    let errors: Vec<String> = problems.iter()
+        .filter(|problem| problem.error)
+        .map(|problem| format!("{}", problem))
+        .collect();
+    assert!(errors.is_empty(), "{}: {}", name, errors.connect(", "));
+}
+
+#[test]
+fn export_round_trip() {
+    for path in scenes().iter() {
+        let name = format!("{}", path.display());
+        let root = match prepare(path) {
+            Some(root) => root,
+            None       => continue,
+        };
+        let (eye, scene, picture) = config::load(&root);
+
+        let first = export::export(&eye, &scene, &picture);
+        let exported = Json::from_str(first.to_string().as_slice()).unwrap();
+        assert_valid(format!("{} exported", name).as_slice(), schema::check(&exported).as_slice());
+
+        let (eye, scene, picture) = config::load(&first);
+        let second = export::export(&eye, &scene, &picture);
+        assert!(same(&first, &second), "{} changed when exported again:\n{}\n{}", name, first.pretty(), second.pretty());
+    }
+}